@@ -0,0 +1,28 @@
+// ブルーム: 周りの明るいところをにじませて足す
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var t: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t));
+    let color = textureSample(t, s, in.uv);
+    // 周りの5x5(3ピクセルおき)のうち、明るさが閾値を超えた分だけを集める
+    var glow = vec3<f32>(0.0, 0.0, 0.0);
+    for (var x = -2; x <= 2; x = x + 1) {
+        for (var y = -2; y <= 2; y = y + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel * 3.0;
+            let sample = textureSample(t, s, in.uv + offset).rgb;
+            glow = glow + max(sample - vec3<f32>(0.6, 0.6, 0.6), vec3<f32>(0.0, 0.0, 0.0));
+        }
+    }
+    return vec4<f32>(color.rgb + glow * 0.12, 1.0) * in.color;
+}
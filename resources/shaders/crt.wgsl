@@ -0,0 +1,27 @@
+// ブラウン管: 画面の端ほど外に膨らませ、走査線と周辺の暗さを加える
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var t: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // 中心が(0, 0)、端が±1の座標にして、中心から遠いほど外側を読む
+    let centered = in.uv * 2.0 - vec2<f32>(1.0, 1.0);
+    let curved = centered * (1.0 + 0.06 * dot(centered, centered));
+    let uv = curved * 0.5 + vec2<f32>(0.5, 0.5);
+    let color = textureSample(t, s, uv);
+    // 膨らませて画面の外になったところは黒
+    let inside = all(uv >= vec2<f32>(0.0, 0.0)) && all(uv <= vec2<f32>(1.0, 1.0));
+    let scanline = select(1.0, 0.75, u32(in.position.y) % 3u == 0u);
+    let vignette = 1.0 - 0.25 * dot(centered, centered);
+    let rgb = select(vec3<f32>(0.0, 0.0, 0.0), color.rgb * scanline * vignette, inside);
+    return vec4<f32>(rgb, 1.0) * in.color;
+}
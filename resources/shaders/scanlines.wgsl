@@ -0,0 +1,19 @@
+// 走査線: 3ピクセルごとに暗い行を入れる
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var t: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t, s, in.uv);
+    let scanline = select(1.0, 0.7, u32(in.position.y) % 3u == 0u);
+    return vec4<f32>(color.rgb * scanline, 1.0) * in.color;
+}
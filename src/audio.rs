@@ -0,0 +1,185 @@
+use ggez::{
+    audio::{self, SoundSource},
+    Context,
+};
+
+use crate::config::SOUNDS_DIR;
+use crate::event::GameEvent;
+use crate::food::FoodKind;
+use crate::settings::Settings;
+
+// 効果音の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    // foodを食べた
+    Eat,
+    // ボーナスかゴールドのfoodを食べた
+    Bonus,
+    // 方向を変えた
+    Turn,
+    // 死んだ
+    Die,
+}
+
+impl Sound {
+    // リソースのファイル名(拡張子なし)
+    pub fn file_stem(self) -> &'static str {
+        match self {
+            Sound::Eat => "eat",
+            Sound::Bonus => "bonus",
+            Sound::Turn => "turn",
+            Sound::Die => "die",
+        }
+    }
+
+    // できごとで鳴らす効果音(ボーナスとゴールドのfoodは別の音)
+    pub fn for_event(event: &GameEvent) -> Option<Sound> {
+        match *event {
+            GameEvent::FoodEaten { kind, bonus, .. } => {
+                Some(if bonus || kind == FoodKind::Golden {
+                    Sound::Bonus
+                } else {
+                    Sound::Eat
+                })
+            }
+            GameEvent::SnakeDied { .. } => Some(Sound::Die),
+            GameEvent::PowerUpCollected { .. }
+            | GameEvent::SnakeTurned { .. }
+            | GameEvent::SnakeWrapped { .. }
+            | GameEvent::LevelCompleted { .. } => None,
+        }
+    }
+}
+
+// BGMの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Music {
+    // タイトル画面とエディタ
+    Menu,
+    // プレイ中
+    Game,
+}
+
+impl Music {
+    // リソースのファイル名(拡張子なし)
+    pub fn file_stem(self) -> &'static str {
+        match self {
+            Music::Menu => "music_menu",
+            Music::Game => "music_game",
+        }
+    }
+}
+
+// 効果音とBGMを読み込んで鳴らす
+// 読み込めなかった(音声デバイスがない・ファイルがない)音は鳴らさない
+#[derive(Default)]
+pub struct AudioManager {
+    eat: Option<audio::Source>,
+    bonus: Option<audio::Source>,
+    turn: Option<audio::Source>,
+    die: Option<audio::Source>,
+    menu_music: Option<audio::Source>,
+    game_music: Option<audio::Source>,
+    // 今流れているBGM
+    music: Option<Music>,
+    // 鳴らす時の音量(0.0〜1.0)
+    music_gain: f32,
+    sfx_gain: f32,
+}
+
+impl AudioManager {
+    pub fn load(ctx: &Context, settings: &Settings) -> Self {
+        let mut menu_music = Self::load_source(ctx, Music::Menu.file_stem());
+        let mut game_music = Self::load_source(ctx, Music::Game.file_stem());
+        // BGMは最後まで流れたら最初からもう一度
+        for source in [&mut menu_music, &mut game_music].into_iter().flatten() {
+            source.set_repeat(true);
+        }
+        AudioManager {
+            eat: Self::load_source(ctx, Sound::Eat.file_stem()),
+            bonus: Self::load_source(ctx, Sound::Bonus.file_stem()),
+            turn: Self::load_source(ctx, Sound::Turn.file_stem()),
+            die: Self::load_source(ctx, Sound::Die.file_stem()),
+            menu_music,
+            game_music,
+            music: None,
+            music_gain: settings.music_gain(),
+            sfx_gain: settings.sfx_gain(),
+        }
+    }
+
+    // 設定の音量を反映する(流れているBGMにもすぐ反映)
+    pub fn set_volume(&mut self, settings: &Settings) {
+        self.music_gain = settings.music_gain();
+        self.sfx_gain = settings.sfx_gain();
+        let gain = self.music_gain;
+        if let Some(current) = self.music {
+            if let Some(source) = self.music_source(current) {
+                source.set_volume(gain);
+            }
+        }
+    }
+
+    // /sounds/<名前>.oggか/sounds/<名前>.wavを読み込む
+    fn load_source(ctx: &Context, stem: &str) -> Option<audio::Source> {
+        let ogg = format!("{}/{}.ogg", SOUNDS_DIR, stem);
+        let wav = format!("{}/{}.wav", SOUNDS_DIR, stem);
+        let path = if ctx.fs.exists(&ogg) { ogg } else { wav };
+        match audio::Source::new(ctx, &path) {
+            Ok(source) => Some(source),
+            Err(e) => {
+                eprintln!("Could not load {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn music_source(&mut self, music: Music) -> &mut Option<audio::Source> {
+        match music {
+            Music::Menu => &mut self.menu_music,
+            Music::Game => &mut self.game_music,
+        }
+    }
+
+    // BGMを切り替える(Noneなら止める)
+    // 今と同じBGMなら何もしないので、毎フレーム呼んでも最初からにはならない
+    pub fn set_music(&mut self, ctx: &Context, music: Option<Music>) {
+        if self.music == music {
+            return;
+        }
+        if let Some(current) = self.music {
+            if let Some(source) = self.music_source(current) {
+                if let Err(e) = source.stop(ctx) {
+                    eprintln!("Could not stop {}: {}", current.file_stem(), e);
+                }
+            }
+        }
+        let gain = self.music_gain;
+        if let Some(next) = music {
+            if let Some(source) = self.music_source(next) {
+                source.set_volume(gain);
+                if let Err(e) = source.play(ctx) {
+                    eprintln!("Could not play {}: {}", next.file_stem(), e);
+                }
+            }
+        }
+        self.music = music;
+    }
+
+    // 効果音を鳴らす(同じ音が重なっても最後まで鳴らす)
+    pub fn play(&mut self, ctx: &Context, sound: Sound) {
+        let gain = self.sfx_gain;
+        let source = match sound {
+            Sound::Eat => &mut self.eat,
+            Sound::Bonus => &mut self.bonus,
+            Sound::Turn => &mut self.turn,
+            Sound::Die => &mut self.die,
+        };
+        if let Some(source) = source {
+            source.set_volume(gain);
+            if let Err(e) = source.play_detached(ctx) {
+                eprintln!("Could not play {}: {}", sound.file_stem(), e);
+            }
+        }
+    }
+}
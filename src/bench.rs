@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use ggez::{event::EventHandler, Context, GameResult};
+
+use crate::game_state::GameState;
+use crate::settings::{Difficulty, Settings};
+use crate::simulation::Simulation;
+
+// --benchで測った結果(描画の最適化の前後で比べるための目安)
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    // Simulationで進めたtick数と、stepだけにかかった時間(ゲームの準備は含めない)
+    pub ticks: u64,
+    pub tick_time: Duration,
+    // 1フレームずつの描画(updateを除くdrawとウィンドウへの転送)にかかった時間
+    pub frame_times: Vec<Duration>,
+    // 1フレームでまとめて描画したマスの四角形の数(一番多かったフレーム)
+    pub batched_cells: usize,
+}
+
+impl BenchReport {
+    // ウィンドウなしでSimulationを自動操作でticks回進める
+    // 途中でゲームが終わったら次のシードで始め直す(同じseedなら毎回同じゲームの並び)
+    pub fn run_simulation(&mut self, seed: u64, ticks: u64) {
+        let mut game = 0;
+        while self.ticks < ticks {
+            let settings = Settings {
+                seed: Some(seed.wrapping_add(game)),
+                ..Settings::default()
+            };
+            let mut simulation = Simulation::new(settings, Difficulty::NORMAL, 0);
+            simulation.autopilot();
+            // 終わったtickも1回と数える(すぐ終わるゲームばかりでも止まらないように)
+            let start = Instant::now();
+            while self.ticks < ticks {
+                self.ticks += 1;
+                if !simulation.step(None) {
+                    break;
+                }
+            }
+            self.tick_time += start.elapsed();
+            game += 1;
+        }
+    }
+
+    // 見えないウィンドウにデモをframesフレーム描画する(ggezのイベントループと同じ順に呼ぶ)
+    pub fn run_renderer(
+        &mut self,
+        ctx: &mut Context,
+        state: &mut GameState,
+        frames: u32,
+    ) -> GameResult {
+        state.start_demo();
+        for _ in 0..frames {
+            ctx.time.tick();
+            state.update(ctx)?;
+            let start = Instant::now();
+            ctx.gfx.begin_frame()?;
+            state.draw(ctx)?;
+            ctx.gfx.end_frame()?;
+            self.frame_times.push(start.elapsed());
+            self.batched_cells = self.batched_cells.max(state.batched_cells());
+        }
+        Ok(())
+    }
+
+    // 表示する結果
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Simulation: {} ticks in {:.2}s ({:.0} ticks/s)",
+            self.ticks,
+            self.tick_time.as_secs_f64(),
+            self.ticks as f64 / self.tick_time.as_secs_f64().max(f64::EPSILON)
+        )];
+        if !self.frame_times.is_empty() {
+            let mut times: Vec<f64> = self
+                .frame_times
+                .iter()
+                .map(|time| time.as_secs_f64() * 1000.0)
+                .collect();
+            times.sort_by(f64::total_cmp);
+            let average = times.iter().sum::<f64>() / times.len() as f64;
+            let p95 = times[(times.len() - 1) * 95 / 100];
+            lines.push(format!(
+                "Renderer: {} frames  avg {:.2}ms  p95 {:.2}ms  max {:.2}ms",
+                times.len(),
+                average,
+                p95,
+                times[times.len() - 1]
+            ));
+            lines.push(format!("Batched cells: {} per frame", self.batched_cells));
+        }
+        lines
+    }
+}
@@ -0,0 +1,215 @@
+use crate::config::{CHALLENGE_FOOD_COUNT, CHALLENGE_FOOD_SECONDS, CHALLENGE_LENGTH};
+use crate::event::GameEvent;
+use crate::grid::Direction;
+
+// 目標の状態
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChallengeStatus {
+    // まだ決まっていない
+    InProgress,
+    Completed,
+    Failed,
+}
+
+// 目標を調べる時のゲームの様子(1人目の蛇について)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChallengeContext {
+    // 蛇の長さ
+    pub length: usize,
+    // 蛇が動き出してからの秒数
+    pub seconds: f64,
+}
+
+// 1回のゲームで達成を目指す目標
+// GameStateができごとを配る時に一緒に受け取り、1人目の蛇について進み具合を更新する
+// 一度達成か失敗に決まったらそのゲームの間は変わらない
+pub trait Challenge {
+    // 目標の説明
+    fn description(&self) -> String;
+
+    // 今の状態
+    fn status(&self) -> ChallengeStatus;
+
+    // まだ決まっていない間に表示する進み具合("3/10"など)
+    fn progress(&self) -> String;
+
+    // できごとを1つ受け取る(ゲームの終わりはLevelCompletedで分かる)
+    fn on_event(&mut self, event: &GameEvent, context: &ChallengeContext);
+
+    // できごとがなくても、できごとを配るたびに呼ばれる(時間や長さで決まる目標)
+    fn update(&mut self, _context: &ChallengeContext) {}
+
+    // 結果の画面に表示する1行
+    fn text(&self) -> String {
+        match self.status() {
+            ChallengeStatus::InProgress => {
+                format!("{}  {}", self.description(), self.progress())
+            }
+            ChallengeStatus::Completed => format!("{}  Completed", self.description()),
+            ChallengeStatus::Failed => format!("{}  Failed", self.description()),
+        }
+    }
+}
+
+// 1回のゲームで挑戦する目標の一覧
+pub fn all() -> Vec<Box<dyn Challenge>> {
+    vec![
+        Box::new(LengthWithoutWrapping::default()),
+        Box::new(FoodInTime::default()),
+        Box::new(NeverPress::new(Direction::Left)),
+    ]
+}
+
+// 画面端を越えずにCHALLENGE_LENGTHの長さになる
+#[derive(Debug)]
+pub struct LengthWithoutWrapping {
+    length: usize,
+    status: ChallengeStatus,
+}
+
+impl Default for LengthWithoutWrapping {
+    fn default() -> Self {
+        LengthWithoutWrapping {
+            length: 0,
+            status: ChallengeStatus::InProgress,
+        }
+    }
+}
+
+impl Challenge for LengthWithoutWrapping {
+    fn description(&self) -> String {
+        format!("Reach length {} without wrapping", CHALLENGE_LENGTH)
+    }
+
+    fn status(&self) -> ChallengeStatus {
+        self.status
+    }
+
+    fn progress(&self) -> String {
+        format!("{}/{}", self.length, CHALLENGE_LENGTH)
+    }
+
+    fn on_event(&mut self, event: &GameEvent, _context: &ChallengeContext) {
+        if self.status != ChallengeStatus::InProgress {
+            return;
+        }
+        match event {
+            GameEvent::SnakeWrapped { snake: 0 } | GameEvent::LevelCompleted { .. } => {
+                self.status = ChallengeStatus::Failed
+            }
+            _ => (),
+        }
+    }
+
+    fn update(&mut self, context: &ChallengeContext) {
+        if self.status != ChallengeStatus::InProgress {
+            return;
+        }
+        self.length = context.length;
+        if self.length >= CHALLENGE_LENGTH {
+            self.status = ChallengeStatus::Completed;
+        }
+    }
+}
+
+// CHALLENGE_FOOD_SECONDS秒以内にCHALLENGE_FOOD_COUNT個のfoodを食べる
+#[derive(Debug)]
+pub struct FoodInTime {
+    eaten: u32,
+    status: ChallengeStatus,
+}
+
+impl Default for FoodInTime {
+    fn default() -> Self {
+        FoodInTime {
+            eaten: 0,
+            status: ChallengeStatus::InProgress,
+        }
+    }
+}
+
+impl Challenge for FoodInTime {
+    fn description(&self) -> String {
+        format!(
+            "Eat {} food in {} seconds",
+            CHALLENGE_FOOD_COUNT, CHALLENGE_FOOD_SECONDS
+        )
+    }
+
+    fn status(&self) -> ChallengeStatus {
+        self.status
+    }
+
+    fn progress(&self) -> String {
+        format!("{}/{}", self.eaten, CHALLENGE_FOOD_COUNT)
+    }
+
+    fn on_event(&mut self, event: &GameEvent, context: &ChallengeContext) {
+        if self.status != ChallengeStatus::InProgress {
+            return;
+        }
+        match event {
+            GameEvent::FoodEaten { snake: 0, .. } if context.seconds > CHALLENGE_FOOD_SECONDS => {
+                self.status = ChallengeStatus::Failed
+            }
+            GameEvent::FoodEaten { snake: 0, .. } => {
+                self.eaten += 1;
+                if self.eaten >= CHALLENGE_FOOD_COUNT {
+                    self.status = ChallengeStatus::Completed;
+                }
+            }
+            GameEvent::LevelCompleted { .. } => self.status = ChallengeStatus::Failed,
+            _ => (),
+        }
+    }
+
+    fn update(&mut self, context: &ChallengeContext) {
+        if self.status == ChallengeStatus::InProgress && context.seconds > CHALLENGE_FOOD_SECONDS {
+            self.status = ChallengeStatus::Failed;
+        }
+    }
+}
+
+// ゲームの終わりまで一度もdirの方向キーを押さない
+#[derive(Debug)]
+pub struct NeverPress {
+    dir: Direction,
+    status: ChallengeStatus,
+}
+
+impl NeverPress {
+    pub fn new(dir: Direction) -> Self {
+        NeverPress {
+            dir,
+            status: ChallengeStatus::InProgress,
+        }
+    }
+}
+
+impl Challenge for NeverPress {
+    fn description(&self) -> String {
+        format!("Never press {:?}", self.dir)
+    }
+
+    fn status(&self) -> ChallengeStatus {
+        self.status
+    }
+
+    fn progress(&self) -> String {
+        "OK".to_string()
+    }
+
+    fn on_event(&mut self, event: &GameEvent, _context: &ChallengeContext) {
+        if self.status != ChallengeStatus::InProgress {
+            return;
+        }
+        match *event {
+            GameEvent::SnakeTurned { snake: 0, dir } if dir == self.dir => {
+                self.status = ChallengeStatus::Failed
+            }
+            // 最後まで押さなければ達成
+            GameEvent::LevelCompleted { .. } => self.status = ChallengeStatus::Completed,
+            _ => (),
+        }
+    }
+}
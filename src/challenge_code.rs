@@ -0,0 +1,174 @@
+use crate::mode::GameMode;
+
+// コードに使う32文字(見間違えやすいI, L, O, Uを使わないCrockfordのBase32)
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+// シード8バイト、モード・難易度・ステージ・キャンペーン1バイトずつ、グリッドの幅と高さ2バイトずつ、
+// foodの数・残機・updateの回数の上限1バイトずつ、チェックサム1バイト
+const BYTES: usize = 20;
+// 何文字ごとに「-」で区切って表示するか
+const GROUP: usize = 5;
+
+// 友達と同じ盤面とfoodの並びで遊ぶためのコード(「7ZK3M-...」)
+// ゲームオーバーの画面に表示され、タイトル画面のFで入力するとその設定で始まる
+// 相手の手元にないステージは遊べないので、組み込みのステージのゲームだけコードにする
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChallengeCode {
+    pub seed: u64,
+    pub mode: GameMode,
+    // Difficulty::ALLの何番目か
+    pub difficulty: u8,
+    // 組み込みのステージ(Level::builtin)の何番目か
+    pub level: u8,
+    // キャンペーンのゲームか(levelは最初のステージ)
+    pub campaign: bool,
+    // グリッドのマス数(違う大きさのグリッドでは遊べない)
+    pub grid_size: (u16, u16),
+    pub food_count: u8,
+    pub lives: u8,
+    pub max_tick_rate: u8,
+}
+
+impl ChallengeCode {
+    // 5文字ずつ区切ったコード
+    pub fn encode(&self) -> String {
+        let mode = GameMode::ALL
+            .iter()
+            .position(|m| *m == self.mode)
+            .unwrap_or(0);
+        let mut bytes = self.seed.to_be_bytes().to_vec();
+        bytes.extend([mode as u8, self.difficulty, self.level, self.campaign as u8]);
+        bytes.extend(self.grid_size.0.to_be_bytes());
+        bytes.extend(self.grid_size.1.to_be_bytes());
+        bytes.extend([self.food_count, self.lives, self.max_tick_rate]);
+        bytes.push(checksum(&bytes));
+        // 上位のビットから5ビットずつ1文字にする
+        let mut chars = Vec::new();
+        let (mut acc, mut bits) = (0u32, 0);
+        for byte in bytes {
+            acc = (acc << 8) | byte as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                chars.push(ALPHABET[(acc >> bits) as usize & 31] as char);
+            }
+            acc &= (1 << bits) - 1;
+        }
+        if bits > 0 {
+            chars.push(ALPHABET[(acc << (5 - bits)) as usize & 31] as char);
+        }
+        chars
+            .chunks(GROUP)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    // 入力されたコードを読む(区切りと空白は無視し、小文字やO, I, Lも数字として読む)
+    pub fn decode(code: &str) -> Result<Self, String> {
+        let mut bytes = Vec::new();
+        let (mut acc, mut bits) = (0u32, 0);
+        for c in code.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+            let c = match c.to_ascii_uppercase() {
+                'O' => '0',
+                'I' | 'L' => '1',
+                c => c,
+            };
+            let value = ALPHABET
+                .iter()
+                .position(|a| *a as char == c)
+                .ok_or_else(|| format!("invalid character: {}", c))?;
+            acc = (acc << 5) | value as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((acc >> bits) as u8);
+                acc &= (1 << bits) - 1;
+            }
+        }
+        if bytes.len() != BYTES {
+            return Err("wrong length".to_string());
+        }
+        let (body, check) = bytes.split_at(BYTES - 1);
+        if checksum(body) != check[0] {
+            return Err("mistyped code".to_string());
+        }
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&body[..8]);
+        let mode = *GameMode::ALL.get(body[8] as usize).ok_or("unknown mode")?;
+        let campaign = match body[11] {
+            0 => false,
+            1 => true,
+            _ => return Err("mistyped code".to_string()),
+        };
+        Ok(ChallengeCode {
+            seed: u64::from_be_bytes(seed),
+            mode,
+            difficulty: body[9],
+            level: body[10],
+            campaign,
+            grid_size: (
+                u16::from_be_bytes([body[12], body[13]]),
+                u16::from_be_bytes([body[14], body[15]]),
+            ),
+            food_count: body[16],
+            lives: body[17],
+            max_tick_rate: body[18],
+        })
+    }
+}
+
+// 打ち間違いに気付くためのチェックサム
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0x5a, |acc: u8, byte| acc.rotate_left(3) ^ byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code() -> ChallengeCode {
+        ChallengeCode {
+            seed: 0x0123_4567_89ab_cdef,
+            mode: GameMode::ALL[GameMode::ALL.len() - 1],
+            difficulty: 2,
+            level: 3,
+            campaign: true,
+            grid_size: (300, 40),
+            food_count: 4,
+            lives: 255,
+            max_tick_rate: 20,
+        }
+    }
+
+    #[test]
+    fn decode_returns_the_encoded_settings() {
+        let text = code().encode();
+        assert_eq!(ChallengeCode::decode(&text), Ok(code()));
+        // 区切りと大文字小文字、見間違えやすい文字は気にしない
+        let typed = text.replace('-', " ").replace('0', "o").to_lowercase();
+        assert_eq!(ChallengeCode::decode(&typed), Ok(code()));
+    }
+
+    #[test]
+    fn decode_rejects_mistyped_codes() {
+        let text = code().encode();
+        let last = text.chars().last().unwrap();
+        let wrong = if last == '0' { '1' } else { '0' };
+        let mistyped = format!("{}{}", &text[..text.len() - 1], wrong);
+        assert!(ChallengeCode::decode(&mistyped).is_err());
+        assert!(ChallengeCode::decode(&text[..text.len() - 2]).is_err());
+        assert!(ChallengeCode::decode(&text.replace('-', "U")).is_err());
+    }
+
+    #[test]
+    fn every_byte_changes_the_checksum() {
+        let bytes = [7u8; BYTES - 1];
+        for i in 0..bytes.len() {
+            let mut changed = bytes;
+            changed[i] ^= 1;
+            assert_ne!(checksum(&changed), checksum(&bytes));
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use serde::{Deserialize, Serialize};
+
+use ggez::GameResult;
+
+use std::path::Path;
+use std::sync::OnceLock;
+
+// config.tomlがない場合のグリッドのマス数
+pub const DEFAULT_GRID_SIZE: (i16, i16) = (40, 30);
+// Now we define the pixel size of each tile, which we make 32x32 pixels.
+pub const DEFAULT_GRID_CELL_SIZE: (i16, i16) = (42, 42);
+// 1秒間にupdateが呼ばれる回数(Normalの難易度のゲーム開始時)
+pub const DEFAULT_FPS: u32 = 8;
+// グリッドのマス数とマスの大きさ(ピクセル)の下限
+pub const MIN_GRID_SIZE: i16 = 10;
+pub const MIN_GRID_CELL_SIZE: i16 = 4;
+// グリッドなどの設定ファイル(ユーザーの設定ディレクトリ直下)
+pub const CONFIG_FILE: &str = "config.toml";
+// 何個foodを食べるごとにupdateの回数を1増やすか
+pub const SPEEDUP_FOOD_COUNT: u32 = 5;
+// updateの回数の上限のデフォルト値
+pub const DEFAULT_MAX_TICK_RATE: u32 = 20;
+// +/-キーで速さを変えられる範囲(難易度のupdateの回数からの増減)と、遅くしても下回らない回数
+pub const MAX_SPEED_ADJUST: i32 = 10;
+pub const MIN_TICK_RATE: u32 = 2;
+// 同時に置くfoodの個数のデフォルト値
+pub const DEFAULT_FOOD_COUNT: usize = 1;
+// 残機の数のデフォルト値
+pub const DEFAULT_LIVES: u32 = 3;
+// ゴールドのfoodのスコアの倍率
+pub const GOLDEN_SCORE_MULTIPLIER: u32 = 5;
+// 動き回るfoodのスコアの倍率
+pub const WANDERING_SCORE_MULTIPLIER: u32 = 3;
+// 動き回るfoodが1歩動く間隔(tick数)
+pub const WANDER_INTERVAL: u32 = 3;
+// 蛇の頭がこの距離以内に来ると動き回るfoodが逃げる
+pub const FLEE_DISTANCE: i16 = 5;
+// 前のfoodからこのtick数以内に次のfoodを食べるとコンボが続く
+pub const COMBO_TICKS: u32 = 40;
+// コンボによるスコアの倍率の上限
+pub const MAX_COMBO_MULTIPLIER: u32 = 5;
+// 1tickの間に溜めておけるキー入力の数
+pub const INPUT_QUEUE_SIZE: usize = 3;
+// 毒のfoodで縮むセグメント数
+pub const POISON_SEGMENTS: usize = 3;
+// 混乱のfoodで上下左右の入力が逆になるtick数
+pub const CONFUSION_TICKS: u32 = 40;
+// ボーナスのfoodが出る確率(1tickあたり1/BONUS_CHANCE)
+pub const BONUS_CHANCE: u32 = 80;
+// ボーナスのfoodが消えるまでの秒数
+pub const BONUS_SECONDS: u32 = 5;
+// ボーナスのfoodのvalue
+pub const BONUS_VALUE: u16 = 3;
+// パワーアップが出る確率(1tickあたり1/POWER_UP_CHANCE)
+pub const POWER_UP_CHANCE: u32 = 200;
+
+// ウィンドウタイトルと作者名のデフォルト値
+pub const DEFAULT_TITLE: &str = "Snake!";
+pub const DEFAULT_AUTHOR: &str = "Gray Olson";
+
+// 設定ファイルのパス(ユーザー設定ディレクトリ内)
+pub const SETTINGS_PATH: &str = "/settings.toml";
+
+// ステージファイルを置くディレクトリ(リソースディレクトリ内)
+pub const LEVELS_DIR: &str = "/levels";
+// ゲームのルールを変えるスクリプトを置くディレクトリ(リソースディレクトリ内)
+pub const SCRIPTS_DIR: &str = "/scripts";
+// スクリプトが1回のフックで実行できる命令の数の上限(無限ループでゲームが止まらないように)
+pub const SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+// エディタで保存するステージの名前
+pub const CUSTOM_LEVEL_NAME: &str = "custom";
+
+// ハイスコアファイルの名前(ユーザーデータディレクトリ内)
+pub const HIGH_SCORES_FILE: &str = "highscores.toml";
+// チャレンジコードの入力欄に入る最大の文字数
+pub const CODE_INPUT_LENGTH: usize = 32;
+// ハイスコアの名前の最大の文字数
+pub const NAME_LENGTH: usize = 12;
+// 最後に遊んだゲームのリプレイのファイル名(ハイスコアと同じディレクトリ)
+pub const REPLAY_FILE: &str = "replay.toml";
+// モードごとの一番スコアの高いゲームのリプレイを置くディレクトリ(ハイスコアと同じディレクトリ内)
+pub const GHOSTS_DIR: &str = "ghosts";
+// 自分の一番いいゲームを再生する半透明の蛇の不透明度
+pub const GHOST_ALPHA: f32 = 0.3;
+// ゲームの最後の数秒を書き出したGIFを置くディレクトリ(ハイスコアと同じディレクトリ内)
+pub const GIFS_DIR: &str = "gifs";
+// GIFに残す秒数と1秒あたりのコマ数、GIFの幅(ピクセル。ウィンドウより小さく縮小する)
+pub const GIF_SECONDS: f32 = 10.0;
+pub const GIF_FPS: u32 = 10;
+pub const GIF_WIDTH: u32 = 320;
+// 中断したゲームのセーブデータのファイル名(ハイスコアと同じディレクトリ)
+pub const SAVE_FILE: &str = "save.toml";
+// 通算成績のファイル名(ハイスコアと同じディレクトリ)
+pub const STATS_FILE: &str = "stats.toml";
+// 実績のファイル名(ハイスコアと同じディレクトリ)
+pub const ACHIEVEMENTS_FILE: &str = "achievements.toml";
+// 蛇とfoodのテクスチャを置くディレクトリ(リソースのルートから)
+pub const SPRITES_DIR: &str = "/sprites";
+// 画面全体にかけるシェーダーを置くディレクトリ(リソースのルートから)
+pub const SHADERS_DIR: &str = "/shaders";
+// 効果音を置くディレクトリ(リソースのルートから)
+pub const SOUNDS_DIR: &str = "/sounds";
+// F12で撮ったスクリーンショットを保存するディレクトリ(ユーザー設定ディレクトリ内)
+pub const SCREENSHOTS_DIR: &str = "/screenshots";
+// ハイスコアとして残す件数
+pub const HIGH_SCORE_COUNT: usize = 10;
+
+// 左スティックの入力を無視する範囲(-1.0〜1.0のうち)
+pub const STICK_DEAD_ZONE: f32 = 0.5;
+// スワイプとみなす指やマウスの最小の移動距離(ピクセル)
+pub const SWIPE_MIN_DISTANCE: f32 = 30.0;
+
+// タイトル画面で何も押さずにこの秒数が経つとデモを始める
+pub const DEMO_IDLE_SECONDS: u32 = 15;
+
+// ハイビジビリティモードの縁取りの色と太さ(ピクセル)
+pub const OUTLINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const OUTLINE_WIDTH: f32 = 4.0;
+
+// 盤面の背景のグリッド線の色と太さ(ピクセル)、市松模様の濃い方のマスの色
+pub const GRID_LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.08];
+pub const GRID_LINE_WIDTH: f32 = 1.0;
+pub const CHECKER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.04];
+
+// foodを食べた時に飛び散るパーティクルの数、消えるまでの秒数、大きさ(ピクセル)、速さ(ピクセル/秒)
+pub const PARTICLE_COUNT: usize = 12;
+pub const PARTICLE_SECONDS: f32 = 0.5;
+pub const PARTICLE_SIZE: f32 = 10.0;
+pub const PARTICLE_SPEED: f32 = 150.0;
+
+// 自分にぶつかった時に画面が揺れて赤く光る秒数と、揺れの大きさ(ピクセル)
+pub const DEATH_EFFECT_SECONDS: f32 = 0.5;
+pub const SHAKE_PIXELS: f32 = 12.0;
+
+// ゲームオーバーで蛇が尻尾から消えていく秒数と、その間に数えるtick数
+pub const DYING_SECONDS: f32 = 1.0;
+pub const DYING_TICKS: u32 = 20;
+
+// ゲームを始めた時と一時停止から再開した時に、蛇が動き出すまで数える秒数
+pub const COUNTDOWN_SECONDS: f32 = 3.0;
+
+// 画面上のマスの大きさ(ピクセル)がこれより小さくなるとミニマップを表示する
+pub const MINIMAP_MIN_CELL_PIXELS: f32 = 10.0;
+// ミニマップの幅と高さの上限(画面に対する割合)と、画面の端からの余白(ピクセル)、背景の色
+pub const MINIMAP_SIZE: f32 = 0.2;
+pub const MINIMAP_MARGIN: f32 = 10.0;
+pub const MINIMAP_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+// ミニマップでカメラが映している範囲の色
+pub const MINIMAP_VIEW_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+
+// キャンペーンで次のステージに進むまでに食べるfoodの個数と、ステージごとに増えるupdateの回数
+pub const CAMPAIGN_FOOD_COUNT: u32 = 10;
+pub const CAMPAIGN_SPEEDUP: u32 = 2;
+
+// タイムアタックの制限時間と、foodを1個食べるごとに増える秒数
+pub const TIME_ATTACK_SECONDS: f32 = 120.0;
+pub const TIME_ATTACK_BONUS_SECONDS: f32 = 3.0;
+
+// サバイバルで盤面が外側から1周縮む間隔(秒)と、縮むのを予告する秒数、これ以上は縮まない大きさ(マス数)
+pub const SURVIVAL_SHRINK_SECONDS: f32 = 15.0;
+pub const SURVIVAL_WARNING_SECONDS: f32 = 3.0;
+pub const SURVIVAL_MIN_SIZE: i16 = 6;
+// 縮んで通れなくなったマスと、次に通れなくなるマスの色
+pub const DEAD_ZONE_COLOR: [f32; 4] = [0.5, 0.0, 0.0, 0.6];
+pub const DEAD_ZONE_WARNING_COLOR: [f32; 4] = [0.5, 0.0, 0.0, 0.25];
+
+// ハンガーで何も食べないと尻尾が1つ減るまでのtick数
+pub const HUNGER_TICKS: u32 = 40;
+
+// Shiftでスピードを上げられるスタミナの量と、上げている間に1tickで減る量(上げていない間は1tickで1回復する)
+pub const BOOST_STAMINA: u32 = 120;
+pub const BOOST_DRAIN: u32 = 3;
+
+// 目標の「画面端を越えずにこの長さになる」の長さ
+pub const CHALLENGE_LENGTH: usize = 30;
+// 目標の「この秒数以内にこの個数のfoodを食べる」の個数と秒数
+pub const CHALLENGE_FOOD_COUNT: u32 = 10;
+pub const CHALLENGE_FOOD_SECONDS: f64 = 60.0;
+
+// バトルの盤面がconfig.tomlのグリッドの何倍の大きさか
+pub const BATTLE_GRID_SCALE: i16 = 2;
+// バトルのコンピュータの蛇の数のデフォルト値と範囲
+pub const DEFAULT_BATTLE_SNAKES: usize = 5;
+pub const MIN_BATTLE_SNAKES: usize = 3;
+pub const MAX_BATTLE_SNAKES: usize = 7;
+// 死んだ蛇や切り取られた尻尾が変わったfoodが消えるまでの秒数
+pub const PELLET_SECONDS: u32 = 20;
+// バトルのコンピュータの蛇のheadの色(bodyはこれを暗くした色)
+pub const BATTLE_SNAKE_COLORS: [[f32; 4]; MAX_BATTLE_SNAKES] = [
+    [0.8, 0.2, 1.0, 1.0],
+    [0.0, 0.8, 1.0, 1.0],
+    [1.0, 0.5, 0.0, 1.0],
+    [1.0, 0.2, 0.4, 1.0],
+    [0.9, 0.9, 0.2, 1.0],
+    [0.3, 0.4, 1.0, 1.0],
+    [0.6, 1.0, 0.8, 1.0],
+];
+
+// 音量(%)のデフォルト値と、キー1回で変わる量
+pub const DEFAULT_VOLUME: u32 = 80;
+pub const VOLUME_STEP: u32 = 10;
+
+// 実績の通知を表示する秒数と、そのうちスライドして出入りする秒数
+pub const TOAST_SECONDS: f32 = 3.0;
+pub const TOAST_SLIDE_SECONDS: f32 = 0.3;
+
+// オンラインのランキングに表示する名前のデフォルト値
+pub const DEFAULT_PLAYER_NAME: &str = "Player";
+// オンラインのランキングとの通信を諦めるまでの秒数
+pub const LEADERBOARD_TIMEOUT_SECONDS: u64 = 5;
+
+// ネットワーク対戦で待ち受けるデフォルトのポート番号
+pub const DEFAULT_NET_PORT: u16 = 7777;
+// ネットワーク対戦の相手に接続するのを諦めるまでの秒数
+pub const NET_TIMEOUT_SECONDS: u64 = 5;
+// 観戦中にホストから届いた入力を何tick分まで溜めておくか(それより遅れたらまとめて進める)
+pub const SPECTATOR_DELAY_TICKS: u32 = 3;
+
+// DiscordのRich Presenceを更新する間隔(秒)
+pub const DISCORD_UPDATE_SECONDS: f32 = 5.0;
+
+// --benchでSimulationを進めるtick数と、描画するフレーム数のデフォルト値
+pub const DEFAULT_BENCH_TICKS: u64 = 1_000_000;
+pub const DEFAULT_BENCH_FRAMES: u32 = 300;
+
+// smoothの蛇のセグメントをセルに対して何倍の大きさで描くか(残りはすき間になる)
+pub const SMOOTH_SEGMENT_SCALE: f32 = 0.85;
+
+// 蛇の描き方
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnakeStyle {
+    // テクスチャ(読み込めなければclassic)
+    #[default]
+    Sprites,
+    // セルいっぱいの四角
+    Classic,
+    // すき間を空けた角の丸い四角と丸い頭(Mesh)
+    Smooth,
+}
+
+// 画面全体にかけるシェーダーの効果
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostEffect {
+    // 何もかけない
+    #[default]
+    None,
+    // 走査線
+    Scanlines,
+    // ブラウン管(膨らみ・走査線・周辺の暗さ)
+    Crt,
+    // 明るいところをにじませる
+    Bloom,
+}
+
+impl PostEffect {
+    // SHADERS_DIRのシェーダーのファイル名(拡張子なし)。Noneなら使わない
+    pub fn file_name(self) -> Option<&'static str> {
+        match self {
+            PostEffect::None => None,
+            PostEffect::Scanlines => Some("scanlines"),
+            PostEffect::Crt => Some("crt"),
+            PostEffect::Bloom => Some("bloom"),
+        }
+    }
+}
+
+// classicのテーマで使う色(RGBA)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorConfig {
+    pub background: [f32; 4],
+    pub snake_head: [f32; 4],
+    pub snake_body: [f32; 4],
+    pub wall: [f32; 4],
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            background: [0.0, 0.0, 0.0, 0.0],
+            snake_head: [1.0, 0.5, 0.0, 1.0],
+            snake_body: [0.3, 0.3, 0.0, 1.0],
+            wall: [0.5, 0.5, 0.5, 1.0],
+        }
+    }
+}
+
+// 起動時にconfig.tomlから読み込む、再コンパイルせずに変えられるゲームの設定
+// ウィンドウの大きさが決まるのでContextを作る前に読み込む
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridConfig {
+    // ウィンドウタイトル
+    pub title: String,
+    // グリッドのマス数(横, 縦)
+    pub grid_size: (i16, i16),
+    // 1マスの大きさ(ピクセル)
+    pub cell_size: (i16, i16),
+    // 画面に表示するマス数(横, 縦)。グリッドより小さければカメラが蛇のheadを追う
+    // 指定しなければグリッド全体を表示する
+    pub view_size: Option<(i16, i16)>,
+    // 1秒間にupdateが呼ばれる回数(Normalの難易度のゲーム開始時)
+    pub fps: u32,
+    // 乱数のシード。指定すると毎回同じfoodの並びになる(--seedが優先)
+    pub seed: Option<u64>,
+    // スコアを送信するオンラインのランキングのURL(https://かhttp://。指定しなければ送信しない)
+    pub leaderboard_url: Option<String>,
+    // ランキングに表示する名前
+    pub player_name: String,
+    // 蛇の描き方("sprites", "classic", "smooth")
+    pub snake_style: SnakeStyle,
+    // 盤面の後ろに描く画像(リソースのルートからのパス。指定しなければ背景色で塗るだけ)
+    pub background_image: Option<String>,
+    // trueなら画像を元の大きさで並べ、falseなら画面いっぱいに引き伸ばす
+    pub background_tiled: bool,
+    // 画面全体にかける効果("none", "scanlines", "crt", "bloom")
+    pub post_effect: PostEffect,
+    // バトルのコンピュータの蛇の数(3〜7)
+    pub battle_snakes: usize,
+    // DiscordのRich Presenceに使うアプリのID(discordフィーチャー付きでビルドした時だけ使う。指定しなければ送らない)
+    pub discord_app_id: Option<String>,
+    pub colors: ColorConfig,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            title: DEFAULT_TITLE.to_string(),
+            grid_size: DEFAULT_GRID_SIZE,
+            cell_size: DEFAULT_GRID_CELL_SIZE,
+            view_size: None,
+            fps: DEFAULT_FPS,
+            seed: None,
+            leaderboard_url: None,
+            player_name: DEFAULT_PLAYER_NAME.to_string(),
+            snake_style: SnakeStyle::default(),
+            background_image: None,
+            background_tiled: false,
+            post_effect: PostEffect::default(),
+            battle_snakes: DEFAULT_BATTLE_SNAKES,
+            discord_app_id: None,
+            colors: ColorConfig::default(),
+        }
+    }
+}
+
+// 起動時に一度だけ決めるGridConfig
+pub static GRID_CONFIG: OnceLock<GridConfig> = OnceLock::new();
+
+// 現在のGridConfig(mainで設定する前に呼ばれたらデフォルト値)
+pub fn config() -> &'static GridConfig {
+    GRID_CONFIG.get_or_init(GridConfig::default)
+}
+
+impl GridConfig {
+    // 設定ファイルを読み込む。存在しなければデフォルト値を書き出す
+    // 壊れている場合はエラーを表示してデフォルト値
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => match toml::from_str::<GridConfig>(&contents) {
+                Ok(config) => config.clamped(),
+                Err(e) => {
+                    eprintln!("Could not parse {}: {}", path.display(), e);
+                    GridConfig::default()
+                }
+            },
+            Err(_) => {
+                let config = GridConfig::default();
+                if let Err(e) = config.save(path) {
+                    eprintln!("Could not write {}: {}", path.display(), e);
+                }
+                config
+            }
+        }
+    }
+
+    // 設定ファイルに書き込む(ディレクトリがなければ作る)
+    pub fn save(&self, path: &Path) -> GameResult {
+        let contents =
+            toml::to_string(self).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    // 小さすぎる値を下限に合わせる
+    pub fn clamped(mut self) -> Self {
+        self.grid_size = (
+            self.grid_size.0.max(MIN_GRID_SIZE),
+            self.grid_size.1.max(MIN_GRID_SIZE),
+        );
+        self.cell_size = (
+            self.cell_size.0.max(MIN_GRID_CELL_SIZE),
+            self.cell_size.1.max(MIN_GRID_CELL_SIZE),
+        );
+        // 表示するマス数はグリッドより大きくしない
+        self.view_size = self.view_size.map(|(w, h)| {
+            (
+                w.clamp(MIN_GRID_SIZE, self.grid_size.0),
+                h.clamp(MIN_GRID_SIZE, self.grid_size.1),
+            )
+        });
+        self.fps = self.fps.max(1);
+        self.battle_snakes = self
+            .battle_snakes
+            .clamp(MIN_BATTLE_SNAKES, MAX_BATTLE_SNAKES);
+        self
+    }
+
+    // Next we define how large we want our actual window to be by multiplying
+    // the components of our grid size by its corresponding pixel size.
+    // カメラを使う場合は表示するマス数の分だけ
+    pub fn screen_size(&self) -> (f32, f32) {
+        let (w, h) = self.view_size.unwrap_or(self.grid_size);
+        (
+            w as f32 * self.cell_size.0 as f32,
+            h as f32 * self.cell_size.1 as f32,
+        )
+    }
+}
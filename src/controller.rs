@@ -0,0 +1,212 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::food::{Food, FoodKind};
+use crate::grid::{Board, Cells, Direction, GridPosition};
+use crate::level::{Obstacle, Portal};
+use crate::pathfinding;
+use crate::replay::Replay;
+use crate::snake::{Controller, Snake};
+
+// ネットワーク対戦でtickごとに送った・届いた方向入力(NetSessionと共有する)
+pub type NetInputs = Rc<RefCell<HashMap<u32, VecDeque<Direction>>>>;
+
+// 操作する蛇から見た盤面(方向を決めるのに使う)
+pub struct BoardView<'a> {
+    // 操作する蛇の番号(snakesの添字)
+    pub index: usize,
+    // プレイ開始から経過したtick数
+    pub tick: u32,
+    pub board: Board,
+    // 盤面のマスごとの中身
+    pub cells: &'a Cells,
+    pub snakes: &'a [Snake],
+    pub foods: &'a [Food],
+    pub obstacles: &'a [Obstacle],
+    pub portals: &'a [Portal],
+}
+
+impl BoardView<'_> {
+    // 操作する蛇
+    pub fn snake(&self) -> &Snake {
+        &self.snakes[self.index]
+    }
+
+    // posからdirに1マス進んだ先(ワープマスならワープ先)
+    pub fn step(&self, pos: GridPosition, dir: Direction) -> Option<GridPosition> {
+        pathfinding::step(self.cells, self.portals, pos, dir)
+    }
+}
+
+// 蛇の操作方法(人・コンピュータ・リプレイ・ネットワークの相手)
+// GameStateは毎tick蛇が動く前に、蛇ごとの操作から方向入力を受け取る
+pub trait SnakeController {
+    // このtickで進む方向を決める(Noneなら今の方向のまま)
+    fn decide(&self, view: &BoardView) -> Option<Direction>;
+
+    // このtickの方向入力を全て返す
+    // 1tickに何回も入力できる操作(キー入力やリプレイ)はNoneになるまでdecideを呼ぶ
+    fn inputs(&self, view: &BoardView) -> Vec<Direction> {
+        self.decide(view).into_iter().collect()
+    }
+
+    // 人の方向入力を受け取る(次のtickでdecideから返す)
+    fn input(&self, _dir: Direction) {}
+
+    // tickの入力が揃っているか(ネットワーク対戦では相手の入力が届くまで待つ)
+    fn ready(&self, _tick: u32) -> bool {
+        true
+    }
+}
+
+// 蛇の操作方法に合わせた操作を作る
+pub fn controller_for(controller: Controller) -> Box<dyn SnakeController> {
+    match controller {
+        Controller::Human => Box::new(KeyboardController::default()),
+        Controller::Computer => Box::new(ComputerController),
+        Controller::Autopilot => Box::new(AutopilotController),
+    }
+}
+
+// キーボードやゲームパッドで人が操作する
+#[derive(Default)]
+pub struct KeyboardController {
+    // 次のtickで入れる方向入力(押した順)
+    pending: RefCell<VecDeque<Direction>>,
+}
+
+impl SnakeController for KeyboardController {
+    fn decide(&self, _view: &BoardView) -> Option<Direction> {
+        self.pending.borrow_mut().pop_front()
+    }
+
+    fn inputs(&self, view: &BoardView) -> Vec<Direction> {
+        std::iter::from_fn(|| self.decide(view)).collect()
+    }
+
+    fn input(&self, dir: Direction) {
+        self.pending.borrow_mut().push_back(dir);
+    }
+}
+
+// リプレイに記録された方向入力を同じtickに同じ順番で入れ直す
+pub struct ReplayController {
+    // この蛇への入力(tick, 方向)
+    inputs: Vec<(u32, Direction)>,
+    // 次に入れ直す入力の位置
+    next: Cell<usize>,
+}
+
+impl ReplayController {
+    // リプレイからplayer番目の蛇への入力を取り出す
+    pub fn new(replay: &Replay, player: usize) -> Self {
+        ReplayController {
+            inputs: replay
+                .inputs
+                .iter()
+                .filter(|input| input.player == player)
+                .map(|input| (input.tick, input.dir))
+                .collect(),
+            next: Cell::new(0),
+        }
+    }
+}
+
+impl SnakeController for ReplayController {
+    fn decide(&self, view: &BoardView) -> Option<Direction> {
+        let (tick, dir) = *self.inputs.get(self.next.get())?;
+        if tick > view.tick {
+            return None;
+        }
+        self.next.set(self.next.get() + 1);
+        Some(dir)
+    }
+
+    fn inputs(&self, view: &BoardView) -> Vec<Direction> {
+        std::iter::from_fn(|| self.decide(view)).collect()
+    }
+}
+
+// ネットワーク対戦の蛇(自分の入力も送ったものを相手と同じtickで入れる)
+pub struct NetworkController {
+    inputs: NetInputs,
+}
+
+impl NetworkController {
+    pub fn new(inputs: NetInputs) -> Self {
+        NetworkController { inputs }
+    }
+}
+
+impl SnakeController for NetworkController {
+    fn decide(&self, view: &BoardView) -> Option<Direction> {
+        let mut inputs = self.inputs.borrow_mut();
+        let dirs = inputs.get_mut(&view.tick)?;
+        let dir = dirs.pop_front();
+        // このtickの入力を全て入れたら消す
+        if dir.is_none() {
+            inputs.remove(&view.tick);
+        }
+        dir
+    }
+
+    fn inputs(&self, view: &BoardView) -> Vec<Direction> {
+        std::iter::from_fn(|| self.decide(view)).collect()
+    }
+
+    fn ready(&self, tick: u32) -> bool {
+        self.inputs.borrow().contains_key(&tick)
+    }
+}
+
+// 毎tickコンピュータが方向を決める
+// ぶつからない方向のうち、閉じ込められず一番近いfoodに近づく方向を選ぶ
+// どの方向に進んでもぶつかる場合はNone(そのまま進む)
+pub struct ComputerController;
+
+impl SnakeController for ComputerController {
+    fn decide(&self, view: &BoardView) -> Option<Direction> {
+        let snake = view.snake();
+        let target = view
+            .foods
+            .iter()
+            .filter(|f| f.kind != FoodKind::Poison)
+            .map(|f| f.pos)
+            .min_by_key(|pos| snake.head.pos.distance(*pos, view.board));
+        Direction::ALL
+            .iter()
+            .copied()
+            .filter(|dir| *dir != snake.dir.inverse())
+            .filter_map(|dir| {
+                let next = view.step(snake.head.pos, dir)?;
+                // 蛇と障害物に加えて毒のfoodも避ける
+                if !pathfinding::is_safe(view.cells, next) {
+                    return None;
+                }
+                // 進んだ先から行けるマスが蛇の長さより少なければ閉じ込められる
+                let trapped =
+                    pathfinding::reachable_area(view.cells, view.portals, next, snake.len())
+                        < snake.len();
+                let distance = target.map_or(0, |pos| next.distance(pos, view.board));
+                Some((trapped, distance, dir))
+            })
+            .min_by_key(|(trapped, distance, _)| (*trapped, *distance))
+            .map(|(_, _, dir)| dir)
+    }
+}
+
+// 一番近いfoodまでの最短経路を進む(デモ用)
+// 経路がなければComputerControllerと同じくぶつからない方向に逃げる
+pub struct AutopilotController;
+
+impl SnakeController for AutopilotController {
+    fn decide(&self, view: &BoardView) -> Option<Direction> {
+        let head = view.snake().head.pos;
+        pathfinding::first_step(view.cells, view.portals, head, |pos| {
+            pathfinding::is_food(view.cells, pos)
+        })
+        .map(|step| step.dir)
+        .or_else(|| ComputerController.decide(view))
+    }
+}
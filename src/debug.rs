@@ -0,0 +1,66 @@
+use ggez::graphics;
+
+use crate::config::config;
+
+// F3で切り替えるデバッグ用の表示(普段は表示しない)
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    // 直近1秒間に進んだtick数
+    ticks_per_second: u32,
+    // 数え始めた時のtickと、そこから経過した秒数
+    counted_from: u32,
+    elapsed: f64,
+}
+
+impl DebugOverlay {
+    // 経過時間を進め、1秒ごとに進んだtick数を数え直す
+    // (やり直してtickが戻った場合はそこから数え直す)
+    pub fn update(&mut self, dt: f64, tick: u32) {
+        self.elapsed += dt;
+        if tick < self.counted_from {
+            self.counted_from = tick;
+        }
+        if self.elapsed >= 1.0 {
+            self.ticks_per_second = tick - self.counted_from;
+            self.counted_from = tick;
+            self.elapsed = 0.0;
+        }
+    }
+
+    // 直近1秒間に実際に進んだtick数
+    pub fn ticks_per_second(&self) -> u32 {
+        self.ticks_per_second
+    }
+
+    // 画面の左下に半透明の背景を敷いてlinesを描画する
+    pub fn draw(&self, canvas: &mut graphics::Canvas, lines: &[String]) {
+        if !self.visible {
+            return;
+        }
+        let screen = config().screen_size();
+        let line_height = 22.0;
+        let height = line_height * lines.len() as f32 + 12.0;
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest_rect(graphics::Rect::new(
+                    0.0,
+                    screen.1 - height,
+                    screen.0 / 2.0,
+                    height,
+                ))
+                .color([0.0, 0.0, 0.0, 0.6]),
+        );
+        for (i, line) in lines.iter().enumerate() {
+            let mut text = graphics::Text::new(line.as_str());
+            text.set_scale(20.0);
+            canvas.draw(
+                &text,
+                graphics::DrawParam::new()
+                    .dest([8.0, screen.1 - height + 6.0 + line_height * i as f32])
+                    .color(graphics::Color::GREEN),
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::mpsc::Sender;
+
+use crate::config::DISCORD_UPDATE_SECONDS;
+
+// Discordのプロフィールに表示する今のアクティビティ
+#[derive(Clone, Debug, PartialEq)]
+pub struct Activity {
+    // 1行目(「Classic - Score 12」)
+    pub details: String,
+    // 2行目(「Length 5」)
+    pub state: String,
+    // ゲームを始めた時刻(UNIX時間の秒)。Discordが経過時間を表示する。メニューならNone
+    pub start: Option<u64>,
+}
+
+// DiscordのRich Presence(cargoのdiscordフィーチャーを付けてビルドした時だけ送る)
+// 送信は別スレッドで行うので、Discordが起動していなくてもupdateは止まらない
+#[derive(Default)]
+pub struct Presence {
+    // 送信用のスレッドへのチャンネル(使わなければNone)
+    sender: Option<Sender<Activity>>,
+    // 次に送るまでの秒数
+    cooldown: f32,
+    // 最後に送ったアクティビティ(変わっていなければ送らない)
+    last: Option<Activity>,
+}
+
+impl Presence {
+    // config.tomlにdiscord_app_idがあれば送信用のスレッドを作る
+    pub fn start() -> Self {
+        Presence {
+            sender: ipc::spawn(),
+            ..Presence::default()
+        }
+    }
+
+    // 前に送ってからDISCORD_UPDATE_SECONDS経っていればtrue
+    pub fn due(&mut self, delta: f32) -> bool {
+        if self.sender.is_none() {
+            return false;
+        }
+        self.cooldown -= delta;
+        self.cooldown <= 0.0
+    }
+
+    // アクティビティを送信用のスレッドに渡す(変わっていなければ何もしない)
+    pub fn send(&mut self, activity: Activity) {
+        self.cooldown = DISCORD_UPDATE_SECONDS;
+        if self.last.as_ref() == Some(&activity) {
+            return;
+        }
+        if let Some(sender) = &self.sender {
+            // スレッドが終わっていたらもう送らない
+            if sender.send(activity.clone()).is_err() {
+                self.sender = None;
+            }
+        }
+        self.last = Some(activity);
+    }
+}
+
+// DiscordのアプリとのIPC(ローカルのソケットに長さ付きのJSONを書く)
+// 依存を増やさないように、アクティビティを送るのに必要なやり取りだけをする
+#[cfg(feature = "discord")]
+mod ipc {
+    use std::io::{Read, Write};
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    use super::Activity;
+    use crate::config::config;
+
+    // ハンドシェイクとコマンドのopcode
+    const OP_HANDSHAKE: u32 = 0;
+    const OP_FRAME: u32 = 1;
+
+    trait Pipe: Read + Write + Send {}
+    impl<T: Read + Write + Send> Pipe for T {}
+
+    pub fn spawn() -> Option<Sender<Activity>> {
+        let app_id = config().discord_app_id.clone()?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || run(&app_id, receiver));
+        Some(sender)
+    }
+
+    // アクティビティが届くたびに送る。つながっていなければつなぎ直し、送れなければ次の機会に
+    fn run(app_id: &str, receiver: Receiver<Activity>) {
+        let mut pipe: Option<Box<dyn Pipe>> = None;
+        let mut nonce = 0u64;
+        while let Ok(mut activity) = receiver.recv() {
+            // 溜まっていたら最新のものだけ送る
+            if let Some(latest) = receiver.try_iter().last() {
+                activity = latest;
+            }
+            if pipe.is_none() {
+                pipe = connect(app_id);
+            }
+            let Some(stream) = &mut pipe else {
+                continue;
+            };
+            nonce += 1;
+            let payload = serde_json::json!({
+                "cmd": "SET_ACTIVITY",
+                "args": {
+                    "pid": std::process::id(),
+                    "activity": {
+                        "details": activity.details,
+                        "state": activity.state,
+                        "timestamps": activity.start.map(|start| serde_json::json!({ "start": start })),
+                    },
+                },
+                "nonce": nonce.to_string(),
+            });
+            if send(stream, OP_FRAME, &payload).is_err() {
+                pipe = None;
+            }
+        }
+    }
+
+    // discord-ipc-0〜9のどれかにつないでハンドシェイクする(Discordが起動していなければNone)
+    fn connect(app_id: &str) -> Option<Box<dyn Pipe>> {
+        let mut stream = (0..10).find_map(open)?;
+        let handshake = serde_json::json!({ "v": 1, "client_id": app_id });
+        send(&mut stream, OP_HANDSHAKE, &handshake).ok()?;
+        Some(stream)
+    }
+
+    #[cfg(unix)]
+    fn open(index: usize) -> Option<Box<dyn Pipe>> {
+        let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+            .iter()
+            .find_map(|key| std::env::var(key).ok())
+            .unwrap_or_else(|| "/tmp".to_string());
+        let path = std::path::Path::new(&dir).join(format!("discord-ipc-{}", index));
+        let stream = std::os::unix::net::UnixStream::connect(path).ok()?;
+        Some(Box::new(stream))
+    }
+
+    #[cfg(windows)]
+    fn open(index: usize) -> Option<Box<dyn Pipe>> {
+        let path = format!(r"\\.\pipe\discord-ipc-{}", index);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .ok()?;
+        Some(Box::new(file))
+    }
+
+    // opcodeと長さ(リトルエンディアンのu32)の後にJSONを書き、Discordからの返事を1つ読み捨てる
+    fn send(
+        stream: &mut Box<dyn Pipe>,
+        op: u32,
+        payload: &serde_json::Value,
+    ) -> std::io::Result<()> {
+        let body = payload.to_string();
+        let mut frame = Vec::with_capacity(8 + body.len());
+        frame.extend_from_slice(&op.to_le_bytes());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(body.as_bytes());
+        stream.write_all(&frame)?;
+        let mut header = [0; 8];
+        stream.read_exact(&mut header)?;
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut reply = vec![0; len as usize];
+        stream.read_exact(&mut reply)
+    }
+}
+
+// discordフィーチャーなしでは何も送らない
+#[cfg(not(feature = "discord"))]
+mod ipc {
+    use std::sync::mpsc::Sender;
+
+    use super::Activity;
+
+    pub fn spawn() -> Option<Sender<Activity>> {
+        None
+    }
+}
@@ -0,0 +1,40 @@
+use crate::food::{FoodKind, PowerUpKind};
+use crate::game_state::GameOutcome;
+use crate::grid::{Direction, GridPosition};
+use crate::snake::Ate;
+
+// ゲーム中のできごと
+// GameStateはupdateの中で起きた順に溜め、updateの最後に効果音・パーティクル・実績・通算成績に配る
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    // snake番目の蛇がposのfoodを食べた(bonusは時間で消えるfoodか)
+    FoodEaten {
+        snake: usize,
+        pos: GridPosition,
+        kind: FoodKind,
+        bonus: bool,
+    },
+    // snake番目の蛇がcause(自分の体・壁・障害物・毒のfood)で死んだ
+    SnakeDied {
+        snake: usize,
+        cause: Option<Ate>,
+    },
+    // snake番目の蛇がパワーアップを拾った
+    PowerUpCollected {
+        snake: usize,
+        kind: PowerUpKind,
+    },
+    // snake番目の人が操作する蛇がdirの方向に向きを変えた
+    SnakeTurned {
+        snake: usize,
+        dir: Direction,
+    },
+    // snake番目の蛇が画面端を越えて反対側に出た
+    SnakeWrapped {
+        snake: usize,
+    },
+    // ゲームが終わった
+    LevelCompleted {
+        outcome: GameOutcome,
+    },
+}
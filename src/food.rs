@@ -0,0 +1,372 @@
+use oorandom::Rand32;
+use serde::{Deserialize, Serialize};
+
+use ggez::{graphics, GameResult};
+
+use crate::config::{BONUS_VALUE, FLEE_DISTANCE, WANDER_INTERVAL};
+use crate::grid::{Cells, Direction, GridPosition};
+use crate::rendering::{draw_cell, scale_rect, CellBatch};
+use crate::sprites::Sprites;
+use crate::theme::Theme;
+
+// foodの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FoodKind {
+    // valueの分だけ伸びてスコアが増える
+    Normal,
+    // 伸びる長さは同じで、スコアがGOLDEN_SCORE_MULTIPLIER倍
+    Golden,
+    // POISON_SEGMENTS分縮む。縮めないほど短ければgameover
+    Poison,
+    // 体の長さが半分になる
+    Shrink,
+    // 数tickごとに動き回り、蛇が近づくと逃げる。スコアがWANDERING_SCORE_MULTIPLIER倍
+    Wandering,
+    // 普通に伸びるが、CONFUSION_TICKSの間は上下左右の入力が逆になる
+    Confusion,
+}
+
+impl FoodKind {
+    // 出現する重み
+    pub fn weight(self) -> u32 {
+        match self {
+            FoodKind::Normal => 80,
+            FoodKind::Golden => 8,
+            FoodKind::Poison => 6,
+            FoodKind::Shrink => 6,
+            FoodKind::Wandering => 6,
+            FoodKind::Confusion => 5,
+        }
+    }
+
+    // 重みに従ってランダムな種類を決める
+    pub fn random(rng: &mut Rand32) -> Self {
+        let all = [
+            FoodKind::Normal,
+            FoodKind::Golden,
+            FoodKind::Poison,
+            FoodKind::Shrink,
+            FoodKind::Wandering,
+            FoodKind::Confusion,
+        ];
+        let total: u32 = all.iter().map(|k| k.weight()).sum();
+        let mut roll = rng.rand_range(0..total);
+        for kind in all {
+            if roll < kind.weight() {
+                return kind;
+            }
+            roll -= kind.weight();
+        }
+        FoodKind::Normal
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Food {
+    pub pos: GridPosition,
+    // 食べた時に伸びる長さとスコア(1〜3)
+    pub value: u16,
+    pub kind: FoodKind,
+    // 置かれてから経過したtick数
+    pub age: u32,
+    // 消えるまでのtick数(Noneなら消えない)
+    pub lifetime: Option<u32>,
+}
+
+impl Food {
+    pub fn new(pos: GridPosition, value: u16, kind: FoodKind) -> Self {
+        Food {
+            pos,
+            value,
+            kind,
+            age: 0,
+            lifetime: None,
+        }
+    }
+
+    // lifetime tick後に消えるボーナスのfoodを作成
+    pub fn bonus(pos: GridPosition, lifetime: u32) -> Self {
+        Food {
+            lifetime: Some(lifetime),
+            ..Food::new(pos, BONUS_VALUE, FoodKind::Golden)
+        }
+    }
+
+    // 消えるまでの残り時間の割合(1.0〜0.0)。消えないfoodはNone
+    pub fn remaining(&self) -> Option<f32> {
+        self.lifetime
+            .map(|lifetime| 1.0 - self.age as f32 / lifetime.max(1) as f32)
+    }
+
+    // 消える時間を過ぎたらtrue
+    pub fn expired(&self) -> bool {
+        self.lifetime.is_some_and(|lifetime| self.age >= lifetime)
+    }
+
+    // 動き回るfoodならWANDER_INTERVAL tickごとに1歩動く
+    // headsのどれかがFLEE_DISTANCE以内なら一番遠ざかるマスへ、そうでなければランダムなマスへ
+    // 何かがあるマスには入らない(動けるマスがなければその場に留まる)
+    pub fn update(&mut self, rng: &mut Rand32, cells: &Cells, heads: &[GridPosition]) {
+        if self.kind != FoodKind::Wandering || !self.age.is_multiple_of(WANDER_INTERVAL) {
+            return;
+        }
+        let board = cells.board();
+        let steps: Vec<GridPosition> = Direction::ALL
+            .iter()
+            .filter_map(|dir| GridPosition::new_from_move(self.pos, *dir, board))
+            .filter(|pos| cells.is_empty(*pos))
+            .collect();
+        if steps.is_empty() {
+            return;
+        }
+        // 一番近いheadまでの距離
+        let nearest = |pos: GridPosition| {
+            heads
+                .iter()
+                .map(|head| pos.distance(*head, board))
+                .min()
+                .unwrap_or(i16::MAX)
+        };
+        self.pos = if nearest(self.pos) <= FLEE_DISTANCE {
+            steps
+                .into_iter()
+                .max_by_key(|pos| nearest(*pos))
+                .unwrap_or(self.pos)
+        } else {
+            steps[rng.rand_range(0..steps.len() as u32) as usize]
+        };
+    }
+
+    // ランダムな種類とvalueのfoodを作成(Normal以外のvalueは1)
+    pub fn random(rng: &mut Rand32, pos: GridPosition) -> Self {
+        let kind = FoodKind::random(rng);
+        let value = match kind {
+            FoodKind::Normal => Food::random_value(rng),
+            _ => 1,
+        };
+        Food::new(pos, value, kind)
+    }
+
+    // 小さい値ほど出やすいようにランダムなvalueを決める(1: 60%, 2: 30%, 3: 10%)
+    pub fn random_value(rng: &mut Rand32) -> u16 {
+        match rng.rand_range(0..10) {
+            0..=5 => 1,
+            6..=8 => 2,
+            _ => 3,
+        }
+    }
+
+    // foodを描画する
+    // valueに応じたセル内での大きさ(1: 半分, 2: 3/4, 3: セル全体)
+    fn scale(&self) -> f32 {
+        (self.value.min(3) + 1) as f32 / 4.0
+    }
+
+    // foodの四角形をbatchに追加する
+    // spritesがtrueなら普通のfoodは追加しない(draw_spriteでりんごの画像を描く)
+    pub fn draw(&self, batch: &mut CellBatch, theme: &Theme, outlined: bool, sprites: bool) {
+        let cell: graphics::Rect = self.pos.into();
+        match self.kind {
+            FoodKind::Normal if sprites => (),
+            FoodKind::Normal => {
+                let scale = self.scale();
+                // valueごとのテーマの色
+                let color = match self.value {
+                    1 => theme.food[0],
+                    2 => theme.food[1],
+                    _ => theme.food[2],
+                };
+                draw_cell(batch, scale_rect(cell, scale), color, outlined);
+            }
+            // セル全体
+            FoodKind::Golden => draw_cell(batch, cell, theme.golden, outlined),
+            // 小さい四角
+            FoodKind::Poison => draw_cell(batch, scale_rect(cell, 0.5), theme.poison, outlined),
+            // 枠の中を背景色で塗って輪の形にする
+            FoodKind::Shrink => {
+                draw_cell(batch, scale_rect(cell, 0.75), theme.shrink, false);
+                draw_cell(
+                    batch,
+                    scale_rect(cell, 0.4),
+                    theme.background_fill(),
+                    outlined,
+                );
+            }
+            // 少し小さい四角
+            FoodKind::Wandering => {
+                draw_cell(batch, scale_rect(cell, 0.75), theme.wandering, outlined)
+            }
+            // 斜めに並んだ2つの小さい四角
+            FoodKind::Confusion => {
+                let half = graphics::Rect::new(cell.x, cell.y, cell.w / 2.0, cell.h / 2.0);
+                let far = graphics::Rect::new(half.x + half.w, half.y + half.h, half.w, half.h);
+                draw_cell(batch, scale_rect(half, 0.8), theme.confusion, outlined);
+                draw_cell(batch, scale_rect(far, 0.8), theme.confusion, outlined);
+            }
+        }
+    }
+
+    // foodを種類ごとの形でbuilderのMeshに追加する(theme.shapesの時にdrawの代わりに使う)
+    // 普通は丸、ゴールドはセル全体の四角、毒はバツ印、縮むfoodは輪、動き回るfoodはひし形、混乱のfoodは下向きの三角
+    pub fn draw_shape(&self, builder: &mut graphics::MeshBuilder, theme: &Theme) -> GameResult {
+        let cell: graphics::Rect = self.pos.into();
+        let center = [cell.x + cell.w / 2.0, cell.y + cell.h / 2.0];
+        let size = cell.w.min(cell.h);
+        match self.kind {
+            FoodKind::Normal => {
+                let color = match self.value {
+                    1 => theme.food[0],
+                    2 => theme.food[1],
+                    _ => theme.food[2],
+                };
+                builder.circle(
+                    graphics::DrawMode::fill(),
+                    center,
+                    size * self.scale() / 2.0,
+                    0.5,
+                    color.into(),
+                )?;
+            }
+            FoodKind::Golden => {
+                builder.rectangle(graphics::DrawMode::fill(), cell, theme.golden.into())?;
+            }
+            FoodKind::Poison => {
+                let r = scale_rect(cell, 0.6);
+                let width = size * 0.15;
+                builder.line(
+                    &[[r.x, r.y], [r.x + r.w, r.y + r.h]],
+                    width,
+                    theme.poison.into(),
+                )?;
+                builder.line(
+                    &[[r.x + r.w, r.y], [r.x, r.y + r.h]],
+                    width,
+                    theme.poison.into(),
+                )?;
+            }
+            FoodKind::Shrink => {
+                builder.circle(
+                    graphics::DrawMode::stroke(size * 0.12),
+                    center,
+                    size * 0.35,
+                    0.5,
+                    theme.shrink.into(),
+                )?;
+            }
+            FoodKind::Wandering => {
+                let r = scale_rect(cell, 0.8);
+                builder.polygon(
+                    graphics::DrawMode::fill(),
+                    &[
+                        [center[0], r.y],
+                        [r.x + r.w, center[1]],
+                        [center[0], r.y + r.h],
+                        [r.x, center[1]],
+                    ],
+                    theme.wandering.into(),
+                )?;
+            }
+            FoodKind::Confusion => {
+                let r = scale_rect(cell, 0.8);
+                builder.polygon(
+                    graphics::DrawMode::fill(),
+                    &[[r.x, r.y], [r.x + r.w, r.y], [center[0], r.y + r.h]],
+                    theme.confusion.into(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // 普通のfoodをりんごの画像で描画する
+    pub fn draw_sprite(&self, canvas: &mut graphics::Canvas, sprites: &Sprites) {
+        if self.kind == FoodKind::Normal {
+            let rect = scale_rect(self.pos.into(), self.scale());
+            Sprites::draw(canvas, &sprites.apple, rect, 0.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+
+    // 消えるまでの残り時間をセルの下端のバーで描画
+    pub fn draw_timer(&self, batch: &mut CellBatch) {
+        let Some(remaining) = self.remaining() else {
+            return;
+        };
+        let cell: graphics::Rect = self.pos.into();
+        let height = 5.0;
+        batch.push(
+            graphics::Rect::new(
+                cell.x,
+                cell.y + cell.h - height,
+                cell.w * remaining.max(0.0),
+                height,
+            ),
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
+}
+
+// パワーアップの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    // 自分の体を通り抜けられる
+    Ghost,
+    // updateの回数が半分になる
+    SlowMo,
+    // 次に1回ぶつかっても死なない
+    Shield,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 3] =
+        [PowerUpKind::Ghost, PowerUpKind::SlowMo, PowerUpKind::Shield];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::Ghost => "Ghost",
+            PowerUpKind::SlowMo => "Slow-mo",
+            PowerUpKind::Shield => "Shield",
+        }
+    }
+
+    // 盤面上と効果中の蛇のheadの色
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            PowerUpKind::Ghost => [0.8, 0.8, 1.0, 1.0],
+            PowerUpKind::SlowMo => [0.2, 1.0, 0.4, 1.0],
+            PowerUpKind::Shield => [1.0, 0.2, 0.6, 1.0],
+        }
+    }
+
+    // 効果が続くtick数
+    pub fn duration(self) -> u32 {
+        match self {
+            PowerUpKind::Ghost => 40,
+            PowerUpKind::SlowMo => 40,
+            PowerUpKind::Shield => 80,
+        }
+    }
+}
+
+// 盤面に置かれたパワーアップ
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PowerUp {
+    pub pos: GridPosition,
+    pub kind: PowerUpKind,
+}
+
+impl PowerUp {
+    // パワーアップを描画する(セルの中に小さい四角を2つ重ねる)
+    pub fn draw(&self, batch: &mut CellBatch, outlined: bool) {
+        let cell: graphics::Rect = self.pos.into();
+        draw_cell(batch, scale_rect(cell, 0.8), self.kind.color(), outlined);
+        draw_cell(batch, scale_rect(cell, 0.3), [1.0, 1.0, 1.0, 1.0], false);
+    }
+}
+
+// 効果中のパワーアップ
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    // 効果が切れるまでのtick数
+    pub remaining: u32,
+}
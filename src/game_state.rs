@@ -0,0 +1,3665 @@
+use oorandom::Rand32;
+
+use ggez::{
+    event, graphics,
+    input::{
+        gamepad::{
+            gilrs::{Axis, Button},
+            GamepadId,
+        },
+        keyboard::{KeyCode, KeyInput, KeyMods},
+        mouse::MouseButton,
+    },
+    winit::event::TouchPhase,
+    Context, GameResult,
+};
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audio::{AudioManager, Music, Sound};
+use crate::challenge::{self, Challenge, ChallengeContext};
+use crate::challenge_code::ChallengeCode;
+use crate::config::{
+    config, SnakeStyle, BATTLE_GRID_SCALE, BATTLE_SNAKE_COLORS, BONUS_CHANCE, BONUS_SECONDS,
+    CAMPAIGN_FOOD_COUNT, CAMPAIGN_SPEEDUP, CODE_INPUT_LENGTH, COMBO_TICKS, CONFUSION_TICKS,
+    COUNTDOWN_SECONDS, CUSTOM_LEVEL_NAME, DEAD_ZONE_COLOR, DEAD_ZONE_WARNING_COLOR,
+    DEFAULT_NET_PORT, DEMO_IDLE_SECONDS, DYING_SECONDS, DYING_TICKS, GHOSTS_DIR, GIFS_DIR,
+    GOLDEN_SCORE_MULTIPLIER, LEVELS_DIR, MAX_COMBO_MULTIPLIER, MAX_SPEED_ADJUST, MIN_TICK_RATE,
+    NAME_LENGTH, PELLET_SECONDS, POISON_SEGMENTS, POWER_UP_CHANCE, REPLAY_FILE, SAVE_FILE,
+    SCREENSHOTS_DIR, SPECTATOR_DELAY_TICKS, SPEEDUP_FOOD_COUNT, SURVIVAL_MIN_SIZE,
+    SURVIVAL_WARNING_SECONDS, VOLUME_STEP, WANDERING_SCORE_MULTIPLIER,
+};
+use crate::controller::{
+    controller_for, BoardView, NetworkController, ReplayController, SnakeController,
+};
+use crate::debug::DebugOverlay;
+use crate::discord::{Activity, Presence};
+use crate::event::GameEvent;
+use crate::food::{ActiveEffect, Food, FoodKind, PowerUp, PowerUpKind};
+use crate::grid::{Board, Cell, Cells, Direction, GridPosition};
+use crate::level::{Brush, Editor, Level, Obstacle, Portal};
+use crate::minimap::Minimap;
+use crate::mode::{daily_level, daily_seed, CollisionRule, GameMode};
+use crate::net::{Leaderboard, NetEvent, NetMessage, NetSession, ScoreSubmission};
+use crate::particles::Particles;
+use crate::pathfinding::{self, Step};
+use crate::post_process::PostProcess;
+use crate::recorder::GifRecorder;
+use crate::records::{
+    file_timestamp, format_timestamp, now_timestamp, Achievement, Achievements, HighScores, Stats,
+};
+use crate::rendering::{
+    background_mesh, camera_coordinates, draw_banner, draw_challenges, draw_countdown,
+    draw_dead_zone, draw_effects, draw_high_scores, draw_hint_arrow, draw_hud, draw_leaderboard,
+    draw_meter, draw_overlay, draw_standings, draw_tint, scale_factor, screen_coordinates,
+    window_to_screen, BackgroundImage, CellBatch, DeathEffect,
+};
+use crate::replay::{Playback, Replay, ReplayInput, SaveGame};
+use crate::script::{Command, Hook, Script};
+use crate::settings::{Action, Background, Difficulty, InputBindings, Settings};
+use crate::simulation::Simulation;
+use crate::snake::{Ate, Controller, Skin, Snake};
+use crate::sprites::Sprites;
+use crate::text_input::TextInput;
+use crate::tutorial::Tutorial;
+
+// u8型の配列の値それぞれにランダムな値を格納しu64に変換
+// OSの乱数が使えない環境(ブラウザなど)ではpanicせず現在時刻から作る
+pub fn random_seed() -> u64 {
+    let mut seed: [u8; 8] = [0; 8];
+    match getrandom::getrandom(&mut seed[..]) {
+        Ok(()) => u64::from_ne_bytes(seed),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0),
+    }
+}
+
+// 画面(シーン)の種類
+// シーンごとにupdate/draw/key_down_eventの処理を切り替える
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    // タイトル画面
+    MainMenu,
+    // プレイ中
+    Playing,
+    // 一時停止中
+    Paused,
+    // ゲームオーバー
+    GameOver,
+    // ステージエディタ
+    Editor,
+    // 操作設定
+    Controls,
+    // 通算成績
+    Stats,
+    // ネットワーク対戦の接続待ち
+    Lobby,
+    // 友達のチャレンジコードの入力
+    EnterCode,
+}
+
+impl Scene {
+    // シーンで流すBGM(ゲームオーバーでは止める)
+    pub fn music(self) -> Option<Music> {
+        match self {
+            Scene::MainMenu
+            | Scene::Editor
+            | Scene::Controls
+            | Scene::Stats
+            | Scene::Lobby
+            | Scene::EnterCode => Some(Music::Menu),
+            Scene::Playing | Scene::Paused => Some(Music::Game),
+            Scene::GameOver => None,
+        }
+    }
+}
+
+// ゲームの結果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome {
+    // ぶつかって死んだ
+    Died,
+    // 盤面を全て埋めた
+    Won,
+    // 2人プレイで片方が生き残った(勝った方の番号)
+    PlayerWon(usize),
+    // 2人プレイで同時に死んだ
+    Draw,
+    // タイムアタックの制限時間が来た
+    TimeUp,
+}
+
+// ゲームオーバーになった蛇が尻尾から消えていく途中の状態
+// プレイのtickとは別に、DYING_SECONDSの間にDYING_TICKS回の自分のtickを数える
+#[derive(Clone, Debug)]
+struct Dying {
+    // 死んだ蛇(snakesの添字)
+    snakes: Vec<usize>,
+    // 消え始めてからのtick数
+    tick: u32,
+    // 次のtickまでに溜まった秒数
+    elapsed: f32,
+}
+
+impl Dying {
+    fn new(snakes: &[usize]) -> Self {
+        Dying {
+            snakes: snakes.to_vec(),
+            tick: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    // dt秒進める。消え終わったらfalse
+    fn update(&mut self, dt: f32) -> bool {
+        let tick_seconds = DYING_SECONDS / DYING_TICKS as f32;
+        self.elapsed += dt;
+        while self.elapsed >= tick_seconds {
+            self.elapsed -= tick_seconds;
+            self.tick += 1;
+        }
+        self.tick < DYING_TICKS
+    }
+
+    // 消えている途中のi番目の蛇の残っている部分(消え終わった・点滅で消えている間はNone)
+    // 最初の3/4の間にbodyを尻尾から減らし、残りの間はheadだけを点滅させる
+    fn shown(&self, i: usize, snake: &Snake) -> Option<Snake> {
+        if !self.snakes.contains(&i) {
+            return Some(snake.clone());
+        }
+        let body_ticks = DYING_TICKS * 3 / 4;
+        let mut snake = snake.clone();
+        if self.tick < body_ticks {
+            let keep = snake.body.len() * (body_ticks - self.tick) as usize / body_ticks as usize;
+            snake.body.truncate(keep);
+            Some(snake)
+        } else {
+            snake.body.clear();
+            self.tick.is_multiple_of(2).then_some(snake)
+        }
+    }
+}
+
+// game内の全ての状態を管理
+pub struct GameState {
+    // 操作する蛇(1人プレイなら1匹、2人プレイなら2匹)
+    pub(crate) snakes: Vec<Snake>,
+    // 蛇ごとの操作(snakesと同じ順番)
+    pub(crate) controllers: Vec<Box<dyn SnakeController>>,
+    // プレイヤーの人数
+    players: usize,
+    // 1人プレイでコンピュータの蛇と対戦するか
+    opponent: bool,
+    pub(crate) foods: Vec<Food>,
+    pub(crate) obstacles: Vec<Obstacle>,
+    // ワープマスの組
+    pub(crate) portals: Vec<Portal>,
+    // 選べるステージの一覧
+    levels: Vec<Level>,
+    // 選択中のステージ(levelsの添字)。キャンペーンでは今のステージ
+    level: usize,
+    // キャンペーン(foodを食べると次のステージに進む)で遊ぶか
+    campaign: bool,
+    // キャンペーンで進んだステージの数と、今のステージで食べたfoodの個数
+    stage: usize,
+    level_food: u32,
+    // 選択中のゲームのモード
+    mode: GameMode,
+    // 目標に挑戦するか(Jキーで切り替える)と、このゲームの目標
+    use_challenges: bool,
+    challenges: Vec<Box<dyn Challenge>>,
+    // チュートリアル中ならその進み具合
+    tutorial: Option<Tutorial>,
+    // 制限時間のあるモードなら残りの秒数
+    // 1tickごとにその間の秒数だけ減らす(リプレイでも同じtickで終わる)
+    time_left: Option<f32>,
+    // 盤面が外側から縮んでいくモードなら次に縮むまでの秒数(time_leftと同じく1tickごとに減らす)
+    next_shrink: Option<f32>,
+    // バトルで死んだ蛇(snakesの添字)を死んだ順に
+    standings: Vec<usize>,
+    // このゲームの乱数は全てこのrngから作る(ゲームの途中でシードを変えない)
+    rng: Rand32,
+    // rngのシード
+    pub(crate) seed: u64,
+    // 食べたfoodの個数
+    foods_eaten: u32,
+    // 残機(1人プレイのみ)
+    pub(crate) lives: u32,
+    // 盤面に置かれたパワーアップ
+    power_up: Option<PowerUp>,
+    // 1秒間にupdateが呼ばれる回数(foodを食べるほど速くなる)
+    tick_rate: u32,
+    // 保存される設定
+    pub settings: Settings,
+    // 選択中の難易度
+    difficulty: Difficulty,
+    // 盤面の大きさと画面端の扱い(難易度とモードで決まる)
+    pub(crate) board: Board,
+    // 盤面のマスごとの中身(tickごとと、foodを置く前に作り直す)
+    cells: Cells,
+    // 保存されるハイスコア表
+    high_scores: HighScores,
+    // 今回のスコアがハイスコア表に入った順位と、記録した時刻
+    high_score_rank: Option<usize>,
+    high_score_time: u64,
+    // ハイスコア表に入った時の名前の入力欄(入力中だけSome)
+    name_entry: Option<TextInput>,
+    // 現在のシーン
+    pub(crate) scene: Scene,
+    // ゲームが終わった理由(Scene::GameOverの間だけSome)
+    pub(crate) outcome: Option<GameOutcome>,
+    // 終了確認のオーバーレイを表示中か
+    confirm_quit: bool,
+    // 左スティックの傾き(x, y)
+    stick: (f32, f32),
+    // 左スティックで最後に入力した方向
+    stick_dir: Option<Direction>,
+    // Shiftを押しているか(押している間は1人目の蛇のスピードを上げる)
+    boost_held: bool,
+    // スワイプ中の指やマウスの、最後に方向を変えた位置(画面の座標)
+    swipe_start: Option<(f32, f32)>,
+    // マウスカーソルの位置(画面の座標)。マウスで操作する時に使う
+    cursor: Option<(f32, f32)>,
+    // エディタのシーン中だけSome
+    editor: Option<Editor>,
+    // チャレンジコードの入力欄と、読めなかった・始められなかった理由
+    code_input: TextInput,
+    code_error: Option<String>,
+    // 操作設定の画面で選んでいる操作(Action::ALLの添字)
+    controls_selected: usize,
+    // 操作設定の画面で割り当てるキーを待っているか
+    waiting_for_key: bool,
+    // デモ(自動操作)中か
+    demo: bool,
+    // ウィンドウなしで動かしているか(Simulation。記録は何も保存しない)
+    pub(crate) headless: bool,
+    // 何も入力がないまま経過したtick数
+    idle_ticks: u32,
+    // プレイ開始から経過したtick数
+    pub(crate) tick: u32,
+    // このゲームで人が操作する蛇が一番長くなった時の長さ
+    longest_snake: usize,
+    // このゲームを遊んだ時間(秒)
+    play_time: f64,
+    // 保存される通算成績
+    pub stats: Stats,
+    // このゲームで人が操作する蛇が左に曲がったか
+    turned_left: bool,
+    // 保存される実績
+    pub achievements: Achievements,
+    // リソースから読み込んだ、ゲームのルールを変えるスクリプト
+    pub scripts: Vec<Script>,
+    // このゲームで実行するスクリプト(Dailyでは使わず、リプレイとネットワーク対戦は記録されたもの)
+    active_scripts: Vec<Script>,
+    // F3で切り替えるデバッグ用の表示
+    debug: DebugOverlay,
+    // コマ送り中か(F4で切り替え、F5で1tickだけ進める)
+    frame_step: bool,
+    // コマ送り中に次のupdateで1tick進めるか
+    step_requested: bool,
+    // オンラインのランキング(ゲームオーバーで送信する)
+    leaderboard: Leaderboard,
+    // ネットワーク対戦中ならその接続
+    net: Option<NetSession>,
+    // このゲームの方向入力の記録
+    recording: Vec<ReplayInput>,
+    // リプレイの再生中ならその内容
+    pub(crate) playback: Option<Playback>,
+    // 同じ設定とシードで一番スコアの高かったゲームを横で再生する(ぶつからない半透明の蛇)
+    ghost: Option<Box<Simulation>>,
+    // 効果音
+    pub audio: AudioManager,
+    // DiscordのRich Presence(discordフィーチャーなしでは何もしない)
+    pub presence: Presence,
+    // 蛇とfoodのテクスチャ(読み込めなければ四角で描画する)
+    pub sprites: Option<Sprites>,
+    // ウィンドウの大きさ(論理ピクセル)。盤面はこの中に縦横比を保って収める
+    window_size: (f32, f32),
+    // 盤面のマスの四角形をまとめて描画するバッチ(最初のdrawで作る)
+    batch: Option<CellBatch>,
+    // 盤面のマスが小さすぎる時に表示するミニマップ(最初のdrawで作る)
+    minimap: Option<Minimap>,
+    // プレイ中の最後の数秒をGIFに書き出すために取っておくフレーム
+    recorder: GifRecorder,
+    // foodを食べた時に飛び散るパーティクル
+    particles: Particles,
+    // 自分にぶつかった時の画面の揺れと赤いフラッシュ
+    death_effect: DeathEffect,
+    // ゲームオーバーで蛇が消えていく途中ならその状態(消え終わるまでゲームオーバーの画面を出さない)
+    dying: Option<Dying>,
+    // 蛇が動き出すまでの残りの秒数(0なら数えていない)
+    countdown: f32,
+    // 画面全体にかけるシェーダーの効果(使わなければNone)
+    pub post_process: Option<PostProcess>,
+    // config.tomlで指定した盤面の後ろに描く画像
+    pub background_image: Option<BackgroundImage>,
+    // 盤面の背景の模様のMeshと、それを作った時の模様と盤面のマス数(どちらかが変わったら作り直す)
+    background: Option<((Background, i16, i16), graphics::Mesh)>,
+    // 次のupdateの最後に鳴らす効果音
+    pub(crate) sounds: Vec<Sound>,
+    // このupdateで起きたできごと(updateの最後に配る)
+    pub(crate) events: Vec<GameEvent>,
+}
+
+// newでGameStateのインスタンス(ゲームの初期状態)を作成
+impl GameState {
+    pub fn new(
+        settings: Settings,
+        high_scores: HighScores,
+        difficulty: Difficulty,
+        levels: Vec<Level>,
+        level: usize,
+        players: usize,
+        opponent: bool,
+    ) -> Self {
+        // シードの指定がなければランダムなシードを作る
+        let seed = settings.seed.unwrap_or_else(random_seed);
+        let rng = Rand32::new(seed);
+        let obstacles: Vec<Obstacle> = levels[level]
+            .walls
+            .iter()
+            .map(|pos| Obstacle::new(*pos))
+            .collect();
+
+        let mut state = GameState {
+            snakes: Vec::new(),
+            controllers: Vec::new(),
+            players,
+            opponent,
+            foods: Vec::new(),
+            obstacles,
+            portals: levels[level].portals.clone(),
+            levels,
+            level,
+            campaign: false,
+            stage: 0,
+            level_food: 0,
+            mode: GameMode::Classic,
+            use_challenges: false,
+            challenges: Vec::new(),
+            tutorial: None,
+            time_left: None,
+            next_shrink: None,
+            standings: Vec::new(),
+            rng,
+            seed,
+            foods_eaten: 0,
+            lives: settings.lives.max(1),
+            power_up: None,
+            tick_rate: difficulty.tick_rate(),
+            settings,
+            difficulty,
+            board: Board::from_config(difficulty.topology),
+            cells: Cells::new(Board::from_config(difficulty.topology)),
+            high_scores,
+            high_score_rank: None,
+            high_score_time: 0,
+            name_entry: None,
+            scene: Scene::MainMenu,
+            outcome: None,
+            confirm_quit: false,
+            stick: (0.0, 0.0),
+            stick_dir: None,
+            boost_held: false,
+            swipe_start: None,
+            cursor: None,
+            editor: None,
+            code_input: TextInput::new(CODE_INPUT_LENGTH),
+            code_error: None,
+            controls_selected: 0,
+            waiting_for_key: false,
+            demo: false,
+            headless: false,
+            idle_ticks: 0,
+            tick: 0,
+            longest_snake: 0,
+            play_time: 0.0,
+            stats: Stats::default(),
+            turned_left: false,
+            achievements: Achievements::default(),
+            scripts: Vec::new(),
+            active_scripts: Vec::new(),
+            debug: DebugOverlay::default(),
+            frame_step: false,
+            step_requested: false,
+            leaderboard: Leaderboard::default(),
+            net: None,
+            recording: Vec::new(),
+            playback: None,
+            ghost: None,
+            audio: AudioManager::default(),
+            presence: Presence::default(),
+            sprites: None,
+            window_size: config().screen_size(),
+            batch: None,
+            minimap: None,
+            recorder: GifRecorder::default(),
+            particles: Particles::default(),
+            death_effect: DeathEffect::default(),
+            dying: None,
+            countdown: 0.0,
+            post_process: None,
+            background_image: None,
+            background: None,
+            sounds: Vec::new(),
+            events: Vec::new(),
+        };
+        // ステージのスタート位置から始める
+        state.snakes = (0..players).map(|i| state.spawn_snake(i)).collect();
+        // 対戦相手は2人目の位置から始める
+        if opponent && players == 1 {
+            let cpu = state
+                .spawn_snake(1)
+                .with_colors([0.8, 0.2, 1.0, 1.0], [0.3, 0.1, 0.4, 1.0])
+                .computer();
+            state.snakes.push(cpu);
+        }
+        state.reset_controllers();
+        // Then we choose a random place to put our piece of food using the helper we made
+        // earlier.
+        state.fill_foods();
+        state
+    }
+
+    // i番目のプレイヤーのスタート位置と向き
+    // 1人目はステージのスタート位置から右向き、2人目は左右反対の位置から左向き
+    // (そこが壁ならスタート位置の2マス下から右向き)
+    fn start_of(&self, i: usize) -> (GridPosition, Direction) {
+        let grid = config().grid_size;
+        let start = self.levels[self.level].snake_start;
+        if i == 0 {
+            return (start, Direction::Right);
+        }
+        let mirrored = GridPosition::new(grid.0 - 1 - start.x, start.y);
+        let blocked = |pos: GridPosition| {
+            self.obstacles.iter().any(|o| o.pos == pos) || self.levels[self.level].has_portal(pos)
+        };
+        if !blocked(mirrored) && !blocked(Snake::behind(mirrored, Direction::Left, self.board)) {
+            (mirrored, Direction::Left)
+        } else {
+            let below = GridPosition::new(start.x, (start.y + 2).rem_euclid(grid.1));
+            (below, Direction::Right)
+        }
+    }
+
+    // i番目のプレイヤーの蛇をスタート位置に作成(2人目は青系の色)
+    fn spawn_snake(&self, i: usize) -> Snake {
+        let (pos, dir) = self.start_of(i);
+        let snake = Snake::facing(pos, dir, self.board);
+        if i == 0 {
+            snake
+        } else {
+            snake.with_colors([0.0, 0.8, 1.0, 1.0], [0.0, 0.3, 0.4, 1.0])
+        }
+    }
+
+    // 設定とハイスコア、難易度、ステージを引き継いで新しいゲームを始める
+    fn restart(&mut self) {
+        self.reset();
+        self.play();
+    }
+
+    // プレイ中にする。3, 2, 1と数えてから蛇が動き出す
+    fn play(&mut self) {
+        self.scene = Scene::Playing;
+        self.countdown = COUNTDOWN_SECONDS;
+    }
+
+    // チュートリアルを最初から始める(1人プレイで、カウントダウンなしですぐに最初の手順を出す)
+    fn start_tutorial(&mut self) {
+        self.players = 1;
+        self.opponent = false;
+        self.reset();
+        self.tutorial = Some(Tutorial::default());
+        self.scene = Scene::Playing;
+    }
+
+    // チュートリアルでゲームを止めて方向キーを待っているか
+    fn tutorial_waiting(&self) -> bool {
+        self.tutorial
+            .as_ref()
+            .is_some_and(|t| t.waiting_for().is_some())
+    }
+
+    // ゲームを止めている間は、チュートリアルが待っている方向の入力だけを受け付ける
+    fn update_tutorial(&mut self) {
+        let Some(waiting_for) = self.tutorial.as_ref().and_then(Tutorial::waiting_for) else {
+            return;
+        };
+        for dir in self.controllers[0].inputs(&self.view(0)) {
+            if dir == waiting_for {
+                self.apply_input(0, dir);
+            }
+        }
+    }
+
+    // 蛇が動き出すのを待っているか
+    // デモとリプレイは見ているだけ、ネットワーク対戦は相手と合わせるので待たない
+    fn counting_down(&self) -> bool {
+        self.countdown > 0.0 && !self.demo && self.playback.is_none() && self.net.is_none()
+    }
+
+    // 設定とハイスコア、難易度、ステージ、人数以外を初期状態に戻す
+    // キャンペーンで進んだステージは最初のステージに戻す
+    // Dailyでシードを指定していなければ、今日の日付からシードとステージを決めて難易度はNormalにする
+    fn reset(&mut self) {
+        self.level = self.start_level();
+        let user_seed = self.settings.seed;
+        if self.mode.rules().daily && user_seed.is_none() {
+            let now = now_timestamp();
+            self.settings.seed = Some(daily_seed(now));
+            self.level = daily_level(now, Level::builtin().len());
+            self.set_difficulty(Difficulty::NORMAL);
+        }
+        let high_scores = std::mem::take(&mut self.high_scores);
+        let levels = std::mem::take(&mut self.levels);
+        let audio = std::mem::take(&mut self.audio);
+        let presence = std::mem::take(&mut self.presence);
+        let mut recorder = std::mem::take(&mut self.recorder);
+        recorder.clear();
+        let stats = std::mem::take(&mut self.stats);
+        let achievements = std::mem::take(&mut self.achievements);
+        let scripts = std::mem::take(&mut self.scripts);
+        let debug = std::mem::take(&mut self.debug);
+        let sprites = self.sprites.take();
+        let background_image = self.background_image.take();
+        let post_process = self.post_process.take();
+        let rules = self.mode.rules();
+        *self = GameState {
+            post_process,
+            window_size: self.window_size,
+            audio,
+            presence,
+            recorder,
+            sprites,
+            background_image,
+            stats,
+            achievements,
+            active_scripts: if rules.daily {
+                Vec::new()
+            } else {
+                scripts.clone()
+            },
+            scripts,
+            debug,
+            frame_step: self.frame_step,
+            campaign: self.campaign,
+            mode: self.mode,
+            use_challenges: self.use_challenges,
+            challenges: if self.use_challenges {
+                challenge::all()
+            } else {
+                Vec::new()
+            },
+            time_left: rules.time_limit,
+            next_shrink: rules.shrink_interval,
+            board: Board::from_config(rules.topology.unwrap_or(self.difficulty.topology)),
+            ..GameState::new(
+                self.settings.clone(),
+                high_scores,
+                self.difficulty,
+                levels,
+                self.level,
+                self.players,
+                self.opponent,
+            )
+        };
+        self.settings.seed = user_seed;
+        if rules.battle {
+            self.setup_battle();
+        }
+        for snake in &mut self.snakes {
+            snake.hunger_interval = rules.hunger_ticks;
+        }
+    }
+
+    // バトルの盤面を作る(ステージの壁とワープマスは使わない)
+    // グリッドのBATTLE_GRID_SCALE倍の盤面の左右に、人とコンピュータの蛇を交互に1行ずつずらして並べる
+    fn setup_battle(&mut self) {
+        let grid = config().grid_size;
+        self.board = Board::new(
+            (grid.0 * BATTLE_GRID_SCALE, grid.1 * BATTLE_GRID_SCALE),
+            self.board.topology,
+        );
+        self.obstacles.clear();
+        self.portals.clear();
+        let (w, h) = (self.board.width, self.board.height);
+        let count = config().battle_snakes + 1;
+        self.snakes = (0..count)
+            .map(|i| {
+                let y = h * (i as i16 + 1) / (count as i16 + 1);
+                let snake = if i % 2 == 0 {
+                    Snake::facing(GridPosition::new(w / 4, y), Direction::Right, self.board)
+                } else {
+                    Snake::facing(
+                        GridPosition::new(w - 1 - w / 4, y),
+                        Direction::Left,
+                        self.board,
+                    )
+                };
+                if i == 0 {
+                    return snake;
+                }
+                let [r, g, b, a] = BATTLE_SNAKE_COLORS[(i - 1) % BATTLE_SNAKE_COLORS.len()];
+                snake
+                    .with_colors([r, g, b, a], [r * 0.4, g * 0.4, b * 0.4, a])
+                    .computer()
+            })
+            .collect();
+        self.reset_controllers();
+        self.foods.clear();
+        self.fill_foods();
+    }
+
+    // 盤面を外側から1周縮める(SURVIVAL_MIN_SIZEより小さくはしない)
+    // 通れなくなったマスのfoodとパワーアップは消し、foodは内側に置き直す
+    fn shrink_board(&mut self) {
+        let max = (self.board.width.min(self.board.height) - SURVIVAL_MIN_SIZE) / 2;
+        if self.board.margin >= max {
+            return;
+        }
+        self.board.margin += 1;
+        let board = self.board;
+        self.foods.retain(|food| board.contains(food.pos));
+        if self.power_up.is_some_and(|p| !board.contains(p.pos)) {
+            self.power_up = None;
+        }
+        self.fill_foods();
+    }
+
+    // モードを変更し、そのルールでゲームを初期状態に戻す
+    fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+        self.reset();
+    }
+
+    // キャンペーンで最初に遊んだステージ(キャンペーンでなければ選択中のステージ)
+    fn start_level(&self) -> usize {
+        let len = self.levels.len();
+        (self.level + len - self.stage % len) % len
+    }
+
+    // キャンペーンで次のステージに進む
+    // 蛇はスコアと残機、速さを引き継いでスタート位置からやり直し、foodも置き直す
+    fn next_level(&mut self) {
+        self.stage += 1;
+        self.level_food = 0;
+        self.level = (self.level + 1) % self.levels.len();
+        let level = &self.levels[self.level];
+        self.obstacles = level.walls.iter().map(|pos| Obstacle::new(*pos)).collect();
+        self.portals = level.portals.clone();
+        for i in 0..self.snakes.len() {
+            let (pos, dir) = self.start_of(i);
+            self.snakes[i].respawn(pos, dir, self.board);
+        }
+        self.foods.clear();
+        self.power_up = None;
+        self.fill_foods();
+        self.tick_rate = (self.tick_rate + CAMPAIGN_SPEEDUP).min(self.settings.max_tick_rate);
+        // ステージ名を出して3, 2, 1と数えてから始める
+        self.play();
+    }
+
+    // 蛇と障害物、他のfoodに重ならないランダムな位置にfoodを作成
+    fn spawn_food(&mut self, zone: &[GridPosition]) -> Option<Food> {
+        let pos = self.free_cell(zone)?;
+        Some(Food::random(&mut self.rng, pos))
+    }
+
+    // 何も置かれていないマスからランダムに1つ選ぶ
+    // zoneが空でなければその中から選ぶ(全て埋まっていればグリッド全体から)
+    // 空いているマスが1つもなければNone
+    fn free_cell(&mut self, zone: &[GridPosition]) -> Option<GridPosition> {
+        // 盤面が縮んで通れなくなったマスは壁になっているので置かない
+        self.refresh_cells();
+        let cells = &self.cells;
+        let mut free: Vec<GridPosition> = zone
+            .iter()
+            .copied()
+            .filter(|pos| cells.is_empty(*pos))
+            .collect();
+        if free.is_empty() {
+            free = cells
+                .iter()
+                .filter(|(_, cell)| *cell == Cell::Empty)
+                .map(|(pos, _)| pos)
+                .collect();
+        }
+        if free.is_empty() {
+            return None;
+        }
+        Some(free[self.rng.rand_range(0..free.len() as u32) as usize])
+    }
+
+    // 今の蛇・food・パワーアップ・障害物・ワープマスの位置から、盤面のマスの中身を作り直す
+    // (死んだ蛇は盤面から消えているので入れない)
+    fn refresh_cells(&mut self) {
+        let cells = &mut self.cells;
+        cells.reset(self.board);
+        for pos in self.portals.iter().flat_map(|p| [p.a, p.b]) {
+            cells.set(pos, Cell::Portal);
+        }
+        for obstacle in &self.obstacles {
+            cells.set(obstacle.pos, Cell::Wall);
+        }
+        for food in &self.foods {
+            cells.set(food.pos, Cell::Food(food.kind));
+        }
+        if let Some(power_up) = self.power_up {
+            cells.set(power_up.pos, Cell::PowerUp);
+        }
+        for (i, snake) in self.snakes.iter().enumerate() {
+            if snake.dead {
+                continue;
+            }
+            for seg in snake.body.iter() {
+                cells.set(seg.pos, Cell::SnakeBody(i));
+            }
+            cells.set(snake.head.pos, Cell::SnakeHead(i));
+        }
+    }
+
+    // 蛇の操作方法(controller)に合わせて蛇ごとの操作を作り直す
+    pub(crate) fn reset_controllers(&mut self) {
+        self.controllers = self
+            .snakes
+            .iter()
+            .map(|snake| controller_for(snake.controller))
+            .collect();
+    }
+
+    // i番目の蛇から見た盤面
+    fn view(&self, i: usize) -> BoardView<'_> {
+        BoardView {
+            index: i,
+            tick: self.tick,
+            board: self.board,
+            cells: &self.cells,
+            snakes: &self.snakes,
+            foods: &self.foods,
+            obstacles: &self.obstacles,
+            portals: &self.portals,
+        }
+    }
+
+    // ボーナス以外のfoodが設定の個数になるまで置く(空いているマスがなければそこまで)
+    // バトルではステージのfoodが出るマスは使わず、蛇の数だけは置く
+    fn fill_foods(&mut self) {
+        let battle = self.mode.rules().battle;
+        let zone = if battle {
+            Vec::new()
+        } else {
+            self.levels[self.level].food_zone.clone()
+        };
+        let target = if battle {
+            self.settings.food_count.max(self.snakes.len())
+        } else {
+            self.settings.food_count.max(1)
+        };
+        while self.foods.iter().filter(|f| f.lifetime.is_none()).count() < target {
+            match self.spawn_food(&zone) {
+                Some(food) => self.foods.push(food),
+                None => break,
+            }
+        }
+    }
+
+    // プレイ中の1tick分の更新
+    // 前後で盤面のマスの中身を作り直す(前はコンピュータの操作、後はミニマップが使う)
+    pub(crate) fn update_playing(&mut self) {
+        self.refresh_cells();
+        // 最初のtickで一番いいゲームのリプレイを読み込み、その後は同じtickずつ進める
+        if self.tick == 0 {
+            self.ghost = self.load_ghost().map(Box::new);
+        }
+        self.play_tick();
+        if let Some(ghost) = &mut self.ghost {
+            ghost.step(None);
+        }
+        self.refresh_cells();
+    }
+
+    // モードごとの一番スコアの高いゲームのリプレイの場所(ハイスコアと同じディレクトリ内)
+    fn ghost_path(&self) -> PathBuf {
+        self.high_scores
+            .path
+            .with_file_name(GHOSTS_DIR)
+            .join(format!("{}.toml", self.mode.key()))
+    }
+
+    // 人が1人で遊ぶゲームで、このモードの一番いいゲームが同じ設定とシードならその再生を始める
+    fn load_ghost(&self) -> Option<Simulation> {
+        let watching = self.demo || self.playback.is_some() || self.headless;
+        if watching || self.net.is_some() || self.tutorial.is_some() || !self.solo() {
+            return None;
+        }
+        let best = Replay::load(&self.ghost_path()).ok()?;
+        if !best.same_game(&self.replay()) {
+            return None;
+        }
+        Simulation::from_replay(self.settings.clone(), self.levels.clone(), best)
+    }
+
+    // 同じシードの一番いいゲームより高いスコアなら(シードが変わったら必ず)置き換える
+    fn save_ghost(&self) {
+        let replay = self.replay();
+        let path = self.ghost_path();
+        let best = Replay::load(&path)
+            .ok()
+            .filter(|best| best.seed == replay.seed);
+        if best.is_some_and(|best| best.score >= replay.score) {
+            return;
+        }
+        if let Err(e) = replay.save(&path) {
+            eprintln!("Could not save ghost replay: {}", e);
+        }
+    }
+
+    // 入力を受け取って蛇を動かし、ぶつかった・食べた結果を処理する
+    fn play_tick(&mut self) {
+        self.steer_to_cursor();
+        // 蛇ごとの操作(キー入力・コンピュータ・リプレイ・ネットワーク)からこのtickの入力を受け取る
+        for i in 0..self.snakes.len() {
+            if self.snakes[i].dead {
+                continue;
+            }
+            for dir in self.controllers[i].inputs(&self.view(i)) {
+                self.apply_input(i, dir);
+            }
+        }
+        // スピードを上げるのは人が操作する1人目の蛇だけ(ネットワーク対戦では相手とtickを揃えるので使わない)
+        let boost = self.boost_held && self.net.is_none() && !self.demo && self.playback.is_none();
+        if self.snakes[0].controller == Controller::Human {
+            self.snakes[0].update_boost(boost);
+        }
+        self.tick += 1;
+        if let Some(tutorial) = &mut self.tutorial {
+            tutorial.on_tick();
+        }
+        // タイムアタックの制限時間が来たら終わり
+        // (+/-キーで変えた速さはリプレイに残らないので、1tickの秒数には含めない)
+        let seconds = 1.0 / self.game_tick_rate() as f32;
+        if let Some(time_left) = &mut self.time_left {
+            *time_left -= seconds;
+            if *time_left <= 0.0 {
+                *time_left = 0.0;
+                self.end_game(GameOutcome::TimeUp);
+                return;
+            }
+        }
+        // サバイバルでは一定の間隔で盤面が外側から1周縮む
+        let interval = self.mode.rules().shrink_interval.unwrap_or(0.0);
+        if let Some(next_shrink) = &mut self.next_shrink {
+            *next_shrink -= seconds;
+            if *next_shrink <= 0.0 {
+                *next_shrink += interval;
+                self.shrink_board();
+            }
+        }
+        let all: Vec<usize> = (0..self.snakes.len()).collect();
+        self.run_scripts(Hook::Tick(self.tick), &all);
+        // COMBO_TICKS以内に次のfoodを食べられなかったらコンボが途切れる
+        for snake in &mut self.snakes {
+            if snake
+                .last_food_tick
+                .is_some_and(|t| self.tick - t > COMBO_TICKS)
+            {
+                snake.combo = 0;
+            }
+        }
+        // Shiftでスピードを上げている蛇は、他の蛇より先にもう1マス進む(他の蛇の速さは変わらない)
+        let boosting: Vec<bool> = self.snakes.iter().map(|snake| snake.boosting).collect();
+        if boosting.contains(&true) && !self.move_snakes(&boosting) {
+            return;
+        }
+        if !self.move_snakes(&vec![true; self.snakes.len()]) {
+            return;
+        }
+        self.update_bonus_food();
+        self.update_wandering_food();
+        self.update_power_ups();
+        self.check_achievements(false);
+    }
+
+    // moving[i]がtrueの蛇を1マス進め、ぶつかった・食べた結果を処理する
+    // gameoverになるか次のステージに進んだらfalse
+    fn move_snakes(&mut self, moving: &[bool]) -> bool {
+        // ランダムフードの位置に蛇がいけば
+        for (i, snake) in self.snakes.iter_mut().enumerate() {
+            if snake.dead || !moving[i] {
+                continue;
+            }
+            let dir = snake.dir;
+            let head = snake.head.pos;
+            snake.update(&self.foods, self.board, &self.obstacles, &self.portals);
+            if snake.controller == Controller::Human && snake.dir == dir.left() {
+                self.turned_left = true;
+            }
+            if snake.ate != Some(Ate::Wall) && self.board.wraps(head, snake.dir) {
+                self.events.push(GameEvent::SnakeWrapped { snake: i });
+            }
+        }
+        // 他の蛇とぶつかったか(全員動いてから判定する)
+        let hit_other = self.collisions_between_snakes();
+        // ぶつかって死んだ蛇
+        let mut dead = Vec::new();
+        for (i, hit) in hit_other.into_iter().enumerate() {
+            if self.snakes[i].dead {
+                continue;
+            }
+            if hit {
+                if self.crash(i) {
+                    dead.push(i);
+                }
+                continue;
+            }
+            // 動かなかった蛇のateは前に動いた時のもの
+            if !moving[i] {
+                continue;
+            }
+            // 蛇が何か食った場合
+            if let Some(ate) = self.snakes[i].ate {
+                // If it did, we want to know what it ate.
+                match ate {
+                    Ate::Food => {
+                        if !self.eat_food(i) {
+                            dead.push(i);
+                        }
+                    }
+                    // Ghost中は自分の体を通り抜ける
+                    Ate::Itself if self.snakes[i].has_effect(PowerUpKind::Ghost) => (),
+                    // bodyか壁か障害物だったら死ぬ
+                    Ate::Itself | Ate::Wall | Ate::Obstacle => {
+                        if self.crash(i) {
+                            dead.push(i);
+                        }
+                    }
+                    // 飢えはShieldでも防げない
+                    Ate::Starved => dead.push(i),
+                }
+            }
+        }
+        // 通算成績のために人が操作する蛇の最長を覚えておく
+        for snake in &self.snakes {
+            if snake.controller == Controller::Human {
+                self.longest_snake = self.longest_snake.max(snake.len());
+            }
+        }
+        if !dead.is_empty() {
+            self.run_scripts(Hook::Death, &dead);
+            for &snake in &dead {
+                self.events.push(GameEvent::SnakeDied {
+                    snake,
+                    cause: self.snakes[snake].ate,
+                });
+            }
+            self.on_dead(&dead);
+        }
+        // 残機がなくなってgameoverになった
+        if self.scene != Scene::Playing {
+            return false;
+        }
+        // 障害物以外のマスが全て蛇で埋まったら勝ち
+        if self.occupied_cells() >= self.board.width as usize * self.board.height as usize {
+            self.end_game(GameOutcome::Won);
+            return false;
+        }
+        // キャンペーンではステージごとにCAMPAIGN_FOOD_COUNT個食べたら次のステージに進む
+        if self.campaign && !self.mode.rules().battle && self.level_food >= CAMPAIGN_FOOD_COUNT {
+            self.next_level();
+            return false;
+        }
+        true
+    }
+
+    // 実績の条件を満たしていれば解除して保存する(wonはこのゲームに勝ったか)
+    fn check_achievements(&mut self, won: bool) {
+        if self.demo || self.playback.is_some() || self.headless {
+            return;
+        }
+        let mut unlocked = false;
+        if self.stats.foods_eaten + self.foods_eaten >= 100 {
+            unlocked |= self.achievements.unlock(Achievement::Eat100);
+        }
+        if self.longest_snake >= 50 {
+            unlocked |= self.achievements.unlock(Achievement::Length50);
+        }
+        if self.play_time >= 300.0 {
+            unlocked |= self.achievements.unlock(Achievement::Survive5Minutes);
+        }
+        if won && !self.turned_left {
+            unlocked |= self.achievements.unlock(Achievement::NoLeftTurns);
+        }
+        if unlocked {
+            if let Err(e) = self.achievements.save() {
+                eprintln!("Could not save achievements: {}", e);
+            }
+        }
+    }
+
+    // 蛇ごとに、他の蛇にぶつかって死ぬならtrue(死んだ蛇にはぶつからない)
+    // head同士なら短い方(同じ長さなら両方)、bodyにheadが入ったらモードのルールで決まる
+    // TailCutのルールでは入られた蛇の尻尾を切り取ってfoodに変える
+    fn collisions_between_snakes(&mut self) -> Vec<bool> {
+        let rule = self.mode.rules().collision;
+        let mut hit = vec![false; self.snakes.len()];
+        // 切り取る蛇と、bodyのどこから後ろを切り取るか
+        let mut cuts = Vec::new();
+        for (i, snake) in self.snakes.iter().enumerate() {
+            if snake.dead {
+                continue;
+            }
+            for (j, other) in self.snakes.iter().enumerate() {
+                if i == j || other.dead {
+                    continue;
+                }
+                if other.head.pos == snake.head.pos {
+                    hit[i] |= snake.len() <= other.len();
+                } else if other.body.contains(snake.head.pos) {
+                    match rule {
+                        CollisionRule::Standard => hit[i] = true,
+                        CollisionRule::TailCut => {
+                            let k = other.body.iter().position(|s| s.pos == snake.head.pos);
+                            cuts.extend(k.map(|k| (j, k)));
+                        }
+                    }
+                }
+            }
+        }
+        // 同じ蛇が何か所も切られたらheadに一番近いところで切る
+        // 入った蛇のheadがいるマスはfoodにしない
+        cuts.sort_by_key(|&(_, k)| k);
+        for (j, k) in cuts {
+            if k < self.snakes[j].body.len() {
+                let severed: Vec<GridPosition> = self.snakes[j]
+                    .body
+                    .split_off(k)
+                    .into_iter()
+                    .skip(1)
+                    .map(|s| s.pos)
+                    .collect();
+                self.snakes[j].pending_growth = 0;
+                self.drop_pellets(severed);
+            }
+        }
+        hit
+    }
+
+    // i番目の蛇がぶつかった。Shield中なら1回だけ防いでfalseを返す
+    fn crash(&mut self, i: usize) -> bool {
+        !self.snakes[i].consume_effect(PowerUpKind::Shield)
+    }
+
+    // i番目の蛇がheadの位置のfoodを食べる
+    // 種類ごとの効果を適用し、食べたfoodだけ置き直す。毒で死んだらfalse
+    fn eat_food(&mut self, i: usize) -> bool {
+        let head = self.snakes[i].head.pos;
+        let Some(index) = self.foods.iter().position(|f| f.pos == head) else {
+            return true;
+        };
+        let food = self.foods.swap_remove(index);
+        let growth_per_food = self.difficulty.growth_per_food;
+        let score_multiplier = self.mode.rules().score_multiplier;
+        let tick = self.tick;
+        let snake = &mut self.snakes[i];
+        let mut alive = true;
+        match food.kind {
+            // valueの分だけ伸ばしてスコアを加算
+            FoodKind::Normal | FoodKind::Golden | FoodKind::Wandering | FoodKind::Confusion => {
+                snake.pending_growth += food.value * growth_per_food;
+                let multiplier = match food.kind {
+                    FoodKind::Golden => GOLDEN_SCORE_MULTIPLIER,
+                    FoodKind::Wandering => WANDERING_SCORE_MULTIPLIER,
+                    _ => 1,
+                } * score_multiplier;
+                // コンボが続いていれば数を増やし、途切れていれば1から
+                snake.combo = if snake.combo > 0 { snake.combo + 1 } else { 1 };
+                snake.max_combo = snake.max_combo.max(snake.combo);
+                snake.last_food_tick = Some(tick);
+                let multiplier = multiplier * snake.combo.min(MAX_COMBO_MULTIPLIER);
+                snake.score += food.value as u32 * multiplier;
+                if food.kind == FoodKind::Confusion {
+                    snake.confusion = CONFUSION_TICKS;
+                }
+            }
+            // 縮めなければ死ぬ
+            FoodKind::Poison => alive = snake.shrink(POISON_SEGMENTS),
+            // 体を半分にする
+            FoodKind::Shrink => {
+                let count = snake.body.len() / 2;
+                snake.shrink(count);
+            }
+        }
+        self.events.push(GameEvent::FoodEaten {
+            snake: i,
+            pos: food.pos,
+            kind: food.kind,
+            bonus: food.lifetime.is_some(),
+        });
+        // ボーナスのfoodは置き直さない
+        if food.lifetime.is_none() {
+            self.fill_foods();
+        }
+        // SPEEDUP_FOOD_COUNT個ごとに上限まで速くする
+        self.foods_eaten += 1;
+        self.level_food += 1;
+        let time_bonus = self.mode.rules().time_bonus;
+        if let Some(time_left) = &mut self.time_left {
+            *time_left += time_bonus;
+        }
+        if self.foods_eaten.is_multiple_of(SPEEDUP_FOOD_COUNT) {
+            self.tick_rate = (self.tick_rate + 1).min(self.settings.max_tick_rate);
+        }
+        self.run_scripts(Hook::FoodEaten, &[i]);
+        alive
+    }
+
+    // 溜まったできごとを効果音・パーティクル・実績・通算成績に配る
+    fn dispatch_events(&mut self) {
+        // 目標は1人目の蛇について調べる
+        let context = ChallengeContext {
+            length: self.snakes.first().map_or(0, Snake::len),
+            seconds: self.play_time,
+        };
+        for challenge in &mut self.challenges {
+            for event in &self.events {
+                challenge.on_event(event, &context);
+            }
+            challenge.update(&context);
+        }
+        if let Some(tutorial) = &mut self.tutorial {
+            for event in &self.events {
+                tutorial.on_event(event);
+            }
+        }
+        for event in std::mem::take(&mut self.events) {
+            if let Some(sound) = Sound::for_event(&event) {
+                self.sounds.push(sound);
+            }
+            match event {
+                GameEvent::FoodEaten { pos, kind, .. } => {
+                    let color = self.settings.active_theme().food_color(kind);
+                    self.particles.burst(pos, color);
+                }
+                GameEvent::LevelCompleted { outcome } => self.record_game(outcome),
+                // 人の蛇が自分にぶつかったら画面を揺らして赤く光らせる
+                GameEvent::SnakeDied {
+                    snake,
+                    cause: Some(Ate::Itself),
+                } if self.snakes[snake].controller == Controller::Human => {
+                    self.death_effect.start()
+                }
+                GameEvent::SnakeDied { .. }
+                | GameEvent::PowerUpCollected { .. }
+                | GameEvent::SnakeTurned { .. }
+                | GameEvent::SnakeWrapped { .. } => (),
+            }
+        }
+    }
+
+    // 終わったゲームの実績を調べて通算成績に足す(デモやリプレイは記録しない)
+    fn record_game(&mut self, outcome: GameOutcome) {
+        if self.demo || self.playback.is_some() || self.headless {
+            return;
+        }
+        // 人が勝った時だけ勝利の実績を調べる(通算成績に足す前に調べる)
+        let won = match outcome {
+            GameOutcome::Won => true,
+            GameOutcome::PlayerWon(i) => self.snakes[i].controller == Controller::Human,
+            GameOutcome::Died | GameOutcome::Draw | GameOutcome::TimeUp => false,
+        };
+        self.check_achievements(won);
+        self.stats
+            .record(self.foods_eaten, self.longest_snake, self.play_time);
+        if let Err(e) = self.stats.save() {
+            eprintln!("Could not save statistics: {}", e);
+        }
+    }
+
+    // スクリプトのeventのフックのコマンドを実行する(targetsはスコアを変える蛇)
+    fn run_scripts(&mut self, event: Hook, targets: &[usize]) {
+        let commands: Vec<Command> = self
+            .active_scripts
+            .iter()
+            .flat_map(|script| script.commands(event))
+            .collect();
+        for command in commands {
+            match command {
+                Command::AddScore(n) => {
+                    for &i in targets {
+                        let snake = &mut self.snakes[i];
+                        snake.score = snake.score.saturating_add_signed(n);
+                    }
+                }
+                // 空いているマスがなければそこまで
+                Command::SpawnFood(n) => {
+                    let zone = self.levels[self.level].food_zone.clone();
+                    for _ in 0..n {
+                        match self.spawn_food(&zone) {
+                            Some(food) => self.foods.push(food),
+                            None => break,
+                        }
+                    }
+                }
+                Command::Speed(n) => {
+                    self.tick_rate = self
+                        .tick_rate
+                        .saturating_add_signed(n)
+                        .clamp(1, self.settings.max_tick_rate.max(1));
+                }
+            }
+        }
+    }
+
+    // 蛇が1匹だけ(対戦相手がいない)ならtrue
+    fn solo(&self) -> bool {
+        self.snakes.len() == 1
+    }
+
+    // i番目の蛇の表示名(「P1」「P2」、コンピュータなら「CPU」)
+    fn label(&self, i: usize) -> String {
+        match self.snakes[i].controller {
+            Controller::Human | Controller::Autopilot => format!("P{}", i + 1),
+            // バトルではコンピュータの蛇が何匹もいるので番号を付ける
+            Controller::Computer if self.snakes.len() > 2 => format!("CPU{}", i),
+            Controller::Computer => "CPU".to_string(),
+        }
+    }
+
+    // 蛇が死んだ時の処理
+    // 1人だけなら残機を減らし、対戦中なら生き残った方の勝ち
+    fn on_dead(&mut self, dead: &[usize]) {
+        if self.mode.rules().battle {
+            self.on_battle_dead(dead);
+        } else if self.solo() {
+            self.lose_life();
+        } else if dead.len() >= self.snakes.len() {
+            self.end_game(GameOutcome::Draw);
+        } else {
+            let winner = (0..self.snakes.len())
+                .find(|i| !dead.contains(i))
+                .unwrap_or(0);
+            self.end_game(GameOutcome::PlayerWon(winner));
+        }
+        // ゲームが終わったら死んだ蛇を尻尾から消していく
+        if self.scene == Scene::GameOver {
+            self.dying = Some(Dying::new(dead));
+        }
+    }
+
+    // バトルで蛇が死んだ時の処理
+    // 死んだ蛇は時間で消えるfoodに変えて盤面から消す
+    // 最後の1匹になったらその蛇の勝ち、人の蛇が死んだら他の蛇が残っていてもゲームオーバー
+    fn on_battle_dead(&mut self, dead: &[usize]) {
+        for &i in dead {
+            self.snakes[i].dead = true;
+            self.standings.push(i);
+            let cells = self.snakes[i].segments().collect();
+            self.drop_pellets(cells);
+        }
+        let alive: Vec<usize> = (0..self.snakes.len())
+            .filter(|&i| !self.snakes[i].dead)
+            .collect();
+        match alive[..] {
+            [] => self.end_game(GameOutcome::Draw),
+            [winner] => self.end_game(GameOutcome::PlayerWon(winner)),
+            _ if self.snakes[0].dead => self.end_game(GameOutcome::Died),
+            _ => (),
+        }
+    }
+
+    // 死んだ蛇や切り取られた尻尾のマスを、PELLET_SECONDS秒で消えるfoodにする
+    fn drop_pellets(&mut self, cells: Vec<GridPosition>) {
+        let lifetime = PELLET_SECONDS * self.tick_rate;
+        let board = self.board;
+        let cells: Vec<GridPosition> = cells
+            .into_iter()
+            .filter(|pos| board.contains(*pos) && !self.foods.iter().any(|f| f.pos == *pos))
+            .collect();
+        for pos in cells {
+            self.foods.push(Food {
+                lifetime: Some(lifetime),
+                ..Food::new(pos, 1, FoodKind::Normal)
+            });
+        }
+    }
+
+    // バトルの順位の表示(生き残った蛇は長い順に上位、死んだ蛇は後に死んだほど上位)
+    fn standings_lines(&self) -> Vec<String> {
+        let mut alive: Vec<usize> = (0..self.snakes.len())
+            .filter(|&i| !self.snakes[i].dead)
+            .collect();
+        alive.sort_by_key(|&i| std::cmp::Reverse(self.snakes[i].len()));
+        alive
+            .into_iter()
+            .chain(self.standings.iter().rev().copied())
+            .enumerate()
+            .map(|(rank, i)| {
+                format!(
+                    "{}. {}  Length: {}  Score: {}",
+                    rank + 1,
+                    self.label(i),
+                    self.snakes[i].len(),
+                    self.snakes[i].score
+                )
+            })
+            .collect()
+    }
+
+    // バトルの生き残っている蛇の数の表示(「Alive: 5/8」)
+    fn battle_text(&self) -> String {
+        if self.mode.rules().battle {
+            let alive = self.snakes.iter().filter(|snake| !snake.dead).count();
+            format!("  Alive: {}/{}", alive, self.snakes.len())
+        } else {
+            String::new()
+        }
+    }
+
+    // パワーアップを拾う・置く処理と、効果の残りtick数を減らす処理
+    fn update_power_ups(&mut self) {
+        for snake in &mut self.snakes {
+            for effect in &mut snake.effects {
+                effect.remaining = effect.remaining.saturating_sub(1);
+            }
+            snake.effects.retain(|effect| effect.remaining > 0);
+            snake.confusion = snake.confusion.saturating_sub(1);
+        }
+
+        match self.power_up {
+            Some(power_up) => {
+                // headの位置にあれば拾う(同じ効果中なら時間を延長)
+                let picker = self
+                    .snakes
+                    .iter_mut()
+                    .enumerate()
+                    .find(|(_, snake)| !snake.dead && snake.head.pos == power_up.pos);
+                if let Some((i, snake)) = picker {
+                    snake.effects.retain(|effect| effect.kind != power_up.kind);
+                    snake.effects.push(ActiveEffect {
+                        kind: power_up.kind,
+                        remaining: power_up.kind.duration(),
+                    });
+                    self.power_up = None;
+                    self.events.push(GameEvent::PowerUpCollected {
+                        snake: i,
+                        kind: power_up.kind,
+                    });
+                }
+            }
+            None => {
+                if self.rng.rand_range(0..POWER_UP_CHANCE) == 0 {
+                    let kind_index = self.rng.rand_range(0..PowerUpKind::ALL.len() as u32);
+                    let kind = PowerUpKind::ALL[kind_index as usize];
+                    self.power_up = self.free_cell(&[]).map(|pos| PowerUp { pos, kind });
+                }
+            }
+        }
+    }
+
+    // +/-キーで変えた分を足した1秒間のupdateの回数
+    // 制限時間などはgame_tick_rateで数えるので、速さを変えてもゲームの進み方は同じ
+    // ネットワーク対戦中は相手と同じ速さで進めるので変えない
+    fn base_tick_rate(&self) -> u32 {
+        if self.net.is_some() {
+            return self.tick_rate;
+        }
+        (self.tick_rate as i32 + self.settings.speed_adjust).max(MIN_TICK_RATE as i32) as u32
+    }
+
+    // 誰かがSlow-mo中ならrateを半分にする
+    fn slowed(&self, rate: u32) -> u32 {
+        if self
+            .snakes
+            .iter()
+            .any(|snake| snake.has_effect(PowerUpKind::SlowMo))
+        {
+            (rate / 2).max(1)
+        } else {
+            rate
+        }
+    }
+
+    // 制限時間などを1tickでどれだけ減らすかに使うupdateの回数
+    // +/-キーの速さはリプレイに残らないので含めない(Slow-moは盤面で決まるので含める)
+    fn game_tick_rate(&self) -> u32 {
+        self.slowed(self.tick_rate)
+    }
+
+    // 実際のupdateの回数(Shiftのスピードは蛇ごとにmove_snakesで上げるので含めない)
+    fn effective_tick_rate(&self) -> u32 {
+        self.slowed(self.base_tick_rate())
+    }
+
+    // 蛇と障害物で埋まっているマスの数
+    fn occupied_cells(&self) -> usize {
+        self.snakes
+            .iter()
+            .filter(|snake| !snake.dead)
+            .map(|snake| snake.len())
+            .sum::<usize>()
+            + self.obstacles.len()
+    }
+
+    // foodの経過時間を進めて時間切れのものを消し、たまにボーナスのfoodを置く
+    fn update_bonus_food(&mut self) {
+        for food in &mut self.foods {
+            food.age += 1;
+        }
+        self.foods.retain(|food| !food.expired());
+        let has_bonus = self.foods.iter().any(|food| food.lifetime.is_some());
+        if !has_bonus && self.rng.rand_range(0..BONUS_CHANCE) == 0 {
+            if let Some(pos) = self.free_cell(&[]) {
+                self.foods
+                    .push(Food::bonus(pos, BONUS_SECONDS * self.tick_rate));
+            }
+        }
+    }
+
+    // 動き回るfoodを動かす(蛇と障害物、他のfood、パワーアップには重ならない)
+    fn update_wandering_food(&mut self) {
+        self.refresh_cells();
+        let heads: Vec<GridPosition> = self.snakes.iter().map(|s| s.head.pos).collect();
+        for food in &mut self.foods {
+            let from = food.pos;
+            food.update(&mut self.rng, &self.cells, &heads);
+            self.cells.set(from, Cell::Empty);
+            self.cells.set(food.pos, Cell::Food(food.kind));
+        }
+    }
+
+    // ゲームオーバーにしてハイスコアを記録
+    fn game_over(&mut self) {
+        self.end_game(GameOutcome::Died);
+    }
+
+    // 残機を1つ減らし、スコアはそのままでスタート位置から最初の長さでやり直す
+    // 残機がなくなったらgameover
+    fn lose_life(&mut self) {
+        self.lives = self.lives.saturating_sub(1);
+        if self.lives == 0 {
+            self.game_over();
+        } else {
+            // サバイバルでは盤面を元の大きさに戻す(スタート位置が通れなくなっていることがある)
+            if self.next_shrink.is_some() {
+                self.board.margin = 0;
+                self.next_shrink = self.mode.rules().shrink_interval;
+            }
+            let (pos, dir) = self.start_of(0);
+            self.snakes[0].respawn(pos, dir, self.board);
+        }
+    }
+
+    // ゲームを終了し、対戦中でなければハイスコアを記録
+    fn end_game(&mut self, outcome: GameOutcome) {
+        self.scene = Scene::GameOver;
+        self.outcome = Some(outcome);
+        self.events.push(GameEvent::LevelCompleted { outcome });
+        if self.demo || self.playback.is_some() || self.headless {
+            return;
+        }
+        if let Err(e) = self.replay().save(&self.replay_path()) {
+            eprintln!("Could not save replay: {}", e);
+        }
+        if !self.solo() {
+            return;
+        }
+        self.save_ghost();
+        if self.settings.record_gif {
+            let dir = self.high_scores.path.with_file_name(GIFS_DIR);
+            self.recorder.request_export(dir);
+        }
+        // スクリプトでルールを変えたゲームはランキングにもハイスコアにも記録しない
+        if !self.active_scripts.is_empty() {
+            return;
+        }
+        // ハイスコアはモードごとの表に記録する
+        // 表に入ったらひとまず前回の名前で保存し、名前を入力してもらう(ランキングには入力した名前で送る)
+        let name = self.default_name();
+        self.high_score_time = now_timestamp();
+        self.high_score_rank = self.high_scores.insert(
+            self.mode,
+            self.snakes[0].score,
+            self.high_score_time,
+            self.difficulty.name,
+            &name,
+        );
+        if self.high_score_rank.is_some() {
+            let mut entry = TextInput::new(NAME_LENGTH);
+            for c in name.chars() {
+                entry.insert(c);
+            }
+            self.name_entry = Some(entry);
+            if let Err(e) = self.high_scores.save() {
+                eprintln!("Could not save high scores: {}", e);
+            }
+        } else {
+            self.submit_score(name);
+        }
+    }
+
+    // ランキングにスコアを送信する(送るのはClassicのスコアだけ)
+    fn submit_score(&mut self, name: String) {
+        let Some(url) = config()
+            .leaderboard_url
+            .as_ref()
+            .filter(|_| self.mode.rules().leaderboard)
+        else {
+            return;
+        };
+        self.leaderboard.submit(
+            url,
+            ScoreSubmission {
+                name,
+                score: self.snakes[0].score,
+                seed: self.seed,
+                difficulty: self.difficulty.name.to_string(),
+            },
+        );
+    }
+
+    // ハイスコアの名前の最初の値(前回入力した名前、なければconfig.tomlのプレイヤー名)
+    fn default_name(&self) -> String {
+        if self.settings.high_score_name.is_empty() {
+            config().player_name.clone()
+        } else {
+            self.settings.high_score_name.clone()
+        }
+    }
+
+    // 入力した名前をハイスコア表に記録して保存し、次の時のために覚えておく
+    // 空ならプレイヤー名のまま。その名前でランキングに送信する
+    fn confirm_name(&mut self, ctx: &Context) {
+        let (Some(entry), Some(rank)) = (self.name_entry.take(), self.high_score_rank) else {
+            return;
+        };
+        let name = entry.text().trim();
+        if !name.is_empty() {
+            self.high_scores
+                .set_name(self.mode, self.high_score_time, rank, name);
+            if let Err(e) = self.high_scores.save() {
+                eprintln!("Could not save high scores: {}", e);
+            }
+            self.settings.high_score_name = name.to_string();
+            self.save_settings(ctx);
+        }
+        self.submit_score(self.default_name());
+    }
+
+    // Discordに表示するアクティビティ(「Classic - Score: 10」「Length: 5」と遊び始めた時刻)
+    fn activity(&self) -> Activity {
+        let playing = matches!(self.scene, Scene::Playing | Scene::Paused | Scene::GameOver);
+        if !playing || self.demo {
+            return Activity {
+                details: "In the menu".to_string(),
+                state: self.mode_text(),
+                start: None,
+            };
+        }
+        let state = match self.scene {
+            Scene::Paused => "Paused".to_string(),
+            Scene::GameOver => "Game over".to_string(),
+            _ => format!("Length: {}", self.snakes[0].len()),
+        };
+        Activity {
+            details: format!("{} - {}", self.mode_text(), self.score_text()),
+            state,
+            // 経過時間が止まって見えるように、プレイ中だけ遊び始めた時刻を送る
+            start: (self.scene == Scene::Playing)
+                .then(|| now_timestamp().saturating_sub(self.play_time as u64)),
+        }
+    }
+
+    // スコアの表示(1人だけなら「Score: 10」、対戦中なら「P1: 10  CPU: 5」)
+    fn score_text(&self) -> String {
+        // バトルでは人の蛇のスコアだけ
+        if self.solo() || self.mode.rules().battle {
+            format!("Score: {}", self.snakes[0].score)
+        } else {
+            self.snakes
+                .iter()
+                .enumerate()
+                .map(|(i, snake)| format!("{}: {}", self.label(i), snake.score))
+                .collect::<Vec<_>>()
+                .join("  ")
+        }
+    }
+
+    // メニューのモードの表示(Dailyなら今日の日付も「Daily 2026-10-15」)
+    fn mode_text(&self) -> String {
+        if self.mode.rules().daily {
+            let today = format_timestamp(now_timestamp());
+            format!("{} {}", self.mode.name(), &today[..10])
+        } else {
+            self.mode.name().to_string()
+        }
+    }
+
+    // サバイバルで次に縮むまでの表示(「Shrink: 12s」)
+    fn shrink_text(&self) -> String {
+        match self.next_shrink {
+            Some(next_shrink) => format!("  Shrink: {}s", next_shrink.ceil() as u32),
+            None => String::new(),
+        }
+    }
+
+    // タイムアタックの残り時間の表示(「Time: 1:05」)
+    fn time_text(&self) -> String {
+        match self.time_left {
+            Some(time_left) => {
+                let seconds = time_left.ceil() as u32;
+                format!("  Time: {}:{:02}", seconds / 60, seconds % 60)
+            }
+            None => String::new(),
+        }
+    }
+
+    // キャンペーンの進み具合の表示(「Level 2 (3/10)」)
+    fn campaign_text(&self) -> String {
+        if self.campaign {
+            format!(
+                "  Level {} ({}/{})",
+                self.stage + 1,
+                self.level_food,
+                CAMPAIGN_FOOD_COUNT
+            )
+        } else {
+            String::new()
+        }
+    }
+
+    // コンボの倍率の表示(2つ以上続いている時だけ。「Combo: x3」)
+    fn combo_text(snake: &Snake) -> String {
+        if snake.combo >= 2 {
+            format!("  Combo: x{}", snake.combo.min(MAX_COMBO_MULTIPLIER))
+        } else {
+            String::new()
+        }
+    }
+
+    // 最大コンボの表示(1人だけなら「Max combo: 4」、対戦中なら「Max combo  P1: 4  CPU: 2」)
+    fn max_combo_text(&self) -> String {
+        if self.solo() {
+            format!("Max combo: {}", self.snakes[0].max_combo)
+        } else {
+            let combos: Vec<String> = self
+                .snakes
+                .iter()
+                .enumerate()
+                .map(|(i, snake)| format!("{}: {}", self.label(i), snake.max_combo))
+                .collect();
+            format!("Max combo  {}", combos.join("  "))
+        }
+    }
+
+    // 障害物, snake, food, HUDを描画
+    // 前のtickから次のtickまでの経過の割合(0.0〜1.0)
+    // プレイ中以外と、tickが時間通りに進まないコマ送りやネットワーク対戦では補間しない
+    fn render_alpha(&self, ctx: &Context) -> f32 {
+        if self.scene != Scene::Playing
+            || self.counting_down()
+            || self.tutorial_waiting()
+            || self.frame_step
+            || self.confirm_quit
+            || self.net.is_some()
+        {
+            return 1.0;
+        }
+        let remaining = ctx.time.remaining_update_time().as_secs_f32();
+        (remaining * self.effective_tick_rate() as f32).min(1.0)
+    }
+
+    // 描画する蛇と番号(消えていく途中の蛇は残っている部分だけ)
+    fn shown_snakes(&self) -> Vec<(usize, Cow<'_, Snake>)> {
+        self.snakes
+            .iter()
+            .enumerate()
+            .filter(|(i, snake)| {
+                !snake.dead || self.dying.as_ref().is_some_and(|d| d.snakes.contains(i))
+            })
+            .filter_map(|(i, snake)| match &self.dying {
+                Some(dying) => dying.shown(i, snake).map(|s| (i, Cow::Owned(s))),
+                None => Some((i, Cow::Borrowed(snake))),
+            })
+            .collect()
+    }
+
+    // i番目の蛇のbodyの塗り方(設定のスキンは1人目だけ。解除していなければSolid)
+    fn skin(&self, i: usize) -> Skin {
+        let skin = self.settings.skin;
+        let unlocked = skin
+            .unlocked_by()
+            .is_none_or(|a| self.achievements.is_unlocked(a));
+        if i == 0 && unlocked {
+            skin
+        } else {
+            Skin::Solid
+        }
+    }
+
+    // 解除したスキンのうち今のスキンの次のもの
+    fn next_skin(&self) -> Skin {
+        let current = Skin::ALL
+            .iter()
+            .position(|s| *s == self.settings.skin)
+            .unwrap_or(0);
+        (1..=Skin::ALL.len())
+            .map(|step| Skin::ALL[(current + step) % Skin::ALL.len()])
+            .find(|skin| {
+                skin.unlocked_by()
+                    .is_none_or(|a| self.achievements.is_unlocked(a))
+            })
+            .unwrap_or_default()
+    }
+
+    // 盤面の背景の画像と模様を描画(設定の模様か盤面の大きさが変わった時だけMeshを作り直す)
+    fn draw_background(&mut self, ctx: &Context, canvas: &mut graphics::Canvas) -> GameResult {
+        if let Some(image) = &self.background_image {
+            image.draw(canvas, self.board);
+        }
+        let key = (
+            self.settings.background,
+            self.board.width,
+            self.board.height,
+        );
+        if self.background.as_ref().map(|(k, _)| *k) != Some(key) {
+            self.background = background_mesh(ctx, key.0, self.board)?.map(|mesh| (key, mesh));
+        }
+        if let Some((_, mesh)) = &self.background {
+            canvas.draw(mesh, graphics::DrawParam::new());
+        }
+        Ok(())
+    }
+
+    // カメラが追う位置(1人目の蛇のheadのマスの中心、盤面の座標)
+    fn camera_focus(&self, alpha: f32) -> (f32, f32) {
+        self.snakes.first().map_or((0.0, 0.0), |snake| {
+            let head = snake.head.rect(alpha);
+            (head.x + head.w / 2.0, head.y + head.h / 2.0)
+        })
+    }
+
+    // 盤面のマスの四角形をbatchに溜めて1回で描画し、その上にテクスチャとHUDを描画
+    // alphaは蛇を前のマスとの間に描く割合(render_alpha)
+    // 蛇はconfigの描き方で描く(ハイビジビリティモードでは縁取りした四角)
+    // 自分にぶつかった直後は最後に揺れを戻して赤いフラッシュを重ねる
+    // マスが小さすぎて見づらい時はHUDの下に盤面全体のミニマップを重ねる
+    fn draw_board(
+        &self,
+        ctx: &Context,
+        canvas: &mut graphics::Canvas,
+        batch: &mut CellBatch,
+        minimap: &mut Minimap,
+        alpha: f32,
+    ) -> GameResult {
+        let outlined = self.settings.high_visibility;
+        let theme = self.settings.active_theme();
+        // ハイビジビリティモードと形で見分けるテーマでは四角で描く
+        let style = if outlined || theme.shapes {
+            SnakeStyle::Classic
+        } else {
+            config().snake_style
+        };
+        let sprites = self
+            .sprites
+            .as_ref()
+            .filter(|_| style == SnakeStyle::Sprites);
+        let snakes = self.shown_snakes();
+        batch.clear();
+        for obstacle in &self.obstacles {
+            obstacle.draw(batch, &theme, outlined);
+        }
+        for (i, portal) in self.portals.iter().enumerate() {
+            portal.draw(batch, i, outlined);
+        }
+        // サバイバルで通れなくなったマスと、もうすぐ通れなくなるマス
+        if let Some(next_shrink) = self.next_shrink {
+            if next_shrink <= SURVIVAL_WARNING_SECONDS {
+                draw_dead_zone(
+                    batch,
+                    self.board,
+                    self.board.margin + 1,
+                    DEAD_ZONE_WARNING_COLOR,
+                );
+            }
+            draw_dead_zone(batch, self.board, self.board.margin, DEAD_ZONE_COLOR);
+        }
+        if let Some(power_up) = &self.power_up {
+            power_up.draw(batch, outlined);
+        }
+        // 一番いいゲームの蛇は本物の蛇の下に描く
+        if let Some(ghost) = self.ghost.as_ref().filter(|ghost| !ghost.is_over()) {
+            ghost.snake().draw_ghost(batch, theme.snake_head, alpha);
+        }
+        if style != SnakeStyle::Smooth && sprites.is_none() {
+            for (i, snake) in &snakes {
+                snake.draw(batch, &theme, self.skin(*i), outlined, alpha);
+            }
+        }
+        if !theme.shapes {
+            for food in &self.foods {
+                food.draw(batch, &theme, outlined, sprites.is_some());
+            }
+        }
+        self.particles.draw(batch);
+        // ボーナスのfoodの残り時間
+        for food in &self.foods {
+            food.draw_timer(batch);
+        }
+        batch.draw(canvas);
+        // 空のMeshは作れないので、追加するものがある時だけ作る
+        let smooth = style == SnakeStyle::Smooth && !snakes.is_empty();
+        let shapes = theme.shapes && !self.foods.is_empty();
+        let hint = self.hint_step();
+        if smooth || shapes || hint.is_some() {
+            let mut builder = graphics::MeshBuilder::new();
+            if smooth {
+                for (i, snake) in &snakes {
+                    snake.draw_mesh(&mut builder, &theme, self.skin(*i), alpha)?;
+                }
+            }
+            if shapes {
+                for food in &self.foods {
+                    food.draw_shape(&mut builder, &theme)?;
+                }
+            }
+            if let Some(step) = hint {
+                draw_hint_arrow(&mut builder, step.pos, step.dir, theme.hud)?;
+            }
+            let mesh = graphics::Mesh::from_data(ctx, builder.build());
+            canvas.draw(&mesh, graphics::DrawParam::new());
+        }
+        if let Some(sprites) = sprites {
+            // ドット絵のテクスチャをぼかさずに拡大する
+            canvas.set_sampler(graphics::Sampler::nearest_clamp());
+            for (i, snake) in &snakes {
+                snake.draw_sprites(canvas, sprites, &theme, self.skin(*i), alpha, self.board);
+            }
+            for food in &self.foods {
+                food.draw_sprite(canvas, sprites);
+            }
+            canvas.set_default_sampler();
+        }
+        // ミニマップとHUDはカメラの位置によらず画面の同じ場所に描く
+        canvas.set_screen_coordinates(screen_coordinates(self.window_size));
+        if Minimap::is_needed(self.window_size, self.board) {
+            let screen = config().screen_size();
+            let view = camera_coordinates(
+                graphics::Rect::new(0.0, 0.0, screen.0, screen.1),
+                self.camera_focus(alpha),
+                self.board,
+            );
+            minimap.draw(canvas, &theme, view, &self.view(0));
+        }
+        if self.solo() {
+            let info = format!(
+                "{}  Length: {}  Difficulty: {}  Speed: {}{}{}{}{}",
+                self.score_text(),
+                self.snakes[0].len(),
+                self.difficulty.name,
+                self.base_tick_rate(),
+                self.time_text(),
+                self.shrink_text(),
+                self.campaign_text(),
+                GameState::combo_text(&self.snakes[0])
+            );
+            draw_hud(canvas, &theme, &info, self.lives);
+        } else {
+            let combos: String = self
+                .snakes
+                .iter()
+                .enumerate()
+                .filter(|(_, snake)| snake.combo >= 2)
+                .map(|(i, snake)| format!("  {}{}", self.label(i), GameState::combo_text(snake)))
+                .collect();
+            let info = format!(
+                "{}  Difficulty: {}  Speed: {}{}{}{}{}",
+                self.score_text(),
+                self.difficulty.name,
+                self.base_tick_rate(),
+                self.time_text(),
+                self.shrink_text(),
+                self.battle_text(),
+                combos
+            );
+            draw_hud(canvas, &theme, &info, 0);
+        }
+        // 1人目の蛇の空腹とスタミナ(スタミナはShiftで使った後だけ表示する)
+        let mut meters: Vec<f32> = self.snakes[0].fullness().into_iter().collect();
+        if self.snakes[0].stamina() < 1.0 {
+            meters.push(self.snakes[0].stamina());
+        }
+        for (row, fullness) in meters.into_iter().enumerate() {
+            draw_meter(canvas, &theme, row, fullness);
+        }
+        // 対戦中なら効果の前に表示名を付ける
+        let effects: Vec<(String, ActiveEffect)> = self
+            .snakes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, snake)| {
+                let label = if self.solo() {
+                    String::new()
+                } else {
+                    format!("{} ", self.label(i))
+                };
+                snake
+                    .effects
+                    .iter()
+                    .map(move |effect| (label.clone(), *effect))
+            })
+            .collect();
+        draw_effects(canvas, &effects, self.effective_tick_rate());
+        // 1人目の蛇が混乱している間は画面を混乱のfoodの色で染める
+        if self.snakes[0].confusion > 0 {
+            draw_tint(canvas, theme.confusion, self.snakes[0].confusion, self.tick);
+        }
+        self.death_effect
+            .end_shake(canvas, screen_coordinates(self.window_size));
+        Ok(())
+    }
+
+    // タイトル画面のキー入力
+    fn key_down_main_menu(&mut self, key: KeyCode) {
+        match key {
+            // 1人で初めて遊ぶ時はチュートリアルから始める
+            KeyCode::Return
+                if !self.settings.tutorial_done && self.players == 1 && !self.opponent =>
+            {
+                self.start_tutorial()
+            }
+            KeyCode::Return => {
+                // Dailyはメニューで変えた難易度やステージを今日の設定に戻してから始める
+                if self.mode.rules().daily {
+                    self.reset();
+                }
+                self.play();
+            }
+            // Uでチュートリアルをもう一度遊ぶ
+            KeyCode::U => self.start_tutorial(),
+            // 左右で難易度を選ぶ
+            KeyCode::Left => self.set_difficulty(self.difficulty.cycle(-1)),
+            KeyCode::Right => self.set_difficulty(self.difficulty.cycle(1)),
+            // 上下でステージを選ぶ
+            KeyCode::Up => self.set_level(self.level + self.levels.len() - 1),
+            KeyCode::Down => self.set_level(self.level + 1),
+            // 1, 2で人数を選ぶ
+            KeyCode::Key1 => self.set_players(1),
+            KeyCode::Key2 => self.set_players(2),
+            // Cでコンピュータの対戦相手を切り替える(1人プレイのみ)
+            KeyCode::C => {
+                self.opponent = !self.opponent;
+                self.reset();
+            }
+            // Aでキャンペーン(foodを食べると次のステージに進む)を切り替える
+            KeyCode::A => self.campaign = !self.campaign,
+            // Jで目標への挑戦を切り替える
+            KeyCode::J => {
+                self.use_challenges = !self.use_challenges;
+                self.reset();
+            }
+            // Xでモードを切り替える
+            KeyCode::X => self.set_mode(self.mode.next()),
+            // Kで操作設定の画面を開く
+            KeyCode::K => {
+                self.controls_selected = 0;
+                self.scene = Scene::Controls;
+            }
+            // Nでネットワーク対戦の相手を待ち受ける
+            KeyCode::N => self.start_network(NetSession::host(DEFAULT_NET_PORT)),
+            // Sで通算成績の画面を開く
+            KeyCode::S => self.scene = Scene::Stats,
+            // Fで友達のチャレンジコードを入力する
+            KeyCode::F => {
+                self.code_input.clear();
+                self.code_error = None;
+                self.scene = Scene::EnterCode;
+            }
+            // Lで中断したゲームの続きから始める
+            KeyCode::L => self.continue_game(),
+            // Vで最後に遊んだゲームのリプレイを再生する
+            KeyCode::V => match Replay::load(&self.replay_path()) {
+                Ok(replay) => self.start_playback(replay),
+                Err(e) => eprintln!("Could not load replay: {}", e),
+            },
+            // Eで選択中のステージをエディタで開く
+            KeyCode::E => {
+                self.editor = Some(Editor::new(self.levels[self.level].clone()));
+                self.scene = Scene::Editor;
+            }
+            _ => (),
+        }
+    }
+
+    // 現在のシーンのキー入力
+    fn key_down_scene(&mut self, ctx: &Context, key: KeyCode) -> GameResult {
+        match self.scene {
+            Scene::MainMenu => self.key_down_main_menu(key),
+            Scene::Playing => self.key_down_playing(key),
+            Scene::Paused => self.key_down_paused(key),
+            Scene::GameOver => self.key_down_game_over(ctx, key)?,
+            Scene::Editor => self.key_down_editor(ctx, key)?,
+            Scene::Controls => self.key_down_controls(ctx, key)?,
+            Scene::EnterCode => self.key_down_enter_code(key),
+            Scene::Stats => {
+                // Backspaceでタイトル画面に戻る
+                if key == KeyCode::Back {
+                    self.scene = Scene::MainMenu;
+                }
+            }
+            Scene::Lobby => {
+                // Backspaceで接続をやめてタイトル画面に戻る
+                if key == KeyCode::Back {
+                    self.stop_network();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 操作設定の画面のキー入力
+    fn key_down_controls(&mut self, ctx: &Context, key: KeyCode) -> GameResult {
+        // キーを待っている間は押されたキーを割り当てて保存
+        if self.waiting_for_key {
+            let action = Action::ALL[self.controls_selected];
+            self.settings.bindings.bind(action, key);
+            self.waiting_for_key = false;
+            self.save_settings(ctx);
+            return Ok(());
+        }
+        match key {
+            // 上下で操作を選ぶ
+            KeyCode::Up => {
+                self.controls_selected =
+                    (self.controls_selected + Action::ALL.len() - 1) % Action::ALL.len()
+            }
+            KeyCode::Down => {
+                self.controls_selected = (self.controls_selected + 1) % Action::ALL.len()
+            }
+            // Enterで次に押したキーを割り当てる
+            KeyCode::Return => self.waiting_for_key = true,
+            // Deleteで全てデフォルトに戻す
+            KeyCode::Delete => {
+                self.settings.bindings = InputBindings::default();
+                self.save_settings(ctx);
+            }
+            // Tabでマウスで操作するかを切り替えて保存
+            KeyCode::Tab => {
+                self.settings.mouse_steering = !self.settings.mouse_steering;
+                self.save_settings(ctx);
+            }
+            // Iでヒントの矢印を出すかを切り替えて保存
+            KeyCode::I => {
+                self.settings.hint_arrow = !self.settings.hint_arrow;
+                self.save_settings(ctx);
+            }
+            // Rでゲームの最後をGIFに書き出すかを切り替えて保存
+            KeyCode::R => {
+                self.settings.record_gif = !self.settings.record_gif;
+                self.save_settings(ctx);
+            }
+            // Backspaceでタイトル画面に戻る
+            KeyCode::Back => self.scene = Scene::MainMenu,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // 操作設定の画面の表示
+    fn controls_lines(&self) -> Vec<String> {
+        let mut lines = vec!["Controls".to_string()];
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let keys = if i == self.controls_selected && self.waiting_for_key {
+                "press a key...".to_string()
+            } else {
+                self.settings.bindings.keys_text(*action)
+            };
+            let cursor = if i == self.controls_selected {
+                ">"
+            } else {
+                " "
+            };
+            lines.push(format!("{} {}: {}", cursor, action.name(), keys));
+        }
+        lines.push(format!(
+            "Mouse steering: {} (Tab)",
+            if self.settings.mouse_steering {
+                "On"
+            } else {
+                "Off"
+            }
+        ));
+        lines.push(format!(
+            "Hint arrow: {} (I)",
+            if self.settings.hint_arrow {
+                "On"
+            } else {
+                "Off"
+            }
+        ));
+        lines.push(format!(
+            "Record GIF: {} (R)",
+            if self.settings.record_gif {
+                "On"
+            } else {
+                "Off"
+            }
+        ));
+        lines.push("Enter: rebind  Delete: reset  Backspace: back".to_string());
+        lines
+    }
+
+    // エディタのキー入力
+    fn key_down_editor(&mut self, ctx: &Context, key: KeyCode) -> GameResult {
+        let Some(editor) = &mut self.editor else {
+            return Ok(());
+        };
+        match key {
+            // 数字キーでブラシを選ぶ
+            KeyCode::Key1 | KeyCode::Key2 | KeyCode::Key3 | KeyCode::Key4 => {
+                let index = match key {
+                    KeyCode::Key1 => 0,
+                    KeyCode::Key2 => 1,
+                    KeyCode::Key3 => 2,
+                    _ => 3,
+                };
+                editor.brush = Brush::ALL[index];
+            }
+            // Sで保存し、ステージの一覧にも反映する
+            KeyCode::S => {
+                editor.level.name = CUSTOM_LEVEL_NAME.to_string();
+                let level = editor.level.clone();
+                editor.message = match Self::save_level(ctx, &level) {
+                    Ok(()) => format!("Saved {}.txt", CUSTOM_LEVEL_NAME),
+                    Err(e) => format!("Could not save: {}", e),
+                };
+                match self.levels.iter().position(|l| l.name == level.name) {
+                    Some(i) => {
+                        self.levels[i] = level;
+                        self.level = i;
+                    }
+                    None => {
+                        self.levels.push(level);
+                        self.level = self.levels.len() - 1;
+                    }
+                }
+            }
+            // Enterでメニューに戻る
+            KeyCode::Return => {
+                self.editor = None;
+                self.set_level(self.level);
+                self.scene = Scene::MainMenu;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // ステージをユーザー設定ディレクトリの/levelsに書き込む
+    fn save_level(ctx: &Context, level: &Level) -> GameResult {
+        ctx.fs.create_dir(LEVELS_DIR)?;
+        let mut file = ctx
+            .fs
+            .create(format!("{}/{}.txt", LEVELS_DIR, level.name))?;
+        file.write_all(level.to_text().as_bytes())?;
+        Ok(())
+    }
+
+    // 最後に描いたフレームをユーザー設定ディレクトリの/screenshotsに撮った日時の名前で書き込む
+    fn save_screenshot(ctx: &Context) -> GameResult {
+        let frame = ctx.gfx.frame();
+        let mut pixels = frame.to_pixels(ctx)?;
+        // ウィンドウの画像はBGRAのことが多いので、PNGに書けるRGBAに並べ替える
+        if matches!(
+            frame.format(),
+            graphics::ImageFormat::Bgra8Unorm | graphics::ImageFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        let image = graphics::Image::from_pixels(
+            ctx,
+            &pixels,
+            graphics::ImageFormat::Rgba8UnormSrgb,
+            frame.width(),
+            frame.height(),
+        );
+        ctx.fs.create_dir(SCREENSHOTS_DIR)?;
+        image.encode(
+            ctx,
+            graphics::ImageEncodingFormat::Png,
+            format!(
+                "{}/screenshot-{}.png",
+                SCREENSHOTS_DIR,
+                file_timestamp(now_timestamp())
+            ),
+        )
+    }
+
+    // 人が操作する蛇を全て自動操作にしてデモを始める
+    pub fn start_demo(&mut self) {
+        self.restart();
+        for snake in &mut self.snakes {
+            if snake.controller == Controller::Human {
+                snake.controller = Controller::Autopilot;
+            }
+        }
+        self.reset_controllers();
+        self.demo = true;
+    }
+
+    // 今のゲームのリプレイ
+    fn replay(&self) -> Replay {
+        Replay {
+            seed: self.seed as i64,
+            difficulty: self.difficulty.name.to_string(),
+            level: self.levels[self.start_level()].name.clone(),
+            players: self.players,
+            opponent: self.opponent,
+            campaign: self.campaign,
+            mode: self.mode,
+            food_count: self.settings.food_count,
+            lives: self.settings.lives,
+            max_tick_rate: self.settings.max_tick_rate,
+            grid_size: config().grid_size,
+            score: self.snakes[0].score,
+            inputs: self.recording.clone(),
+            scripts: self
+                .active_scripts
+                .iter()
+                .map(|script| script.source.clone())
+                .collect(),
+        }
+    }
+
+    // リプレイファイルの場所(ハイスコアと同じディレクトリ)
+    fn replay_path(&self) -> PathBuf {
+        self.high_scores.path.with_file_name(REPLAY_FILE)
+    }
+
+    // 保存・受信したゲームの設定から、グリッドの大きさを確かめてステージと難易度を探す
+    fn find_setup(
+        &self,
+        grid_size: (i16, i16),
+        level: &str,
+        difficulty: &str,
+    ) -> Result<(usize, Difficulty), String> {
+        if grid_size != config().grid_size {
+            return Err(format!("played on a {}x{} grid", grid_size.0, grid_size.1));
+        }
+        let level = self
+            .levels
+            .iter()
+            .position(|l| l.name == level)
+            .ok_or_else(|| format!("level not found: {}", level))?;
+        let difficulty = Difficulty::ALL
+            .into_iter()
+            .find(|d| d.name == difficulty)
+            .ok_or_else(|| format!("unknown difficulty: {}", difficulty))?;
+        Ok((level, difficulty))
+    }
+
+    // リプレイと同じ設定とシードでゲームを始め、記録された入力で蛇を動かす
+    pub fn start_playback(&mut self, replay: Replay) {
+        let (level, difficulty) =
+            match self.find_setup(replay.grid_size, &replay.level, &replay.difficulty) {
+                Ok(setup) => setup,
+                Err(e) => {
+                    eprintln!("Could not play replay: {}", e);
+                    return;
+                }
+            };
+        let scripts = match Script::parse_all(&replay.scripts) {
+            Ok(scripts) => scripts,
+            Err(e) => {
+                eprintln!("Could not play replay: {}", e);
+                return;
+            }
+        };
+        let settings = self.settings.clone();
+        self.settings.seed = Some(replay.seed as u64);
+        self.settings.food_count = replay.food_count;
+        self.settings.lives = replay.lives;
+        self.settings.max_tick_rate = replay.max_tick_rate;
+        self.level = level;
+        self.players = replay.players;
+        self.opponent = replay.opponent;
+        self.campaign = replay.campaign;
+        self.mode = replay.mode;
+        self.set_difficulty(difficulty);
+        self.restart();
+        self.active_scripts = scripts;
+        // 人が操作する蛇はリプレイの入力で動かす
+        for (i, snake) in self.snakes.iter().enumerate() {
+            if snake.controller == Controller::Human {
+                self.controllers[i] = Box::new(ReplayController::new(&replay, i));
+            }
+        }
+        self.playback = Some(Playback { replay, settings });
+    }
+
+    // セーブデータの場所(ハイスコアと同じディレクトリ)
+    fn save_path(&self) -> PathBuf {
+        self.high_scores.path.with_file_name(SAVE_FILE)
+    }
+
+    // 続きから遊べるゲームの途中か(デモやリプレイは保存しない)
+    fn can_save(&self) -> bool {
+        matches!(self.scene, Scene::Playing | Scene::Paused)
+            && !self.demo
+            && self.playback.is_none()
+            && self.net.is_none()
+    }
+
+    // 今のゲームをセーブデータに書き込む
+    fn save_game(&self) -> GameResult {
+        let (state, inc) = self.rng.state();
+        SaveGame {
+            difficulty: self.difficulty.name.to_string(),
+            level: self.levels[self.level].name.clone(),
+            players: self.players,
+            opponent: self.opponent,
+            campaign: self.campaign,
+            stage: self.stage,
+            level_food: self.level_food,
+            mode: self.mode,
+            time_left: self.time_left,
+            next_shrink: self.next_shrink,
+            margin: self.board.margin,
+            standings: self.standings.clone(),
+            food_count: self.settings.food_count,
+            max_tick_rate: self.settings.max_tick_rate,
+            grid_size: config().grid_size,
+            seed: self.seed as i64,
+            rng_state: (state as i64, inc as i64),
+            foods_eaten: self.foods_eaten,
+            lives: self.lives,
+            tick_rate: self.tick_rate,
+            tick: self.tick,
+            longest_snake: self.longest_snake,
+            play_time: self.play_time,
+            turned_left: self.turned_left,
+            snakes: self.snakes.clone(),
+            foods: self.foods.clone(),
+            power_up: self.power_up,
+            recording: self.recording.clone(),
+        }
+        .save(&self.save_path())
+    }
+
+    // セーブデータから続きを始める(一時停止した状態で再開し、セーブデータは消す)
+    fn continue_game(&mut self) {
+        let path = self.save_path();
+        let save = match SaveGame::load(&path) {
+            Ok(save) => save,
+            Err(e) => {
+                eprintln!("Could not load saved game: {}", e);
+                return;
+            }
+        };
+        let (level, difficulty) =
+            match self.find_setup(save.grid_size, &save.level, &save.difficulty) {
+                Ok(setup) => setup,
+                Err(e) => {
+                    eprintln!("Could not continue saved game: {}", e);
+                    return;
+                }
+            };
+        self.settings.food_count = save.food_count;
+        self.settings.max_tick_rate = save.max_tick_rate;
+        self.level = level;
+        self.players = save.players;
+        self.opponent = save.opponent;
+        self.campaign = save.campaign;
+        self.mode = save.mode;
+        self.stage = 0;
+        self.set_difficulty(difficulty);
+        // Dailyでも保存した日のシードとステージで続ける
+        let user_seed = self.settings.seed;
+        self.settings.seed = Some(save.seed as u64);
+        self.reset();
+        self.settings.seed = user_seed;
+        self.stage = save.stage;
+        self.level_food = save.level_food;
+        self.time_left = save.time_left;
+        self.next_shrink = save.next_shrink;
+        self.board.margin = save.margin;
+        self.standings = save.standings;
+        self.seed = save.seed as u64;
+        self.rng = Rand32::from_state((save.rng_state.0 as u64, save.rng_state.1 as u64));
+        self.foods_eaten = save.foods_eaten;
+        self.lives = save.lives;
+        self.tick_rate = save.tick_rate;
+        self.tick = save.tick;
+        self.longest_snake = save.longest_snake;
+        self.play_time = save.play_time;
+        self.turned_left = save.turned_left;
+        self.snakes = save.snakes;
+        self.reset_controllers();
+        self.foods = save.foods;
+        self.power_up = save.power_up;
+        self.recording = save.recording;
+        self.scene = Scene::Paused;
+        if let Err(e) = std::fs::remove_file(&path) {
+            eprintln!("Could not remove saved game: {}", e);
+        }
+    }
+
+    // ネットワーク対戦の接続待ちの画面を開く
+    pub fn start_network(&mut self, net: Result<NetSession, String>) {
+        match net {
+            Ok(net) => {
+                self.net = Some(net);
+                self.scene = Scene::Lobby;
+            }
+            Err(e) => eprintln!("Could not start network play: {}", e),
+        }
+    }
+
+    // ネットワーク対戦を終えてタイトル画面に戻る
+    fn stop_network(&mut self) {
+        self.net = None;
+        self.reset();
+    }
+
+    // 接続待ちと、相手から届いたメッセージを処理する
+    fn update_network(&mut self) {
+        let Some(net) = &mut self.net else {
+            return;
+        };
+        // ホストは対戦の相手が決まったら対戦を始める(その後も観戦者は受け付ける)
+        if let Some(stream) = net.accept_peers() {
+            self.on_connected(stream);
+        }
+        let Some(net) = &mut self.net else {
+            return;
+        };
+        let events: Vec<NetEvent> = match &net.events {
+            Some(events) => events.try_iter().collect(),
+            None => Vec::new(),
+        };
+        for event in events {
+            match event {
+                NetEvent::Connected(stream) => self.on_connected(stream),
+                NetEvent::Message(NetMessage::Start {
+                    seed,
+                    difficulty,
+                    level,
+                    food_count,
+                    max_tick_rate,
+                    grid_size,
+                    scripts,
+                }) => match self
+                    .find_setup(grid_size, &level, &difficulty)
+                    .and_then(|setup| Ok((setup, Script::parse_all(&scripts)?)))
+                {
+                    Ok(((level, difficulty), scripts)) => {
+                        self.settings.food_count = food_count;
+                        self.settings.max_tick_rate = max_tick_rate;
+                        self.start_network_game(seed, level, difficulty, scripts);
+                    }
+                    Err(e) => self.disconnect(format!("Could not start: {}", e)),
+                },
+                NetEvent::Message(NetMessage::Input { tick, dirs }) => {
+                    if let Some(net) = &mut self.net {
+                        net.remote.borrow_mut().insert(tick, dirs.into());
+                    }
+                }
+                // 観戦者は両方の蛇の入力をホストから受け取る
+                NetEvent::Message(NetMessage::Tick { tick, inputs }) => {
+                    if let Some(net) = &mut self.net {
+                        let mut inputs = inputs.into_iter();
+                        for map in [&net.local, &net.remote] {
+                            let dirs = inputs.next().unwrap_or_default();
+                            map.borrow_mut().insert(tick, dirs.into());
+                        }
+                    }
+                }
+                NetEvent::Message(NetMessage::Join { .. }) => (),
+                NetEvent::Disconnected(e) => self.disconnect(e),
+            }
+        }
+    }
+
+    // 相手に接続できた。ホストは今の設定で対戦を始め、設定を相手と観戦者に送る
+    // 接続した側は対戦か観戦かを送ってホストが始めるのを待つ
+    fn on_connected(&mut self, stream: TcpStream) {
+        let Some(net) = &mut self.net else {
+            return;
+        };
+        net.stream = Some(stream);
+        if !net.host {
+            let join = NetMessage::Join {
+                spectator: net.spectator,
+            };
+            match net.send(&join) {
+                Ok(()) => net.status = "Connected. Waiting for the host to start...".to_string(),
+                Err(e) => self.disconnect(e),
+            }
+            return;
+        }
+        let seed = self.settings.seed.unwrap_or_else(random_seed);
+        let start = NetMessage::Start {
+            seed,
+            difficulty: self.difficulty.name.to_string(),
+            level: self.levels[self.level].name.clone(),
+            food_count: self.settings.food_count,
+            max_tick_rate: self.settings.max_tick_rate,
+            grid_size: config().grid_size,
+            scripts: self
+                .scripts
+                .iter()
+                .map(|script| script.source.clone())
+                .collect(),
+        };
+        net.broadcast(&start);
+        match net.send(&start) {
+            Ok(()) => {
+                net.start = Some(start);
+                self.start_network_game(seed, self.level, self.difficulty, self.scripts.clone());
+            }
+            Err(e) => self.disconnect(e),
+        }
+    }
+
+    // お互いに同じシードと設定、ホストのスクリプトで2人プレイを始める
+    fn start_network_game(
+        &mut self,
+        seed: u64,
+        level: usize,
+        difficulty: Difficulty,
+        scripts: Vec<Script>,
+    ) {
+        let user_seed = self.settings.seed;
+        self.settings.seed = Some(seed);
+        self.level = level;
+        self.players = 2;
+        self.opponent = false;
+        self.campaign = false;
+        self.use_challenges = false;
+        self.mode = GameMode::Classic;
+        self.set_difficulty(difficulty);
+        // resetで接続が消えないように退避する
+        let net = self.net.take();
+        self.restart();
+        // 自分の蛇も相手の蛇も、お互いに送った同じtickの入力で動かす
+        if let Some(net) = &net {
+            let me = net.local_player();
+            self.controllers[me] = Box::new(NetworkController::new(net.local.clone()));
+            self.controllers[1 - me] = Box::new(NetworkController::new(net.remote.clone()));
+        }
+        self.net = net;
+        self.active_scripts = scripts;
+        self.settings.seed = user_seed;
+    }
+
+    // 接続が切れたら理由を表示して接続待ちの画面に戻る
+    // (ゲームオーバーの後に相手が抜けた場合は結果を表示したままにする)
+    fn disconnect(&mut self, reason: String) {
+        if let Some(net) = &mut self.net {
+            net.status = format!("Disconnected: {}", reason);
+            net.listener = None;
+            net.events = None;
+            net.stream = None;
+            for peer in net.joining.drain(..).chain(net.spectators.drain(..)) {
+                peer.close();
+            }
+        }
+        if self.scene != Scene::GameOver {
+            self.scene = Scene::Lobby;
+        }
+    }
+
+    // ネットワーク対戦の1tick
+    // 自分の入力を送り、相手の同じtickの入力が届いていれば両方を反映して進める
+    // ホストは進めたtickの両方の入力を観戦者に送る
+    fn update_lockstep(&mut self) {
+        let tick = self.tick;
+        let Some(net) = &mut self.net else {
+            return;
+        };
+        if net.spectator {
+            self.update_spectating();
+            return;
+        }
+        if !net.local.borrow().contains_key(&tick) {
+            let dirs = std::mem::take(&mut net.pending);
+            let input = NetMessage::Input {
+                tick,
+                dirs: dirs.clone(),
+            };
+            if let Err(e) = net.send(&input) {
+                self.disconnect(e);
+                return;
+            }
+            net.local.borrow_mut().insert(tick, dirs.into());
+        }
+        // 相手の入力が届くまで待つ
+        if !self.controllers.iter().all(|c| c.ready(tick)) {
+            return;
+        }
+        if let Some(net) = self.net.as_mut().filter(|net| net.host) {
+            let inputs: Vec<Vec<Direction>> = [&net.local, &net.remote]
+                .iter()
+                .map(|map| {
+                    map.borrow()
+                        .get(&tick)
+                        .map(|dirs| dirs.iter().copied().collect())
+                        .unwrap_or_default()
+                })
+                .collect();
+            net.broadcast(&NetMessage::Tick {
+                tick,
+                inputs: inputs.clone(),
+            });
+            net.history.push(inputs);
+        }
+        self.update_playing();
+    }
+
+    // 観戦中の1tick。ホストから届いた入力で進める
+    // 途中から観戦を始めた時など遅れている間は、届いている分までまとめて進めて追いつく
+    fn update_spectating(&mut self) {
+        let ready = |state: &GameState, tick: u32| state.controllers.iter().all(|c| c.ready(tick));
+        while self.scene == Scene::Playing
+            && ready(self, self.tick)
+            && ready(self, self.tick + SPECTATOR_DELAY_TICKS)
+        {
+            self.update_playing();
+        }
+        if self.scene == Scene::Playing && ready(self, self.tick) {
+            self.update_playing();
+        }
+    }
+
+    // i番目の蛇の向きを変え、人が操作する蛇ならリプレイに記録して効果音を鳴らす
+    // 人の入力は混乱中なら逆の方向に曲がる(リプレイには押した方向を記録する)
+    fn apply_input(&mut self, i: usize, dir: Direction) {
+        let human = self.snakes[i].controller == Controller::Human;
+        let turn = if human {
+            self.snakes[i].input_dir(dir)
+        } else {
+            dir
+        };
+        if self.snakes[i].steer(turn) && human {
+            self.recording.push(ReplayInput {
+                tick: self.tick,
+                player: i,
+                dir,
+            });
+            self.events.push(GameEvent::SnakeTurned {
+                snake: i,
+                dir: turn,
+            });
+            self.sounds.push(Sound::Turn);
+        }
+    }
+
+    // デバッグ用の表示の行(fpsは実際の1秒間の描画回数)
+    fn debug_lines(&self, fps: f64) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "FPS: {:.0}  Ticks/s: {} (target {})",
+                fps,
+                self.debug.ticks_per_second(),
+                self.effective_tick_rate()
+            ),
+            format!("Tick: {}  Seed: {}", self.tick, self.seed),
+        ];
+        for (i, snake) in self.snakes.iter().enumerate() {
+            lines.push(format!(
+                "{}: length {}  head ({}, {})  dir {:?}  last {:?}  next {}",
+                self.label(i),
+                snake.len(),
+                snake.head.pos.x,
+                snake.head.pos.y,
+                snake.dir,
+                snake.last_update_dir(),
+                snake
+                    .next_dir()
+                    .map_or("-".to_string(), |dir| format!("{:?}", dir))
+            ));
+        }
+        lines
+    }
+
+    // 最後に描いたフレームでまとめて描画したマスの四角形の数(--benchで表示する)
+    pub fn batched_cells(&self) -> usize {
+        self.batch.as_ref().map_or(0, CellBatch::cell_count)
+    }
+
+    // 今のゲームを友達と同じ設定で遊ぶためのコード
+    // 自分で作ったステージや、コードに入らない大きさの設定ならNone
+    fn challenge_code(&self) -> Option<ChallengeCode> {
+        // 組み込みのステージはlevelsの先頭に並んでいる
+        let level = self.start_level();
+        if level >= Level::builtin().len() {
+            return None;
+        }
+        let grid = config().grid_size;
+        Some(ChallengeCode {
+            seed: self.seed,
+            mode: self.mode,
+            difficulty: Difficulty::ALL
+                .iter()
+                .position(|d| d.name == self.difficulty.name)
+                .unwrap_or(0) as u8,
+            level: level.try_into().ok()?,
+            campaign: self.campaign,
+            grid_size: (grid.0.try_into().ok()?, grid.1.try_into().ok()?),
+            food_count: self.settings.food_count.try_into().ok()?,
+            lives: self.settings.lives.try_into().ok()?,
+            max_tick_rate: self.settings.max_tick_rate.try_into().ok()?,
+        })
+    }
+
+    // チャレンジコードの設定とシードでゲームを始める(シードの設定はそのまま)
+    fn start_challenge(&mut self, code: ChallengeCode) -> Result<(), String> {
+        let grid = config().grid_size;
+        // 設定のグリッドは最小の大きさ以上なので負にはならない
+        if code.grid_size != (grid.0 as u16, grid.1 as u16) {
+            return Err(format!(
+                "made on a {}x{} grid (this is {}x{})",
+                code.grid_size.0, code.grid_size.1, grid.0, grid.1
+            ));
+        }
+        let difficulty = *Difficulty::ALL
+            .get(code.difficulty as usize)
+            .ok_or("unknown difficulty")?;
+        let level = code.level as usize;
+        if level >= Level::builtin().len() {
+            return Err(format!("level {} not found", level + 1));
+        }
+        self.mode = code.mode;
+        self.level = level;
+        self.campaign = code.campaign;
+        self.settings.food_count = code.food_count as usize;
+        self.settings.lives = code.lives as u32;
+        self.settings.max_tick_rate = code.max_tick_rate as u32;
+        self.set_difficulty(difficulty);
+        let fixed_seed = self.settings.seed;
+        self.settings.seed = Some(code.seed);
+        self.restart();
+        self.settings.seed = fixed_seed;
+        Ok(())
+    }
+
+    // チャレンジコードの入力画面のキー入力(文字はtext_input_eventで受け取る)
+    fn key_down_enter_code(&mut self, key: KeyCode) {
+        match key {
+            // Enterで読んで始める
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                match ChallengeCode::decode(self.code_input.text())
+                    .and_then(|code| self.start_challenge(code))
+                {
+                    Ok(()) => self.code_error = None,
+                    Err(e) => self.code_error = Some(e),
+                }
+            }
+            // Backspaceで1文字消し、空ならタイトル画面に戻る
+            KeyCode::Back if !self.code_input.is_empty() => self.code_input.backspace(),
+            KeyCode::Back | KeyCode::Escape => self.scene = Scene::MainMenu,
+            _ => (),
+        }
+    }
+
+    // チャレンジコードの入力画面の表示
+    fn enter_code_lines(&self) -> Vec<String> {
+        let mut lines = vec!["Enter a challenge code".to_string(), self.code_input.line()];
+        if let Some(error) = &self.code_error {
+            lines.push(format!("Could not start: {}", error));
+        }
+        lines.push("Enter: play  Backspace: delete / back".to_string());
+        lines
+    }
+
+    // 接続待ちの画面に表示する行
+    fn lobby_lines(&self) -> Vec<String> {
+        let status = self
+            .net
+            .as_ref()
+            .map(|net| net.status.clone())
+            .unwrap_or_default();
+        vec![
+            "Network play".to_string(),
+            status,
+            "Backspace: back".to_string(),
+        ]
+    }
+
+    // リプレイの再生を終え、設定を戻してタイトル画面に戻る
+    fn stop_playback(&mut self) {
+        if let Some(playback) = self.playback.take() {
+            self.settings = playback.settings;
+        }
+        self.reset();
+    }
+
+    // デモを終えてタイトル画面に戻る
+    fn stop_demo(&mut self) {
+        self.reset();
+    }
+
+    // タイトル画面で放置されたらデモを始め、デモが終わったらしばらくして次のデモ
+    fn update_idle(&mut self) {
+        if self.scene == Scene::GameOver && !self.demo
+            || self.playback.is_some()
+            || self.net.is_some()
+        {
+            return;
+        }
+        self.idle_ticks += 1;
+        if self.idle_ticks >= DEMO_IDLE_SECONDS * self.effective_tick_rate() {
+            self.start_demo();
+        }
+    }
+
+    // 人数を変更して蛇を置き直す
+    fn set_players(&mut self, players: usize) {
+        self.players = players;
+        self.reset();
+    }
+
+    // ステージを変更して蛇とfoodを置き直す(levelは一覧の長さで折り返す)
+    fn set_level(&mut self, level: usize) {
+        self.level = level % self.levels.len();
+        self.reset();
+    }
+
+    // 難易度を変更し、それに合わせてupdateの回数と画面端の扱い(モードで決まっていなければ)も戻す
+    fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+        self.tick_rate = difficulty.tick_rate();
+        self.board.topology = self.mode.rules().topology.unwrap_or(difficulty.topology);
+    }
+
+    // プレイ中のキー入力
+    fn key_down_playing(&mut self, key: KeyCode) {
+        let Some(action) = self.settings.bindings.action(key) else {
+            return;
+        };
+        // 一時停止(デフォルトはSpaceかP)
+        // ネットワーク対戦中は相手も止まってしまうので一時停止しない
+        if action == Action::Pause {
+            if self.net.is_none() {
+                self.scene = Scene::Paused;
+            }
+            return;
+        }
+        // key入力を受け取る
+        // 1人プレイなら2人目のキーでも1人目の蛇を動かす
+        if let Some((player, dir)) = action.steer() {
+            self.steer_player(if self.players == 1 { 0 } else { player }, dir);
+        }
+    }
+
+    // 音量の設定を変更し、鳴っている音に反映して保存
+    fn change_volume(&mut self, ctx: &Context, change: impl FnOnce(&mut Settings)) {
+        change(&mut self.settings);
+        self.audio.set_volume(&self.settings);
+        self.save_settings(ctx);
+    }
+
+    // 速さの増減を変えて保存(次のupdateから反映される)
+    fn change_speed(&mut self, ctx: &Context, delta: i32) {
+        self.settings.speed_adjust =
+            (self.settings.speed_adjust + delta).clamp(-MAX_SPEED_ADJUST, MAX_SPEED_ADJUST);
+        self.save_settings(ctx);
+    }
+
+    // 設定を保存する。書き込めなくてもゲームは続ける
+    fn save_settings(&self, ctx: &Context) {
+        if let Err(e) = self.settings.save(ctx) {
+            eprintln!("Could not save settings: {}", e);
+        }
+    }
+
+    // i番目のプレイヤーの蛇の操作に方向入力を渡す(次のtickで向きを変える)
+    pub(crate) fn steer_player(&mut self, i: usize, dir: Direction) {
+        // ネットワーク対戦中はどちらのキーでも自分の蛇の入力として次のtickで送る(観戦中は何もしない)
+        if let Some(net) = &mut self.net {
+            if net.spectator {
+                return;
+            }
+            net.pending.push(dir);
+            return;
+        }
+        self.controllers[i].input(dir);
+    }
+
+    // ヒントの矢印を出す設定なら、1人目の蛇の一番近いfoodまでの最短経路の最初の1歩
+    fn hint_step(&self) -> Option<Step> {
+        let snake = &self.snakes[0];
+        let human = snake.controller == Controller::Human && !snake.dead;
+        if !self.settings.hint_arrow || !human || self.scene != Scene::Playing {
+            return None;
+        }
+        pathfinding::first_step(&self.cells, &self.portals, snake.head.pos, |pos| {
+            pathfinding::is_food(&self.cells, pos)
+        })
+    }
+
+    // マウスで操作する設定なら、1人目の蛇をマウスカーソルのマスに向ける
+    // 差の大きい軸の方向に曲がり、真後ろならもう一方の軸で近づく(同じ列なら左に曲がる)
+    fn steer_to_cursor(&mut self) {
+        let human = self.snakes[0].controller == Controller::Human && !self.snakes[0].dead;
+        if !self.settings.mouse_steering || !human || self.demo || self.playback.is_some() {
+            return;
+        }
+        let Some((x, y)) = self.cursor else {
+            return;
+        };
+        // カメラが動いていればその分ずらして盤面の座標にする
+        let cursor = camera_coordinates(
+            graphics::Rect::new(x, y, 0.0, 0.0),
+            self.camera_focus(1.0),
+            self.board,
+        );
+        let cell = config().cell_size;
+        let target = GridPosition::new(
+            (cursor.x / cell.0 as f32).floor() as i16,
+            (cursor.y / cell.1 as f32).floor() as i16,
+        );
+        let snake = &self.snakes[0];
+        let head = snake.head.pos;
+        let Some(mut dir) = Direction::toward(head, target) else {
+            return;
+        };
+        if dir == snake.last_update_dir().inverse() {
+            let side = match dir {
+                Direction::Left | Direction::Right => GridPosition::new(head.x, target.y),
+                Direction::Up | Direction::Down => GridPosition::new(target.x, head.y),
+            };
+            dir = Direction::toward(head, side).unwrap_or(snake.dir.left());
+        }
+        if dir != snake.dir {
+            self.steer_player(0, dir);
+        }
+    }
+
+    // スワイプを始める(プレイ中だけ)
+    fn start_swipe(&mut self, x: f32, y: f32) {
+        self.idle_ticks = 0;
+        if self.scene == Scene::Playing && !self.confirm_quit && self.playback.is_none() {
+            self.swipe_start = Some((x, y));
+        }
+    }
+
+    // スワイプ中に指やマウスが動いた
+    // 最小の距離より動いたらキーと同じように方向を変え、続けて別の方向にもスワイプできるようにする
+    fn move_swipe(&mut self, x: f32, y: f32) {
+        let Some((start_x, start_y)) = self.swipe_start else {
+            return;
+        };
+        if let Some(dir) = Direction::from_swipe(x - start_x, y - start_y) {
+            self.swipe_start = Some((x, y));
+            if self.scene == Scene::Playing {
+                self.steer_player(0, dir);
+            }
+        }
+    }
+
+    // デバッグ用のキー入力。受け付けたらtrue
+    fn key_down_debug(&mut self, key: KeyCode) -> bool {
+        match key {
+            // F3でデバッグ用の表示を切り替える
+            KeyCode::F3 => self.debug.visible = !self.debug.visible,
+            // F4でコマ送りを切り替え、コマ送り中はF5で1tickだけ進める
+            // (ネットワーク対戦では相手を待たせるので使わない)
+            KeyCode::F4 if self.net.is_none() => self.frame_step = !self.frame_step,
+            KeyCode::F5 if self.frame_step => self.step_requested = true,
+            _ => return false,
+        }
+        true
+    }
+
+    // 一時停止中のキー入力
+    fn key_down_paused(&mut self, key: KeyCode) {
+        // 一時停止と同じキーで再開
+        if self.settings.bindings.action(key) == Some(Action::Pause) {
+            self.play();
+        }
+    }
+
+    // ゲームオーバー中のキー入力
+    fn key_down_game_over(&mut self, ctx: &Context, key: KeyCode) -> GameResult {
+        // 名前を入力している間は、Enterで決定、Backspaceで1文字消す、Escapeで前回の名前のまま
+        if let Some(entry) = &mut self.name_entry {
+            match key {
+                KeyCode::Return | KeyCode::NumpadEnter => self.confirm_name(ctx),
+                KeyCode::Back => entry.backspace(),
+                // Escapeで入力をやめ、前回の名前のままランキングに送信する
+                KeyCode::Escape => {
+                    self.name_entry = None;
+                    self.submit_score(self.default_name());
+                }
+                _ => (),
+            }
+            return Ok(());
+        }
+        match key {
+            // Rで最初からやり直す
+            KeyCode::R => self.restart(),
+            // Sで同じシードでやり直す(同じfoodの並びになる)
+            KeyCode::S => {
+                let fixed_seed = self.settings.seed;
+                self.settings.seed = Some(self.seed);
+                self.restart();
+                self.settings.seed = fixed_seed;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+// EventHandlerトレイトで状態の更新を行う(update, draw)
+impl event::EventHandler<ggez::GameError> for GameState {
+    // drawよりも先に呼ばれる
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.boost_held = ctx.keyboard.is_key_pressed(KeyCode::LShift)
+            || ctx.keyboard.is_key_pressed(KeyCode::RShift);
+        // コマ送り中はcheck_update_timeで溜まった分を捨て、F5が押された時だけ1tick進める
+        if self.frame_step {
+            while ctx.time.check_update_time(self.effective_tick_rate()) {}
+            if std::mem::take(&mut self.step_requested)
+                && self.scene == Scene::Playing
+                && !self.confirm_quit
+            {
+                self.update_playing();
+            }
+        }
+        while !self.frame_step && ctx.time.check_update_time(self.effective_tick_rate()) {
+            // 終了確認中はどのシーンも止める
+            if self.confirm_quit {
+                continue;
+            }
+            match self.scene {
+                Scene::Playing if self.counting_down() => (),
+                Scene::Playing if self.tutorial_waiting() => self.update_tutorial(),
+                Scene::Playing if self.net.is_some() => self.update_lockstep(),
+                Scene::Playing => self.update_playing(),
+                Scene::MainMenu | Scene::GameOver => self.update_idle(),
+                Scene::Paused
+                | Scene::Editor
+                | Scene::Controls
+                | Scene::Stats
+                | Scene::Lobby
+                | Scene::EnterCode => (),
+            }
+        }
+        self.update_network();
+        self.achievements.update(ctx.time.delta().as_secs_f32());
+        self.leaderboard.poll();
+        match self.recorder.poll() {
+            Some(Ok(_)) => self.achievements.notify("Saved GIF"),
+            Some(Err(e)) => eprintln!("Could not save GIF: {}", e),
+            None => (),
+        }
+        self.debug.update(ctx.time.delta().as_secs_f64(), self.tick);
+        // パーティクルは一時停止中と終了確認中は止める
+        if self.scene != Scene::Paused && !self.confirm_quit {
+            self.particles.update(ctx.time.delta().as_secs_f32());
+        }
+        self.death_effect.update(ctx.time.delta().as_secs_f32());
+        if let Some(dying) = &mut self.dying {
+            if !dying.update(ctx.time.delta().as_secs_f32()) {
+                self.dying = None;
+            }
+        }
+        // プレイ中の時間を数える(終了確認中は止め、蛇が動き出すまでは数えない)
+        if self.scene == Scene::Playing && !self.confirm_quit {
+            if self.counting_down() {
+                self.countdown = (self.countdown - ctx.time.delta().as_secs_f32()).max(0.0);
+            } else if !self.tutorial_waiting() {
+                self.play_time += ctx.time.delta().as_secs_f64();
+            }
+        }
+        self.dispatch_events();
+        // Discordの表示は数秒おきに送信用のスレッドへ渡すだけなのでループは止まらない
+        if self.presence.due(ctx.time.delta().as_secs_f32()) {
+            self.presence.send(self.activity());
+        }
+        // チュートリアルを最後まで終えたらそのまま普通のゲームとして続ける
+        if self.tutorial.as_ref().is_some_and(Tutorial::is_finished) {
+            self.tutorial = None;
+            self.settings.tutorial_done = true;
+            self.save_settings(ctx);
+        }
+        // このフレームで鳴らす効果音
+        for sound in std::mem::take(&mut self.sounds) {
+            self.audio.play(ctx, sound);
+        }
+        // シーンが変わっていたらBGMを切り替える
+        self.audio.set_music(ctx, self.scene.music());
+
+        Ok(())
+    }
+
+    /// 描画
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        // canvasインスタンスを作成、描画
+        let background = self.settings.active_theme().background;
+        // シェーダーの効果をかける時はウィンドウではなく画像に描く
+        let mut canvas = match &mut self.post_process {
+            Some(post_process) => post_process.canvas(ctx, background.into()),
+            None => graphics::Canvas::from_frame(ctx, graphics::Color::from(background)),
+        };
+        // ウィンドウの大きさが変わっても盤面を縦横比を保って収める
+        let coords = screen_coordinates(self.window_size);
+        canvas.set_screen_coordinates(coords);
+        let alpha = self.render_alpha(ctx);
+        let mut batch = self.batch.take().unwrap_or_else(|| CellBatch::new(ctx));
+        let mut minimap = self.minimap.take().unwrap_or_else(|| Minimap::new(ctx));
+        // 背景の模様は盤面を描くシーンで一番下に描く
+        // 自分にぶつかった直後は盤面を揺らす
+        // 盤面が画面に収まらない時はカメラが蛇のheadを追う
+        if matches!(self.scene, Scene::Playing | Scene::Paused | Scene::GameOver) {
+            let board_coords = camera_coordinates(coords, self.camera_focus(alpha), self.board);
+            canvas.set_screen_coordinates(board_coords);
+            self.death_effect.begin_shake(&mut canvas, board_coords);
+            self.draw_background(ctx, &mut canvas)?;
+        }
+
+        match self.scene {
+            Scene::MainMenu => {
+                let mut lines = vec![
+                    "Snake!".to_string(),
+                    format!("Difficulty: < {} >", self.difficulty.name),
+                    format!("Level: < {} >", self.levels[self.level].name),
+                    format!("Players: {} (1/2)", self.players),
+                    format!(
+                        "CPU opponent: {} (C)",
+                        if self.opponent && self.players == 1 {
+                            "On"
+                        } else {
+                            "Off"
+                        }
+                    ),
+                    format!("Campaign: {} (A)", if self.campaign { "On" } else { "Off" }),
+                    format!("Mode: {} (X)", self.mode_text()),
+                    format!(
+                        "Challenges: {} (J)",
+                        if self.use_challenges { "On" } else { "Off" }
+                    ),
+                    "Press Enter to start".to_string(),
+                    "Press U to play the tutorial".to_string(),
+                    "Press E to edit the level".to_string(),
+                    "Press K to change controls".to_string(),
+                    "Press S to see statistics".to_string(),
+                    "Press V to watch the last replay".to_string(),
+                    "Press F to enter a challenge code".to_string(),
+                    format!("Press N to host a network game (port {})", DEFAULT_NET_PORT),
+                    self.settings.volume_text(),
+                    format!("Speed: {:+} (-/+)", self.settings.speed_adjust),
+                    format!("Theme: {} (T)", self.settings.theme.name()),
+                    format!("Background: {} (G)", self.settings.background.name()),
+                    format!("Skin: {} (O)", self.skin(0).name()),
+                    format!(
+                        "Colorblind mode: {} (B)",
+                        if self.settings.colorblind {
+                            "On"
+                        } else {
+                            "Off"
+                        }
+                    ),
+                ];
+                if self.save_path().exists() {
+                    lines.push("Press L to continue the saved game".to_string());
+                }
+                draw_overlay(&mut canvas, &lines);
+            }
+            Scene::Playing => {
+                self.draw_board(ctx, &mut canvas, &mut batch, &mut minimap, alpha)?;
+                draw_challenges(&mut canvas, &self.challenges);
+                if self.counting_down() {
+                    let title = self.campaign.then(|| {
+                        format!("Level {}: {}", self.stage + 1, self.levels[self.level].name)
+                    });
+                    draw_countdown(&mut canvas, self.countdown.ceil() as u32, title.as_deref());
+                }
+                if self.frame_step {
+                    draw_banner(&mut canvas, "FRAME STEP - F5: next tick  F4: resume");
+                } else if self.demo {
+                    draw_banner(&mut canvas, "DEMO - press any key");
+                } else if self.playback.is_some() {
+                    draw_banner(&mut canvas, "REPLAY - press any key");
+                } else if self.net.as_ref().is_some_and(|net| net.spectator) {
+                    draw_banner(&mut canvas, &format!("SPECTATING  {}", self.score_text()));
+                } else if let Some(step) = self.tutorial.as_ref().and_then(Tutorial::current) {
+                    draw_banner(&mut canvas, step.prompt);
+                }
+            }
+            Scene::Paused => {
+                self.draw_board(ctx, &mut canvas, &mut batch, &mut minimap, alpha)?;
+                draw_overlay(&mut canvas, &["PAUSED".to_string()]);
+            }
+            // 揺れとフラッシュ、蛇が消えていくのが終わるまではゲームオーバーの画面を出さない
+            Scene::GameOver if self.death_effect.is_active() || self.dying.is_some() => {
+                self.draw_board(ctx, &mut canvas, &mut batch, &mut minimap, alpha)?;
+            }
+            Scene::GameOver => {
+                self.draw_board(ctx, &mut canvas, &mut batch, &mut minimap, alpha)?;
+                let title = match self.outcome {
+                    Some(GameOutcome::Won) => "You Win!".to_string(),
+                    Some(GameOutcome::PlayerWon(i)) => format!("{} wins!", self.label(i)),
+                    Some(GameOutcome::Draw) => "Draw!".to_string(),
+                    Some(GameOutcome::TimeUp) => "Time Up!".to_string(),
+                    _ => "Game Over".to_string(),
+                };
+                draw_overlay(
+                    &mut canvas,
+                    &[
+                        title,
+                        self.score_text(),
+                        self.max_combo_text(),
+                        format!("Seed: {}", self.seed),
+                        match self.challenge_code() {
+                            Some(code) => format!("Challenge code: {}", code.encode()),
+                            None => "Challenge code: not available for custom levels".to_string(),
+                        },
+                        match &self.name_entry {
+                            Some(entry) => format!("New high score! Name: {}", entry.line()),
+                            None if self.net.is_some() => "Press any key".to_string(),
+                            None => "R: restart  S: replay this seed".to_string(),
+                        },
+                    ],
+                );
+                draw_high_scores(
+                    &mut canvas,
+                    &self.high_scores.table(self.mode, now_timestamp()),
+                    self.high_score_rank,
+                );
+                draw_leaderboard(&mut canvas, &self.leaderboard.lines());
+                draw_challenges(&mut canvas, &self.challenges);
+                if self.mode.rules().battle {
+                    draw_standings(&mut canvas, &self.standings_lines());
+                }
+            }
+            Scene::Editor => {
+                if let Some(editor) = &self.editor {
+                    editor.draw(&mut canvas, &mut batch, &self.settings.active_theme());
+                }
+            }
+            Scene::Controls => draw_overlay(&mut canvas, &self.controls_lines()),
+            Scene::Stats => draw_overlay(&mut canvas, &self.stats.lines()),
+            Scene::Lobby => draw_overlay(&mut canvas, &self.lobby_lines()),
+            Scene::EnterCode => draw_overlay(&mut canvas, &self.enter_code_lines()),
+        }
+
+        // 実績の通知とデバッグ用の表示はどのシーンの上にも描画
+        self.achievements.draw_toast(&mut canvas);
+        self.debug
+            .draw(&mut canvas, &self.debug_lines(ctx.time.fps()));
+
+        // 終了確認中ならどのシーンの上にもオーバーレイを描画
+        if self.confirm_quit {
+            let mut lines = vec!["Quit? Y/N".to_string()];
+            if self.can_save() {
+                lines.push("S: save and quit".to_string());
+            }
+            draw_overlay(&mut canvas, &lines);
+        }
+
+        self.batch = Some(batch);
+        self.minimap = Some(minimap);
+
+        // 実際に描画
+        canvas.finish(ctx)?;
+        if let Some(post_process) = &mut self.post_process {
+            post_process.present(ctx)?;
+        }
+        // 描き終えたフレームをGIF用に取る(蛇が消え終わるまで)
+        if self.settings.record_gif {
+            let recording = self.scene == Scene::Playing || self.dying.is_some();
+            self.recorder.capture(ctx, recording)?;
+        }
+
+        // 次のupdateまで他スレッドも実行
+        ggez::timer::yield_now();
+
+        Ok(())
+    }
+
+    /// ウィンドウを閉じようとした時に呼ばれる
+    fn quit_event(&mut self, _ctx: &mut Context) -> GameResult<bool> {
+        // ゲームの途中なら一度止めて、保存するか確認する
+        if self.can_save() && !self.confirm_quit {
+            self.confirm_quit = true;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// キーが押されたタイミングで呼ばれる
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
+        let Some(key) = input.keycode else {
+            return Ok(());
+        };
+        // 終了確認中はY/N(ゲームの途中ならSも)だけを受け付ける
+        if self.confirm_quit {
+            match key {
+                KeyCode::Y => ctx.request_quit(),
+                KeyCode::N => self.confirm_quit = false,
+                KeyCode::S if self.can_save() => {
+                    self.save_game()?;
+                    ctx.request_quit();
+                }
+                _ => (),
+            }
+            return Ok(());
+        }
+        self.idle_ticks = 0;
+        // F11かAlt+Enterで全画面表示を切り替えて保存(どのシーンでも使える)
+        if key == KeyCode::F11 || (key == KeyCode::Return && input.mods.contains(KeyMods::ALT)) {
+            self.settings.fullscreen = !self.settings.fullscreen;
+            ctx.gfx.set_fullscreen(self.settings.fullscreen_type())?;
+            self.save_settings(ctx);
+            return Ok(());
+        }
+        // F12で画面をPNGで保存する(どのシーンでも使える)
+        if key == KeyCode::F12 {
+            match Self::save_screenshot(ctx) {
+                Ok(()) => self.achievements.notify("Saved screenshot"),
+                Err(e) => eprintln!("Could not save screenshot: {}", e),
+            }
+            return Ok(());
+        }
+        // デバッグ用のキーはデモやリプレイの再生中も使える
+        if self.key_down_debug(key) {
+            return Ok(());
+        }
+        // デモ中はどのキーでもタイトル画面に戻る
+        if self.demo {
+            self.stop_demo();
+            return Ok(());
+        }
+        // リプレイの再生中も同じ
+        if self.playback.is_some() {
+            self.stop_playback();
+            return Ok(());
+        }
+        // ネットワーク対戦が終わったらどのキーでも接続を終えてタイトル画面に戻る
+        if self.net.is_some() && self.scene == Scene::GameOver {
+            self.stop_network();
+            return Ok(());
+        }
+        // 割り当てるキーを待っている間やプレイ中の操作に割り当てたキーは
+        // 下の共通のキーより優先する
+        let bound = matches!(self.scene, Scene::Playing | Scene::Paused)
+            && self.settings.bindings.action(key).is_some();
+        // 文字を入力している間は共通のキーを使わない
+        let typing = self.scene == Scene::EnterCode || self.name_entry.is_some();
+        if self.waiting_for_key || bound || typing {
+            return self.key_down_scene(ctx, key);
+        }
+        match key {
+            // Escapeで終了確認を表示
+            KeyCode::Escape => self.confirm_quit = true,
+            // Hでハイビジビリティモードを切り替えて保存
+            KeyCode::H => {
+                self.settings.high_visibility = !self.settings.high_visibility;
+                self.save_settings(ctx);
+            }
+            // Tで色のテーマを切り替えて保存
+            KeyCode::T => {
+                self.settings.theme = self.settings.theme.next();
+                self.save_settings(ctx);
+            }
+            // Bで色覚モード(見分けやすい配色と形)を切り替えて保存
+            KeyCode::B => {
+                self.settings.colorblind = !self.settings.colorblind;
+                self.save_settings(ctx);
+            }
+            // Oで解除したスキンを順番に切り替えて保存
+            KeyCode::O => {
+                self.settings.skin = self.next_skin();
+                self.save_settings(ctx);
+            }
+            // Gで盤面の背景の模様を切り替えて保存
+            KeyCode::G => {
+                self.settings.background = self.settings.background.next();
+                self.save_settings(ctx);
+            }
+            // Mで消音を切り替え、9/0で全体、,/.でBGM、[/]で効果音の音量を変えて保存
+            KeyCode::M => self.change_volume(ctx, |s| s.muted = !s.muted),
+            KeyCode::Key9 => self.change_volume(ctx, |s| {
+                s.master_volume = s.master_volume.saturating_sub(VOLUME_STEP)
+            }),
+            KeyCode::Key0 => self.change_volume(ctx, |s| {
+                s.master_volume = (s.master_volume + VOLUME_STEP).min(100)
+            }),
+            KeyCode::Comma => self.change_volume(ctx, |s| {
+                s.music_volume = s.music_volume.saturating_sub(VOLUME_STEP)
+            }),
+            KeyCode::Period => self.change_volume(ctx, |s| {
+                s.music_volume = (s.music_volume + VOLUME_STEP).min(100)
+            }),
+            KeyCode::LBracket => self.change_volume(ctx, |s| {
+                s.sfx_volume = s.sfx_volume.saturating_sub(VOLUME_STEP)
+            }),
+            KeyCode::RBracket => self.change_volume(ctx, |s| {
+                s.sfx_volume = (s.sfx_volume + VOLUME_STEP).min(100)
+            }),
+            // +/-でゲームの速さを変えて保存
+            // (USキーボードの+はShift+=なので=でも速くし、テンキーの+/-も使える)
+            KeyCode::Equals | KeyCode::Plus | KeyCode::NumpadAdd => self.change_speed(ctx, 1),
+            KeyCode::Minus | KeyCode::NumpadSubtract => self.change_speed(ctx, -1),
+            // それ以外は現在のシーンに任せる
+            _ => self.key_down_scene(ctx, key)?,
+        }
+        Ok(())
+    }
+
+    /// ウィンドウの大きさが変わったタイミングで呼ばれる
+    /// 大きさは物理ピクセルなので論理ピクセルにして覚える(描画は物理ピクセルのまま細かく描かれる)
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        let scale = scale_factor(ctx);
+        self.window_size = (width / scale, height / scale);
+        Ok(())
+    }
+
+    /// マウスのボタンが押されたタイミングで呼ばれる
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        let scale = scale_factor(ctx);
+        let (x, y) = window_to_screen(self.window_size, x / scale, y / scale);
+        if let (Scene::Editor, Some(editor)) = (self.scene, &mut self.editor) {
+            // ツールバーのクリックを優先し、それ以外は左ボタンで塗り始める
+            if button == MouseButton::Left && !editor.click_toolbar(x, y) {
+                editor.painting = true;
+                if let Some(pos) = GridPosition::from_screen(x, y) {
+                    editor.paint(pos);
+                }
+            }
+        } else if button == MouseButton::Left {
+            // プレイ中は左ボタンを押したままドラッグしてスワイプできる
+            self.start_swipe(x, y);
+        }
+        Ok(())
+    }
+
+    /// マウスのボタンが離されたタイミングで呼ばれる
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) -> GameResult {
+        if let Some(editor) = &mut self.editor {
+            if button == MouseButton::Left {
+                editor.painting = false;
+            }
+        }
+        if button == MouseButton::Left {
+            self.swipe_start = None;
+        }
+        Ok(())
+    }
+
+    /// マウスが動いたタイミングで呼ばれる
+    fn mouse_motion_event(
+        &mut self,
+        ctx: &mut Context,
+        x: f32,
+        y: f32,
+        _dx: f32,
+        _dy: f32,
+    ) -> GameResult {
+        let scale = scale_factor(ctx);
+        let (x, y) = window_to_screen(self.window_size, x / scale, y / scale);
+        if let Some(editor) = &mut self.editor {
+            editor.hovered = GridPosition::from_screen(x, y);
+            // ボタンを押したまま動かしたら通ったマスも塗る
+            if let (true, Some(pos)) = (editor.painting, editor.hovered) {
+                editor.paint(pos);
+            }
+        }
+        self.cursor = Some((x, y));
+        self.move_swipe(x, y);
+        Ok(())
+    }
+
+    /// 文字が入力されたタイミングで呼ばれる(キーボードの配列やShiftを反映した文字)
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if self.scene == Scene::EnterCode {
+            self.code_input.insert(character);
+        } else if let Some(entry) = &mut self.name_entry {
+            entry.insert(character);
+        }
+        Ok(())
+    }
+
+    /// タッチスクリーンに触れた・指を動かした・離したタイミングで呼ばれる
+    /// マウスのドラッグと同じようにスワイプで方向を変える
+    fn touch_event(&mut self, ctx: &mut Context, phase: TouchPhase, x: f64, y: f64) -> GameResult {
+        let scale = scale_factor(ctx);
+        let (x, y) = window_to_screen(self.window_size, x as f32 / scale, y as f32 / scale);
+        match phase {
+            TouchPhase::Started => self.start_swipe(x, y),
+            TouchPhase::Moved => self.move_swipe(x, y),
+            TouchPhase::Ended | TouchPhase::Cancelled => self.swipe_start = None,
+        }
+        Ok(())
+    }
+
+    /// ウィンドウがフォーカスを得た・失ったタイミングで呼ばれる
+    /// 他のウィンドウに切り替えている間に死なないように一時停止する
+    /// (フォーカスが戻っても再開はせず、一時停止のキーを押すまで待つ)
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) -> GameResult {
+        // デモとリプレイは見ているだけ、ネットワーク対戦は相手も止まるので一時停止しない
+        let playing = self.scene == Scene::Playing
+            && !self.demo
+            && self.playback.is_none()
+            && self.net.is_none();
+        if !gained && playing {
+            self.scene = Scene::Paused;
+        }
+        Ok(())
+    }
+
+    /// ゲームパッドのボタンが押されたタイミングで呼ばれる
+    fn gamepad_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        btn: Button,
+        _id: GamepadId,
+    ) -> GameResult {
+        self.idle_ticks = 0;
+        // デモ中はどのボタンでもタイトル画面に戻る
+        if self.demo {
+            self.stop_demo();
+            return Ok(());
+        }
+        // リプレイの再生中も同じ
+        if self.playback.is_some() {
+            self.stop_playback();
+            return Ok(());
+        }
+        // プレイ中だけ十字キーで方向を変える
+        if self.scene == Scene::Playing && !self.confirm_quit {
+            if let Some(dir) = Direction::from_button(btn) {
+                self.steer_player(0, dir);
+            }
+        }
+        Ok(())
+    }
+
+    /// ゲームパッドのスティックが動いたタイミングで呼ばれる
+    fn gamepad_axis_event(
+        &mut self,
+        _ctx: &mut Context,
+        axis: Axis,
+        value: f32,
+        _id: GamepadId,
+    ) -> GameResult {
+        match axis {
+            Axis::LeftStickX => self.stick.0 = value,
+            Axis::LeftStickY => self.stick.1 = value,
+            _ => return Ok(()),
+        }
+        // 傾けた方向が変わった時だけ方向を変える
+        let dir = Direction::from_stick(self.stick.0, self.stick.1);
+        if dir != self.stick_dir {
+            self.stick_dir = dir;
+            if let Some(dir) = dir {
+                if self.scene == Scene::Playing && !self.confirm_quit {
+                    self.steer_player(0, dir);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boosting_speeds_up_only_the_boosting_snake() {
+        let settings = Settings {
+            seed: Some(1),
+            ..Settings::default()
+        };
+        let levels = Level::builtin();
+        let mut state = GameState::new(
+            settings,
+            HighScores::default(),
+            Difficulty::NORMAL,
+            levels,
+            0,
+            2,
+            false,
+        );
+        state.headless = true;
+        state.scene = Scene::Playing;
+        state.boost_held = true;
+        let ahead = |snake: &Snake, steps: usize| {
+            (0..steps).fold(snake.head.pos, |pos, _| {
+                GridPosition::new_from_move(pos, snake.dir, state.board).unwrap()
+            })
+        };
+        let expected = [ahead(&state.snakes[0], 2), ahead(&state.snakes[1], 1)];
+        let rate = state.effective_tick_rate();
+        state.play_tick();
+        assert!(state.snakes[0].boosting);
+        assert_eq!(state.snakes[0].head.pos, expected[0]);
+        assert_eq!(state.snakes[1].head.pos, expected[1]);
+        // ゲーム全体の速さは変わらない
+        assert_eq!(state.effective_tick_rate(), rate);
+    }
+}
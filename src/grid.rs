@@ -0,0 +1,518 @@
+use serde::{Deserialize, Serialize};
+
+use ggez::{graphics, input::gamepad::gilrs::Button};
+
+use crate::config::{config, STICK_DEAD_ZONE, SWIPE_MIN_DISTANCE};
+use crate::food::FoodKind;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct GridPosition {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl GridPosition {
+    // 画面上のピクセル座標にあるマス。グリッドの外ならNone
+    pub fn from_screen(x: f32, y: f32) -> Option<Self> {
+        let cell = config().cell_size;
+        let grid = config().grid_size;
+        let gx = (x / cell.0 as f32).floor() as i16;
+        let gy = (y / cell.1 as f32).floor() as i16;
+        if x >= 0.0 && y >= 0.0 && gx < grid.0 && gy < grid.1 {
+            Some(GridPosition::new(gx, gy))
+        } else {
+            None
+        }
+    }
+
+    // x, yあわせてGridPositionとする
+    pub fn new(x: i16, y: i16) -> Self {
+        GridPosition { x, y }
+    }
+
+    // 受け取ったDirectionをGridPositionの座標に変換
+    // Boundedで画面端を越える場合と、盤面が縮んで通れなくなったマスに入る場合はNone
+    pub fn new_from_move(pos: GridPosition, dir: Direction, board: Board) -> Option<Self> {
+        let (x, y) = match dir {
+            Direction::Up => (pos.x, pos.y - 1),
+            Direction::Down => (pos.x, pos.y + 1),
+            Direction::Left => (pos.x - 1, pos.y),
+            Direction::Right => (pos.x + 1, pos.y),
+        };
+        let pos = GridPosition::new(x, y);
+        match board.topology {
+            Topology::Wrapping => Some(GridPosition::new(
+                x.rem_euclid(board.width),
+                y.rem_euclid(board.height),
+            ))
+            .filter(|pos| board.contains(*pos)),
+            Topology::Bounded => board.contains(pos).then_some(pos),
+        }
+    }
+
+    // 画面端の扱いを考えたマス同士の距離(縦と横の移動回数の合計)
+    pub fn distance(self, other: GridPosition, board: Board) -> i16 {
+        let dx = (self.x - other.x).abs();
+        let dy = (self.y - other.y).abs();
+        match board.topology {
+            Topology::Wrapping => dx.min(board.width - dx) + dy.min(board.height - dy),
+            Topology::Bounded => dx + dy,
+        }
+    }
+}
+
+/// We implement the `From` trait, which in this case allows us to convert easily between
+/// a `GridPosition` and a ggez `graphics::Rect` which fills that grid cell.
+/// Now we can just call `.into()` on a `GridPosition` where we want a
+/// `Rect` that represents that grid cell.
+impl From<GridPosition> for graphics::Rect {
+    fn from(pos: GridPosition) -> Self {
+        let cell = config().cell_size;
+        graphics::Rect::new_i32(
+            pos.x as i32 * cell.0 as i32,
+            pos.y as i32 * cell.1 as i32,
+            cell.0 as i32,
+            cell.1 as i32,
+        )
+    }
+}
+
+/// And here we implement `From` again to allow us to easily convert between
+/// `(i16, i16)` and a `GridPosition`.
+impl From<(i16, i16)> for GridPosition {
+    fn from(pos: (i16, i16)) -> Self {
+        GridPosition { x: pos.0, y: pos.1 }
+    }
+}
+
+// 画面端の扱い
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topology {
+    // 反対側の端から出てくる
+    Wrapping,
+    // 画面端は壁(ぶつかるとgameover)
+    Bounded,
+}
+
+// 盤面の大きさ(マス数)と画面端の扱い
+// 蛇やfoodの移動はグローバルな設定ではなくこれを受け取って計算する
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Board {
+    pub width: i16,
+    pub height: i16,
+    pub topology: Topology,
+    // 外側から何周のマスが通れないか(サバイバルで盤面が縮んだ分)
+    pub margin: i16,
+}
+
+impl Board {
+    pub fn new(size: (i16, i16), topology: Topology) -> Self {
+        Board {
+            width: size.0,
+            height: size.1,
+            topology,
+            margin: 0,
+        }
+    }
+
+    // config.tomlのグリッドの大きさの盤面
+    pub fn from_config(topology: Topology) -> Self {
+        Board::new(config().grid_size, topology)
+    }
+
+    // 盤面全体の大きさ(ピクセル)
+    pub fn pixel_size(self) -> (f32, f32) {
+        let cell = config().cell_size;
+        (
+            self.width as f32 * cell.0 as f32,
+            self.height as f32 * cell.1 as f32,
+        )
+    }
+
+    // 盤面が画面に収まらず、カメラが蛇を追うか
+    pub fn has_camera(self) -> bool {
+        let board = self.pixel_size();
+        let screen = config().screen_size();
+        board.0 > screen.0 || board.1 > screen.1
+    }
+
+    // posが盤面の通れる範囲の中ならtrue(縮んだ外側のマスは外)
+    pub fn contains(self, pos: GridPosition) -> bool {
+        (self.margin..self.width - self.margin).contains(&pos.x)
+            && (self.margin..self.height - self.margin).contains(&pos.y)
+    }
+
+    // posからdirに進むと画面端を越えて反対側に出るならtrue(画面端が壁の盤面では常にfalse)
+    pub fn wraps(self, pos: GridPosition, dir: Direction) -> bool {
+        let bounded = Board {
+            topology: Topology::Bounded,
+            ..self
+        };
+        self.topology == Topology::Wrapping
+            && GridPosition::new_from_move(pos, dir, self).is_some()
+            && GridPosition::new_from_move(pos, dir, bounded).is_none()
+    }
+}
+
+// 盤面のマスの中身
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cell {
+    #[default]
+    Empty,
+    // i番目の蛇のheadとbody
+    SnakeHead(usize),
+    SnakeBody(usize),
+    Food(FoodKind),
+    PowerUp,
+    // 障害物と、盤面が縮んで通れなくなったマス
+    Wall,
+    Portal,
+}
+
+// 盤面のマスごとの中身
+// foodを置くマス探し、コンピュータの操作、ミニマップはここを見て、何がどこにあるかを1か所で調べる
+#[derive(Clone, Debug)]
+pub struct Cells {
+    board: Board,
+    cells: Vec<Cell>,
+}
+
+impl Cells {
+    // 何もない盤面(縮んで通れなくなったマスだけ壁)
+    pub fn new(board: Board) -> Self {
+        let mut cells = Cells {
+            board,
+            cells: Vec::new(),
+        };
+        cells.reset(board);
+        cells
+    }
+
+    // 何もない盤面に戻す(毎tick作り直すので、同じ大きさならメモリを使い回す)
+    pub fn reset(&mut self, board: Board) {
+        let width = board.width.max(0) as usize;
+        let height = board.height.max(0) as usize;
+        self.board = board;
+        self.cells.clear();
+        self.cells.resize(width * height, Cell::Empty);
+        if board.margin <= 0 {
+            return;
+        }
+        // 上下は端から端まで、左右はその間だけ壁にする
+        let margin = (board.margin.max(0) as usize).min(height);
+        for row in (0..margin).chain(height - margin..height) {
+            self.cells[row * width..(row + 1) * width].fill(Cell::Wall);
+        }
+        let side = (board.margin.max(0) as usize).min(width);
+        for row in margin..height - margin {
+            self.cells[row * width..row * width + side].fill(Cell::Wall);
+            self.cells[(row + 1) * width - side..(row + 1) * width].fill(Cell::Wall);
+        }
+    }
+
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    fn index(&self, pos: GridPosition) -> Option<usize> {
+        let inside =
+            (0..self.board.width).contains(&pos.x) && (0..self.board.height).contains(&pos.y);
+        inside.then(|| pos.y as usize * self.board.width as usize + pos.x as usize)
+    }
+
+    // posのマスの中身(盤面の外は壁)
+    pub fn get(&self, pos: GridPosition) -> Cell {
+        self.index(pos).map_or(Cell::Wall, |i| self.cells[i])
+    }
+
+    pub fn set(&mut self, pos: GridPosition, cell: Cell) {
+        if let Some(i) = self.index(pos) {
+            self.cells[i] = cell;
+        }
+    }
+
+    // 蛇か壁があって通れないマスならtrue
+    pub fn is_blocked(&self, pos: GridPosition) -> bool {
+        matches!(
+            self.get(pos),
+            Cell::SnakeHead(_) | Cell::SnakeBody(_) | Cell::Wall
+        )
+    }
+
+    // 何もないマスならtrue
+    pub fn is_empty(&self, pos: GridPosition) -> bool {
+        self.get(pos) == Cell::Empty
+    }
+
+    // 全てのマスの位置と中身
+    pub fn iter(&self) -> impl Iterator<Item = (GridPosition, Cell)> + '_ {
+        let width = self.board.width.max(1) as usize;
+        self.cells.iter().enumerate().map(move |(i, cell)| {
+            (
+                GridPosition::new((i % width) as i16, (i / width) as i16),
+                *cell,
+            )
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    // 全ての方向
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    // 左に曲がった後の方向
+    pub fn left(self) -> Self {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    // 受け取ったDirectionを逆に変換
+    pub fn inverse(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    // fromのマスからtoのマスに向かう方向(差の大きい軸を優先する)。同じマスならNone
+    pub fn toward(from: GridPosition, to: GridPosition) -> Option<Direction> {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        if dx == 0 && dy == 0 {
+            None
+        } else if dx.abs() >= dy.abs() {
+            Some(if dx > 0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            })
+        } else {
+            Some(if dy > 0 {
+                Direction::Down
+            } else {
+                Direction::Up
+            })
+        }
+    }
+
+    // ゲームパッドの十字キーを受け取ったらSomeを返す
+    pub fn from_button(btn: Button) -> Option<Direction> {
+        match btn {
+            Button::DPadUp => Some(Direction::Up),
+            Button::DPadDown => Some(Direction::Down),
+            Button::DPadLeft => Some(Direction::Left),
+            Button::DPadRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    // 画面上でスワイプした移動量(dx, dy)を方向に変換。短すぎればNone
+    // 大きく動いた軸の方を優先する(yは下が正)
+    pub fn from_swipe(dx: f32, dy: f32) -> Option<Direction> {
+        if dx.hypot(dy) < SWIPE_MIN_DISTANCE {
+            None
+        } else {
+            Direction::from_stick(dx, -dy)
+        }
+    }
+
+    // スティックの傾き(x, y)を方向に変換。デッドゾーン内ならNone
+    // 大きく傾いている軸の方を優先する(yは上が正)
+    pub fn from_stick(x: f32, y: f32) -> Option<Direction> {
+        if x.abs() < STICK_DEAD_ZONE && y.abs() < STICK_DEAD_ZONE {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0.0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            })
+        } else {
+            Some(if y > 0.0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            })
+        }
+    }
+}
+
+/// This is mostly just a semantic abstraction over a `GridPosition` to represent
+/// a segment of the snake. It could be useful to, say, have each segment contain its
+/// own color or something similar. This is an exercise left up to the reader ;)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Segment {
+    pub pos: GridPosition,
+    // 前のupdateでいたマス(描画でその間を補間する)。動いていなければNone
+    #[serde(skip)]
+    pub prev: Option<GridPosition>,
+}
+
+impl Segment {
+    pub fn new(pos: GridPosition) -> Self {
+        Segment { pos, prev: None }
+    }
+
+    // 前のマスから今のマスまでalpha(0.0〜1.0)の割合だけ進んだ位置のRect
+    // 前のマスが隣でなければ(画面端やワープで飛んだ場合)補間せず今のマス
+    pub fn rect(&self, alpha: f32) -> graphics::Rect {
+        let to: graphics::Rect = self.pos.into();
+        let Some(prev) = self
+            .prev
+            .filter(|p| (p.x - self.pos.x).abs() + (p.y - self.pos.y).abs() == 1)
+        else {
+            return to;
+        };
+        let from: graphics::Rect = prev.into();
+        graphics::Rect::new(
+            from.x + (to.x - from.x) * alpha,
+            from.y + (to.y - from.y) * alpha,
+            to.w,
+            to.h,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_one_cell_in_each_direction() {
+        let board = Board::new((10, 8), Topology::Bounded);
+        let pos = GridPosition::new(4, 4);
+        let moved: Vec<Option<GridPosition>> = Direction::ALL
+            .iter()
+            .map(|dir| GridPosition::new_from_move(pos, *dir, board))
+            .collect();
+        assert_eq!(
+            moved,
+            vec![
+                Some(GridPosition::new(4, 3)),
+                Some(GridPosition::new(4, 5)),
+                Some(GridPosition::new(3, 4)),
+                Some(GridPosition::new(5, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn treats_the_dead_zone_and_outside_as_walls() {
+        let mut board = Board::new((6, 5), Topology::Bounded);
+        board.margin = 1;
+        let mut cells = Cells::new(board);
+        assert_eq!(cells.get(GridPosition::new(0, 2)), Cell::Wall);
+        assert_eq!(cells.get(GridPosition::new(-1, 2)), Cell::Wall);
+        assert!(cells.is_empty(GridPosition::new(1, 1)));
+        cells.set(GridPosition::new(1, 1), Cell::SnakeBody(0));
+        assert!(cells.is_blocked(GridPosition::new(1, 1)));
+        assert_eq!(
+            cells
+                .iter()
+                .filter(|(_, cell)| *cell == Cell::Empty)
+                .count(),
+            4 * 3 - 1
+        );
+    }
+
+    #[test]
+    fn points_toward_the_farther_axis() {
+        let from = GridPosition::new(5, 5);
+        assert_eq!(
+            Direction::toward(from, GridPosition::new(9, 7)),
+            Some(Direction::Right)
+        );
+        assert_eq!(
+            Direction::toward(from, GridPosition::new(4, 1)),
+            Some(Direction::Up)
+        );
+        assert_eq!(Direction::toward(from, from), None);
+    }
+
+    #[test]
+    fn converts_swipes_longer_than_the_threshold() {
+        let far = SWIPE_MIN_DISTANCE * 2.0;
+        assert_eq!(Direction::from_swipe(far, 5.0), Some(Direction::Right));
+        assert_eq!(Direction::from_swipe(-far, 5.0), Some(Direction::Left));
+        assert_eq!(Direction::from_swipe(5.0, far), Some(Direction::Down));
+        assert_eq!(Direction::from_swipe(5.0, -far), Some(Direction::Up));
+        assert_eq!(Direction::from_swipe(SWIPE_MIN_DISTANCE / 2.0, 0.0), None);
+    }
+
+    #[test]
+    fn detects_moves_across_the_edge() {
+        let wrapping = Board::new((10, 8), Topology::Wrapping);
+        let bounded = Board::new((10, 8), Topology::Bounded);
+        let corner = GridPosition::new(0, 7);
+        assert!(wrapping.wraps(corner, Direction::Left));
+        assert!(wrapping.wraps(corner, Direction::Down));
+        assert!(!wrapping.wraps(corner, Direction::Right));
+        assert!(!bounded.wraps(corner, Direction::Left));
+    }
+
+    #[test]
+    fn wraps_or_stops_at_the_edges() {
+        let wrapping = Board::new((10, 8), Topology::Wrapping);
+        let bounded = Board::new((10, 8), Topology::Bounded);
+        let corner = GridPosition::new(0, 7);
+        assert_eq!(
+            GridPosition::new_from_move(corner, Direction::Left, wrapping),
+            Some(GridPosition::new(9, 7))
+        );
+        assert_eq!(
+            GridPosition::new_from_move(corner, Direction::Down, wrapping),
+            Some(GridPosition::new(0, 0))
+        );
+        assert_eq!(
+            GridPosition::new_from_move(corner, Direction::Left, bounded),
+            None
+        );
+        assert_eq!(
+            GridPosition::new_from_move(corner, Direction::Down, bounded),
+            None
+        );
+    }
+
+    #[test]
+    fn cannot_move_into_the_shrunk_outer_rings() {
+        let mut board = Board::new((10, 8), Topology::Wrapping);
+        board.margin = 2;
+        let pos = GridPosition::new(2, 4);
+        assert_eq!(
+            GridPosition::new_from_move(pos, Direction::Left, board),
+            None
+        );
+        assert_eq!(
+            GridPosition::new_from_move(pos, Direction::Right, board),
+            Some(GridPosition::new(3, 4))
+        );
+        assert!(!board.contains(GridPosition::new(8, 4)));
+        assert!(board.contains(GridPosition::new(7, 5)));
+    }
+
+    #[test]
+    fn distance_takes_the_shorter_way_around_when_wrapping() {
+        let a = GridPosition::new(1, 1);
+        let b = GridPosition::new(9, 7);
+        assert_eq!(a.distance(b, Board::new((10, 8), Topology::Wrapping)), 4);
+        assert_eq!(a.distance(b, Board::new((10, 8), Topology::Bounded)), 14);
+    }
+}
@@ -0,0 +1,491 @@
+use ggez::{graphics, Context};
+
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::config::{config, LEVELS_DIR};
+use crate::grid::{Board, GridPosition, Topology};
+use crate::rendering::{draw_cell, scale_rect, CellBatch};
+use crate::snake::{Skin, Snake};
+use crate::theme::Theme;
+
+// 通り抜けられない障害物
+#[derive(Clone, Copy, Debug)]
+pub struct Obstacle {
+    pub pos: GridPosition,
+}
+
+impl Obstacle {
+    pub fn new(pos: GridPosition) -> Self {
+        Obstacle { pos }
+    }
+
+    // 障害物を描画する
+    pub fn draw(&self, batch: &mut CellBatch, theme: &Theme, outlined: bool) {
+        draw_cell(batch, self.pos.into(), theme.wall, outlined);
+    }
+}
+
+// 2つで1組のワープマス。片方に入るともう片方から同じ方向に出る
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Portal {
+    pub a: GridPosition,
+    pub b: GridPosition,
+}
+
+impl Portal {
+    // 組ごとの色(ステージ内での番号で選ぶ)
+    const COLORS: [[f32; 4]; 3] = [
+        [0.0, 0.9, 0.9, 1.0],
+        [1.0, 0.5, 1.0, 1.0],
+        [0.6, 1.0, 0.3, 1.0],
+    ];
+
+    // posがこの組のどちらかならもう片方のマス
+    pub fn exit(&self, pos: GridPosition) -> Option<GridPosition> {
+        if pos == self.a {
+            Some(self.b)
+        } else if pos == self.b {
+            Some(self.a)
+        } else {
+            None
+        }
+    }
+
+    // posがportalsのどれかならワープ先、そうでなければposのまま
+    pub fn warp(portals: &[Portal], pos: GridPosition) -> GridPosition {
+        portals.iter().find_map(|p| p.exit(pos)).unwrap_or(pos)
+    }
+
+    // ワープマスを描画する(indexは組の番号)
+    pub fn draw(&self, batch: &mut CellBatch, index: usize, outlined: bool) {
+        let color = Portal::COLORS[index % Portal::COLORS.len()];
+        for pos in [self.a, self.b] {
+            let cell: graphics::Rect = pos.into();
+            draw_cell(batch, cell, color, outlined);
+            draw_cell(batch, scale_rect(cell, 0.5), [0.0, 0.0, 0.0, 1.0], false);
+        }
+    }
+}
+
+// 組み込みの障害物の配置
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    // 障害物なし
+    Empty,
+    // 2x2の柱が4本
+    Pillars,
+    // 上下に横長の壁が2本
+    Bars,
+}
+
+impl Layout {
+    // 組み込みのステージとして選べる順番
+    const ALL: [Layout; 3] = [Layout::Empty, Layout::Pillars, Layout::Bars];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Empty => "Empty",
+            Layout::Pillars => "Pillars",
+            Layout::Bars => "Bars",
+        }
+    }
+
+    // 配置からステージを作成
+    // 蛇のスタート位置(横1/4, 高さ半分)の行には障害物を置かない
+    pub fn level(self) -> Level {
+        let (w, h) = config().grid_size;
+        let mut walls: Vec<(i16, i16)> = Vec::new();
+        match self {
+            Layout::Empty => (),
+            Layout::Pillars => {
+                for (cx, cy) in [
+                    (w / 4, h / 4),
+                    (w * 3 / 4, h / 4),
+                    (w / 4, h * 3 / 4),
+                    (w * 3 / 4, h * 3 / 4),
+                ] {
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        walls.push((cx + dx, cy + dy));
+                    }
+                }
+            }
+            Layout::Bars => {
+                for x in w / 5..w * 4 / 5 {
+                    walls.push((x, h / 4));
+                    walls.push((x, h * 3 / 4));
+                }
+            }
+        }
+        Level {
+            name: self.name().to_string(),
+            walls: walls.into_iter().map(GridPosition::from).collect(),
+            snake_start: (w / 4, h / 2).into(),
+            food_zone: Vec::new(),
+            portals: Vec::new(),
+        }
+    }
+}
+
+// ステージ(障害物、蛇のスタート位置、foodが出る範囲)
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name: String,
+    pub walls: Vec<GridPosition>,
+    // 蛇のheadの位置(右向きでスタートし、bodyはその左)
+    pub snake_start: GridPosition,
+    // foodが出るマス。空ならグリッド全体
+    pub food_zone: Vec<GridPosition>,
+    // ワープマスの組
+    pub portals: Vec<Portal>,
+}
+
+impl Level {
+    // テキストのマップからステージを作成
+    // '#': 壁, 'S': 蛇のスタート位置, 'F': foodが出るマス,
+    // '0'〜'9': ワープマス(同じ数字が2つで1組), それ以外: 空きマス
+    pub fn parse(name: &str, text: &str) -> Result<Level, String> {
+        let grid = config().grid_size;
+        let mut walls = Vec::new();
+        let mut snake_start = None;
+        let mut food_zone = Vec::new();
+        // 数字ごとのワープマスの位置
+        let mut portal_ends: Vec<Vec<GridPosition>> = vec![Vec::new(); 10];
+        for (y, line) in text.lines().enumerate() {
+            if y >= grid.1 as usize {
+                return Err(format!("more than {} rows", grid.1));
+            }
+            for (x, c) in line.chars().enumerate() {
+                if x >= grid.0 as usize {
+                    return Err(format!("row {} is longer than {}", y + 1, grid.0));
+                }
+                let pos = GridPosition::new(x as i16, y as i16);
+                match c {
+                    '#' => walls.push(pos),
+                    'S' => snake_start = Some(pos),
+                    'F' => food_zone.push(pos),
+                    '0'..='9' => portal_ends[c as usize - '0' as usize].push(pos),
+                    _ => (),
+                }
+            }
+        }
+        let mut portals = Vec::new();
+        for (digit, ends) in portal_ends.iter().enumerate() {
+            match ends[..] {
+                [] => (),
+                [a, b] => portals.push(Portal { a, b }),
+                _ => return Err(format!("portal '{}' must appear exactly twice", digit)),
+            }
+        }
+        let snake_start = snake_start.unwrap_or((grid.0 / 4, grid.1 / 2).into());
+        // スタート時のbodyがグリッド内の空きマスに収まるか
+        let body = GridPosition::new(snake_start.x - 1, snake_start.y);
+        let on_portal = portals.iter().any(|p| p.exit(body).is_some());
+        if body.x < 0 || walls.contains(&snake_start) || walls.contains(&body) || on_portal {
+            return Err("snake start is blocked".to_string());
+        }
+        Ok(Level {
+            name: name.to_string(),
+            walls,
+            snake_start,
+            food_zone,
+            portals,
+        })
+    }
+
+    // posがワープマスならtrue
+    pub fn has_portal(&self, pos: GridPosition) -> bool {
+        self.portals.iter().any(|p| p.exit(pos).is_some())
+    }
+
+    // parseで読み込めるテキストのマップに変換
+    pub fn to_text(&self) -> String {
+        let grid = config().grid_size;
+        let mut text = String::new();
+        for y in 0..grid.1 {
+            for x in 0..grid.0 {
+                let pos = GridPosition::new(x, y);
+                let portal = self.portals.iter().position(|p| p.exit(pos).is_some());
+                text.push(if pos == self.snake_start {
+                    'S'
+                } else if let Some(index) = portal {
+                    char::from_digit(index as u32, 10).unwrap_or('.')
+                } else if self.walls.contains(&pos) {
+                    '#'
+                } else if self.food_zone.contains(&pos) {
+                    'F'
+                } else {
+                    '.'
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // 組み込みのステージ(ファイルを読まないのでContextがなくても作れる)
+    pub fn builtin() -> Vec<Level> {
+        Layout::ALL.iter().map(|l| l.level()).collect()
+    }
+
+    // 組み込みのステージと、リソースの/levels/*.txtを全て読み込む
+    // 読み込めないファイルはエラーを表示して飛ばす
+    pub fn load_all(ctx: &Context) -> Vec<Level> {
+        let mut levels = Level::builtin();
+        let mut paths: Vec<PathBuf> = match ctx.fs.read_dir(LEVELS_DIR) {
+            Ok(paths) => paths
+                .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+                .collect(),
+            Err(_) => return levels,
+        };
+        paths.sort();
+        for path in paths {
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut text = String::new();
+            let result = ctx
+                .fs
+                .open(&path)
+                .and_then(|mut file| Ok(file.read_to_string(&mut text)?))
+                .map_err(|e| e.to_string())
+                .and_then(|_| Level::parse(&name, &text));
+            match result {
+                Ok(level) => levels.push(level),
+                Err(e) => eprintln!("Could not load level {:?}: {}", path, e),
+            }
+        }
+        levels
+    }
+}
+
+// エディタで使うブラシの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Brush {
+    // 壁を置く
+    Wall,
+    // 壁とfoodが出るマス、ワープマスを消す
+    Erase,
+    // 蛇のスタート位置を置く
+    Spawn,
+    // foodが出るマスを置く
+    FoodZone,
+}
+
+impl Brush {
+    // ツールバーに並べる順番(数字キーの1〜4に対応)
+    pub const ALL: [Brush; 4] = [Brush::Wall, Brush::Erase, Brush::Spawn, Brush::FoodZone];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Brush::Wall => "Wall",
+            Brush::Erase => "Erase",
+            Brush::Spawn => "Spawn",
+            Brush::FoodZone => "Food zone",
+        }
+    }
+}
+
+// マウスでステージを編集するエディタ
+pub struct Editor {
+    // 編集中のステージ
+    pub level: Level,
+    // 選択中のブラシ
+    pub brush: Brush,
+    // マウスカーソルがあるマス
+    pub hovered: Option<GridPosition>,
+    // 左ボタンを押しながら動かしている間はtrue
+    pub painting: bool,
+    // ツールバーに表示するメッセージ(保存結果など)
+    pub message: String,
+}
+
+impl Editor {
+    // ツールバーの高さと、ブラシ1つ分の幅(ピクセル)
+    const TOOLBAR_HEIGHT: f32 = 36.0;
+    const TOOL_WIDTH: f32 = 160.0;
+
+    pub fn new(level: Level) -> Self {
+        Editor {
+            level,
+            brush: Brush::Wall,
+            hovered: None,
+            painting: false,
+            message: String::new(),
+        }
+    }
+
+    // ツールバー上のブラシのボタンの範囲
+    fn tool_rect(index: usize) -> graphics::Rect {
+        graphics::Rect::new(
+            index as f32 * Self::TOOL_WIDTH,
+            0.0,
+            Self::TOOL_WIDTH,
+            Self::TOOLBAR_HEIGHT,
+        )
+    }
+
+    // 画面上の(x, y)がブラシのボタンの上ならそのブラシを選んでtrue
+    pub fn click_toolbar(&mut self, x: f32, y: f32) -> bool {
+        for (i, brush) in Brush::ALL.iter().enumerate() {
+            if Self::tool_rect(i).contains([x, y]) {
+                self.brush = *brush;
+                return true;
+            }
+        }
+        false
+    }
+
+    // 選択中のブラシをposのマスに使う
+    pub fn paint(&mut self, pos: GridPosition) {
+        let level = &mut self.level;
+        let body = GridPosition::new(level.snake_start.x - 1, level.snake_start.y);
+        match self.brush {
+            Brush::Wall => {
+                // 蛇のスタート位置とワープマスには置けない
+                if pos != level.snake_start
+                    && pos != body
+                    && !level.walls.contains(&pos)
+                    && !level.has_portal(pos)
+                {
+                    level.food_zone.retain(|p| *p != pos);
+                    level.walls.push(pos);
+                }
+            }
+            Brush::Erase => {
+                level.walls.retain(|p| *p != pos);
+                level.food_zone.retain(|p| *p != pos);
+                // ワープマスは組ごと消す
+                level.portals.retain(|p| p.exit(pos).is_none());
+            }
+            Brush::Spawn => {
+                // bodyを置く左のマスも空いている必要がある
+                let new_body = GridPosition::new(pos.x - 1, pos.y);
+                if new_body.x >= 0
+                    && !level.walls.contains(&pos)
+                    && !level.walls.contains(&new_body)
+                    && !level.has_portal(pos)
+                    && !level.has_portal(new_body)
+                {
+                    level.snake_start = pos;
+                }
+            }
+            Brush::FoodZone => {
+                if !level.walls.contains(&pos)
+                    && !level.food_zone.contains(&pos)
+                    && !level.has_portal(pos)
+                {
+                    level.food_zone.push(pos);
+                }
+            }
+        }
+    }
+
+    // 編集中のステージ、カーソル、ツールバーを描画
+    // マスの四角形はbatchに溜めて、文字より先にまとめて描画する
+    pub fn draw(&self, canvas: &mut graphics::Canvas, batch: &mut CellBatch, theme: &Theme) {
+        let screen = config().screen_size();
+        batch.clear();
+        for pos in &self.level.food_zone {
+            draw_cell(batch, (*pos).into(), [0.0, 0.0, 0.4, 1.0], false);
+        }
+        for pos in &self.level.walls {
+            Obstacle::new(*pos).draw(batch, theme, false);
+        }
+        for (i, portal) in self.level.portals.iter().enumerate() {
+            portal.draw(batch, i, false);
+        }
+        let start = self.level.snake_start;
+        Snake::new(start, Board::from_config(Topology::Wrapping)).draw(
+            batch,
+            theme,
+            Skin::Solid,
+            false,
+            1.0,
+        );
+        // カーソルがあるマスを半透明の白で強調
+        if let Some(pos) = self.hovered {
+            draw_cell(batch, pos.into(), [1.0, 1.0, 1.0, 0.3], false);
+        }
+        // ツールバー
+        batch.push(
+            graphics::Rect::new(0.0, 0.0, screen.0, Self::TOOLBAR_HEIGHT),
+            [0.1, 0.1, 0.1, 0.8],
+        );
+        if let Some(i) = Brush::ALL.iter().position(|brush| *brush == self.brush) {
+            draw_cell(batch, Self::tool_rect(i), [0.3, 0.3, 0.6, 1.0], false);
+        }
+        batch.draw(canvas);
+        for (i, brush) in Brush::ALL.iter().enumerate() {
+            let rect = Self::tool_rect(i);
+            let mut text = graphics::Text::new(format!("[{}] {}", i + 1, brush.name()));
+            text.set_scale(24.0);
+            canvas.draw(
+                &text,
+                graphics::DrawParam::new()
+                    .dest([rect.x + 8.0, rect.y + 6.0])
+                    .color(graphics::Color::WHITE),
+            );
+        }
+        let mut text =
+            graphics::Text::new(format!("S: save  Enter: back to menu  {}", self.message));
+        text.set_scale(24.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([Brush::ALL.len() as f32 * Self::TOOL_WIDTH + 16.0, 6.0])
+                .color(graphics::Color::WHITE),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_walls_start_food_and_portals() {
+        let level = Level::parse("test", "#..1\n.S.F\n1..#\n").unwrap();
+        assert_eq!(level.name, "test");
+        assert_eq!(
+            level.walls,
+            vec![GridPosition::new(0, 0), GridPosition::new(3, 2)]
+        );
+        assert_eq!(level.snake_start, GridPosition::new(1, 1));
+        assert_eq!(level.food_zone, vec![GridPosition::new(3, 1)]);
+        assert_eq!(
+            level.portals,
+            vec![Portal {
+                a: GridPosition::new(3, 0),
+                b: GridPosition::new(0, 2),
+            }]
+        );
+    }
+
+    #[test]
+    fn to_text_reads_back_the_same_level() {
+        let level = Level::parse("test", "#..1\n.S.F\n1..#\n").unwrap();
+        let text = level.to_text();
+        let grid = config().grid_size;
+        assert_eq!(text.lines().count(), grid.1 as usize);
+        let read = Level::parse("test", &text).unwrap();
+        assert_eq!(read.walls, level.walls);
+        assert_eq!(read.snake_start, level.snake_start);
+        assert_eq!(read.food_zone, level.food_zone);
+        assert_eq!(read.portals, level.portals);
+    }
+
+    #[test]
+    fn rejects_broken_maps() {
+        // ワープマスが1つだけ
+        assert!(Level::parse("test", "..1.\n").is_err());
+        // スタート時のbodyがグリッドの外
+        assert!(Level::parse("test", "S...\n").is_err());
+        // スタート位置のすぐ左が壁
+        assert!(Level::parse("test", ".#S.\n").is_err());
+        let grid = config().grid_size;
+        assert!(Level::parse("test", &".".repeat(grid.0 as usize + 1)).is_err());
+        assert!(Level::parse("test", &".\n".repeat(grid.1 as usize + 1)).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+// スネークゲーム本体。main.rsはこれを使ってウィンドウを作り、起動するだけ
+
+// 効果音とBGM
+pub mod audio;
+// --benchで測るtickと描画の速さ
+pub mod bench;
+// 1回のゲームで挑戦する目標
+pub mod challenge;
+// 友達と同じ設定とシードで遊ぶためのチャレンジコード
+pub mod challenge_code;
+// 定数と、起動時に読み込むconfig.tomlの設定
+pub mod config;
+// 蛇の操作方法(人・コンピュータ・リプレイ・ネットワーク)
+pub mod controller;
+// F3で表示するデバッグ用の情報
+pub mod debug;
+// ゲーム中のできごと
+pub mod event;
+// DiscordのRich Presenceに今のゲームを表示する
+pub mod discord;
+// foodとパワーアップ
+pub mod food;
+// game内の全ての状態と、シーンごとのupdate/draw/入力の処理
+pub mod game_state;
+// グリッド上の位置と方向
+pub mod grid;
+// ステージ(壁・ワープマス)とステージエディタ
+pub mod level;
+// 大きな盤面の全体を隅に小さく表示するミニマップ
+pub mod minimap;
+// ゲームのモードとそのルール
+pub mod mode;
+// オンラインのランキングとネットワーク対戦の通信
+pub mod net;
+// foodを食べた時などに飛び散るパーティクル
+pub mod particles;
+// 盤面のマスの上で安全な最短経路を探す
+pub mod pathfinding;
+// 画面全体にかけるシェーダーの効果
+pub mod post_process;
+// プレイ中の最後の数秒をGIFに書き出す
+pub mod recorder;
+// ハイスコア・通算成績・実績の記録
+pub mod records;
+// シーンをまたいで使う描画の関数
+pub mod rendering;
+// リプレイとゲームの途中のセーブデータ
+pub mod replay;
+// ゲームのルールを変えるスクリプト
+pub mod script;
+// 保存される設定(音量・操作の割り当て)と難易度
+pub mod settings;
+// ウィンドウなしでゲームを進めるSimulation
+pub mod simulation;
+// 蛇とfoodのテクスチャ
+pub mod sprites;
+// スネーク
+pub mod snake;
+// 1行の文字の入力欄
+pub mod text_input;
+// 盤面とHUDの色のテーマ
+pub mod theme;
+// 初めて遊ぶ時のチュートリアル
+pub mod tutorial;
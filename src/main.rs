@@ -1,370 +1,271 @@
-use oorandom::Rand32;
+use directories::ProjectDirs;
 
-use ggez::{
-    event, graphics,
-    input::keyboard::{KeyCode, KeyInput},
-    Context, GameResult,
-};
-
-use std::collections::VecDeque;
-
-const GRID_SIZE: (i16, i16) = (40, 30);
-// Now we define the pixel size of each tile, which we make 32x32 pixels.
-const GRID_CELL_SIZE: (i16, i16) = (42, 42);
-
-// Next we define how large we want our actual window to be by multiplying
-// the components of our grid size by its corresponding pixel size.
-const SCREEN_SIZE: (f32, f32) = (
-    GRID_SIZE.0 as f32 * GRID_CELL_SIZE.0 as f32,
-    GRID_SIZE.1 as f32 * GRID_CELL_SIZE.1 as f32,
-);
-
-// 1秒間にupdateが呼ばれる回数
-const DESIRED_FPS: u32 = 8;
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-struct GridPosition {
-    x: i16,
-    y: i16,
-}
-
-impl GridPosition {
-    // x, yあわせてGridPositionとする
-    pub fn new(x: i16, y: i16) -> Self {
-        GridPosition { x, y }
-    }
-
-    // グリッド範囲内のランダムな位置を取得
-    pub fn random(rng: &mut Rand32, max_x: i16, max_y: i16) -> Self {
-        // GridPositionの型に合わせる
-        (
-            rng.rand_range(0..(max_x as u32)) as i16,
-            rng.rand_range(0..(max_y as u32)) as i16,
-        )
-            .into()
-    }
+use ggez::winit::dpi::LogicalSize;
+use ggez::{event, GameResult};
 
-    // 受け取ったDirectionをGridPositionの座標に変換
-    pub fn new_from_move(pos: GridPosition, dir: Direction) -> Self {
-        match dir {
-            Direction::Up => GridPosition::new(pos.x, (pos.y - 1).rem_euclid(GRID_SIZE.1)),
-            Direction::Down => GridPosition::new(pos.x, (pos.y + 1).rem_euclid(GRID_SIZE.1)),
-            Direction::Left => GridPosition::new((pos.x - 1).rem_euclid(GRID_SIZE.0), pos.y),
-            Direction::Right => GridPosition::new((pos.x + 1).rem_euclid(GRID_SIZE.0), pos.y),
-        }
-    }
-}
-
-/// We implement the `From` trait, which in this case allows us to convert easily between
-/// a `GridPosition` and a ggez `graphics::Rect` which fills that grid cell.
-/// Now we can just call `.into()` on a `GridPosition` where we want a
-/// `Rect` that represents that grid cell.
-impl From<GridPosition> for graphics::Rect {
-    fn from(pos: GridPosition) -> Self {
-        graphics::Rect::new_i32(
-            pos.x as i32 * GRID_CELL_SIZE.0 as i32,
-            pos.y as i32 * GRID_CELL_SIZE.1 as i32,
-            GRID_CELL_SIZE.0 as i32,
-            GRID_CELL_SIZE.1 as i32,
-        )
-    }
-}
+use std::path::{Path, PathBuf};
 
-/// And here we implement `From` again to allow us to easily convert between
-/// `(i16, i16)` and a `GridPosition`.
-impl From<(i16, i16)> for GridPosition {
-    fn from(pos: (i16, i16)) -> Self {
-        GridPosition { x: pos.0, y: pos.1 }
-    }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+use ggez_tutorial::audio::AudioManager;
+use ggez_tutorial::bench::BenchReport;
+use ggez_tutorial::config::{
+    config, GridConfig, CONFIG_FILE, DEFAULT_AUTHOR, DEFAULT_BENCH_FRAMES, DEFAULT_BENCH_TICKS,
+    GRID_CONFIG,
+};
+use ggez_tutorial::discord::Presence;
+use ggez_tutorial::game_state::GameState;
+use ggez_tutorial::level::Level;
+use ggez_tutorial::net::NetSession;
+use ggez_tutorial::post_process::PostProcess;
+use ggez_tutorial::records::{Achievements, HighScores, Stats};
+use ggez_tutorial::rendering::BackgroundImage;
+use ggez_tutorial::replay::Replay;
+use ggez_tutorial::script::Script;
+use ggez_tutorial::settings::{Difficulty, Settings};
+use ggez_tutorial::simulation::Simulation;
+use ggez_tutorial::sprites::Sprites;
+
+// コマンドライン引数
+struct CliArgs {
+    // ウィンドウタイトル(--title)。指定がなければconfig.tomlのタイトル
+    title: Option<String>,
+    // 作者名(--author)
+    author: String,
+    // 起動してすぐデモを始めるか(--demo)
+    demo: bool,
+    // グリッドのマス数(--grid 60x40)
+    grid: Option<(i16, i16)>,
+    // Normalの難易度の1秒間のupdateの回数(--fps 12)
+    fps: Option<u32>,
+    // 乱数のシード(--seed 42)
+    seed: Option<u64>,
+    // 起動してすぐ再生するリプレイファイル(--replay replay.toml)
+    replay: Option<PathBuf>,
+    // ネットワーク対戦で待ち受けるポート(--host 7777)
+    host: Option<u16>,
+    // ネットワーク対戦で接続するホスト(--connect 192.168.0.2:7777)
+    connect: Option<String>,
+    // ネットワーク対戦を観戦するホスト(--spectate 192.168.0.2:7777)
+    spectate: Option<String>,
+    // ウィンドウを作らずに自動操作で進めるtick数(--headless 1000)
+    headless: Option<u32>,
+    // tickと描画の速さを測って終わるか(--bench)
+    bench: bool,
+    // --benchでSimulationを進めるtick数(--bench-ticks 2で200万tick)
+    bench_ticks: u64,
+    // --benchで描画するフレーム数(--bench-frames 600)
+    bench_frames: u32,
 }
 
-impl Direction {
-    // 受け取ったDirectionを逆に変換
-    pub fn inverse(self) -> Self {
-        match self {
-            Direction::Up => Direction::Down,
-            Direction::Down => Direction::Up,
-            Direction::Left => Direction::Right,
-            Direction::Right => Direction::Left,
+impl CliArgs {
+    // 引数を解析し、指定がない・空文字の場合はデフォルト値を使う
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Self {
+        let mut cli = CliArgs {
+            title: None,
+            author: DEFAULT_AUTHOR.to_string(),
+            demo: false,
+            grid: None,
+            fps: None,
+            seed: None,
+            replay: None,
+            host: None,
+            connect: None,
+            spectate: None,
+            headless: None,
+            bench: false,
+            bench_ticks: DEFAULT_BENCH_TICKS,
+            bench_frames: DEFAULT_BENCH_FRAMES,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--title" => {
+                    if let Some(title) = Self::non_empty(args.next()) {
+                        cli.title = Some(title);
+                    }
+                }
+                "--author" => {
+                    if let Some(author) = Self::non_empty(args.next()) {
+                        cli.author = author;
+                    }
+                }
+                "--demo" => cli.demo = true,
+                "--headless" => cli.headless = Self::value(&arg, args.next(), |v| v.parse().ok()),
+                "--bench" => cli.bench = true,
+                "--bench-ticks" => {
+                    if let Some(millions) =
+                        Self::value(&arg, args.next(), |v| v.parse::<f64>().ok())
+                    {
+                        cli.bench_ticks = (millions * 1_000_000.0) as u64;
+                    }
+                }
+                "--bench-frames" => {
+                    if let Some(frames) = Self::value(&arg, args.next(), |v| v.parse().ok()) {
+                        cli.bench_frames = frames;
+                    }
+                }
+                "--grid" => cli.grid = Self::value(&arg, args.next(), Self::parse_grid),
+                "--fps" => cli.fps = Self::value(&arg, args.next(), |v| v.parse().ok()),
+                "--seed" => cli.seed = Self::value(&arg, args.next(), |v| v.parse().ok()),
+                "--replay" => cli.replay = Self::non_empty(args.next()).map(PathBuf::from),
+                "--host" => cli.host = Self::value(&arg, args.next(), |v| v.parse().ok()),
+                "--connect" => {
+                    cli.connect = Self::value(&arg, args.next(), |v| Some(v.to_string()))
+                }
+                "--spectate" => {
+                    cli.spectate = Self::value(&arg, args.next(), |v| Some(v.to_string()))
+                }
+                _ => eprintln!("Unknown argument: {}", arg),
+            }
         }
+        cli
     }
 
-    // keycodeを受け取ったらSomeを返す
-    pub fn from_keycode(key: KeyCode) -> Option<Direction> {
-        match key {
-            KeyCode::Up => Some(Direction::Up),
-            KeyCode::Down => Some(Direction::Down),
-            KeyCode::Left => Some(Direction::Left),
-            KeyCode::Right => Some(Direction::Right),
-            _ => None,
+    // オプションの値をparseで変換する。変換できなければエラーを表示してNone
+    fn value<T>(name: &str, value: Option<String>, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+        let value = Self::non_empty(value);
+        let parsed = value.as_deref().and_then(parse);
+        if parsed.is_none() {
+            eprintln!("Invalid value for {}: {}", name, value.unwrap_or_default());
         }
+        parsed
     }
-}
-
-/// This is mostly just a semantic abstraction over a `GridPosition` to represent
-/// a segment of the snake. It could be useful to, say, have each segment contain its
-/// own color or something similar. This is an exercise left up to the reader ;)
-#[derive(Clone, Copy, Debug)]
-struct Segment {
-    pos: GridPosition,
-}
-
-impl Segment {
-    pub fn new(pos: GridPosition) -> Self {
-        Segment { pos }
-    }
-}
 
-struct Food {
-    pos: GridPosition,
-}
-
-impl Food {
-    pub fn new(pos: GridPosition) -> Self {
-        Food { pos }
+    // 「60x40」の形式のグリッドのマス数
+    fn parse_grid(value: &str) -> Option<(i16, i16)> {
+        let (w, h) = value.split_once('x')?;
+        Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
     }
 
-    // foodを描画する
-    fn draw(&self, canvas: &mut graphics::Canvas) {
-        // ブルー
-        let color = [0.0, 0.0, 1.0, 1.0];
-
-        // 四角形で描画
-        canvas.draw(
-            &graphics::Quad,
-            graphics::DrawParam::new()
-                .dest_rect(self.pos.into())
-                .color(color),
-        );
+    // 前後の空白を取り除き、空でなければSomeを返す
+    fn non_empty(value: Option<String>) -> Option<String> {
+        value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
     }
 }
 
-// 食べたもの(自分かえさか)
-#[derive(Clone, Copy, Debug)]
-enum Ate {
-    Itself,
-    Food,
-}
-
-// スネーク
-struct Snake {
-    // 頭
-    head: Segment,
-    // 現在の方向
-    dir: Direction,
-    // 体
-    body: VecDeque<Segment>,
-    // 最後になんの餌を食ったか
-    ate: Option<Ate>,
-    // 最後の更新された方向
-    last_update_dir: Direction,
-    // 次のupdateで更新される方向(キー入力を保持)
-    next_dir: Option<Direction>,
-}
-
-impl Snake {
-    pub fn new(pos: GridPosition) -> Self {
-        let mut body = VecDeque::new();
-        // bosy要素を末尾に追加
-        body.push_back(Segment::new((pos.x - 1, pos.y).into()));
-        Snake {
-            head: Segment::new(pos),
-            dir: Direction::Right,
-            last_update_dir: Direction::Right,
-            body,
-            ate: None,
-            next_dir: None,
-        }
+fn main() -> GameResult {
+    // 最初の要素(実行ファイル名)を飛ばして引数を解析
+    let cli = CliArgs::parse(std::env::args().skip(1));
+
+    // ウィンドウの大きさを決めるためにContextを作る前にconfig.tomlを読み込む
+    // 置き場所はggezのユーザー設定ディレクトリ(settings.tomlと同じ場所)
+    let mut grid_config = match ProjectDirs::from("", &cli.author, "snake") {
+        Some(dirs) => GridConfig::load(&dirs.config_dir().join(CONFIG_FILE)),
+        None => GridConfig::default(),
+    };
+    // コマンドライン引数の指定を優先する
+    if let Some(grid) = cli.grid {
+        grid_config.grid_size = grid;
     }
-
-    // ヘッドの位置にfoodがあったらtrue
-    fn eats(&self, food: &Food) -> bool {
-        self.head.pos == food.pos
+    if let Some(fps) = cli.fps {
+        grid_config.fps = fps;
     }
-
-    // ヘッドの位置がbodyのどこかと同じ位置にあったらtrue
-    fn eats_self(&self) -> bool {
-        for seg in &self.body {
-            if self.head.pos == seg.pos {
-                return true;
+    let grid_config = grid_config.clamped();
+    let title = cli.title.clone().unwrap_or(grid_config.title.clone());
+    GRID_CONFIG.get_or_init(|| grid_config);
+
+    // --headlessならウィンドウを作らずに自動操作で進めて結果を表示する
+    if let Some(ticks) = cli.headless {
+        let settings = Settings {
+            seed: cli.seed.or(config().seed),
+            ..Settings::default()
+        };
+        let mut simulation = Simulation::new(settings, Difficulty::NORMAL, 0);
+        simulation.autopilot();
+        for _ in 0..ticks {
+            if !simulation.step(None) {
+                break;
             }
         }
-        false
-    }
-
-    fn update(&mut self, food: &Food) {
-        // nextdirに新しく値が入った時
-        if self.last_update_dir == self.dir && self.next_dir.is_some() {
-            // 進行方向をnextdir, nextdirをNoneに
-            self.dir = self.next_dir.unwrap();
-            self.next_dir = None;
-        }
-        // 新しいヘッドの位置に今のヘッド位置 + 方向
-        let new_head_pos = GridPosition::new_from_move(self.head.pos, self.dir);
-        // ヘッド位置更新
-        let new_head = Segment::new(new_head_pos);
-        // bodyの先頭にヘッドを追加
-        self.body.push_front(self.head);
-        // headにnew_headを格納
-        self.head = new_head;
-        // 何か食べているかの判定
-        if self.eats_self() {
-            self.ate = Some(Ate::Itself);
-        } else if self.eats(food) {
-            self.ate = Some(Ate::Food);
-        } else {
-            self.ate = None;
-        }
-        // 何も食べていない場合は末尾のbodyを削除
-        if self.ate.is_none() {
-            self.body.pop_back();
-        }
-        // last_update_dirにdirを格納
-        self.last_update_dir = self.dir;
-    }
-
-    // スネークを描画
-    fn draw(&self, canvas: &mut graphics::Canvas) {
-        for seg in &self.body {
-            // body分描画
-            canvas.draw(
-                &graphics::Quad,
-                graphics::DrawParam::new()
-                    .dest_rect(seg.pos.into())
-                    .color([0.3, 0.3, 0.0, 1.0]),
-            );
-        }
-        // head描画
-        canvas.draw(
-            &graphics::Quad,
-            graphics::DrawParam::new()
-                .dest_rect(self.head.pos.into())
-                .color([1.0, 0.5, 0.0, 1.0]),
+        println!(
+            "Ticks: {}  Score: {}  Length: {}  Seed: {}",
+            simulation.tick(),
+            simulation.snake().score,
+            simulation.snake().body.len() + 1,
+            simulation.seed()
         );
+        return Ok(());
     }
-}
-
-// game内の全ての状態を管理
-struct GameState {
-    snake: Snake,
-    food: Food,
-    gameover: bool,
-    rng: Rand32,
-}
-
-// newでGameStateのインスタンス(ゲームの初期状態)を作成
-impl GameState {
-    pub fn new() -> Self {
-        // GRID_SIZE -> (30, 20)
-        // 画面の横4/1, 高さ半分のところからスタート
-        let snake_pos = (GRID_SIZE.0 / 4, GRID_SIZE.1 / 2).into();
-        // u8型の配列の値それぞれにランダムな値を格納しu64に変換
-        let mut seed: [u8; 8] = [0; 8];
-        getrandom::getrandom(&mut seed[..]).expect("Could not create RNG seed");
-        let mut rng = Rand32::new(u64::from_ne_bytes(seed));
-        // Then we choose a random place to put our piece of food using the helper we made
-        // earlier.
-        let food_pos = GridPosition::random(&mut rng, GRID_SIZE.0, GRID_SIZE.1);
-
-        GameState {
-            snake: Snake::new(snake_pos),
-            food: Food::new(food_pos),
-            gameover: false,
-            rng,
-        }
-    }
-}
-
-// EventHandlerトレイトで状態の更新を行う(update, draw)
-impl event::EventHandler<ggez::GameError> for GameState {
-    // drawよりも先に呼ばれる
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        while ctx.time.check_update_time(DESIRED_FPS) {
-            // ゲームが続いていたら
-            if !self.gameover {
-                // ランダムフードの位置に蛇がいけば
-                self.snake.update(&self.food);
-                // 蛇が何か食った場合
-                if let Some(ate) = self.snake.ate {
-                    // If it did, we want to know what it ate.
-                    match ate {
-                        // foodだったら、新しくfoodをランダムな位置に追加
-                        Ate::Food => {
-                            let new_food_pos =
-                                GridPosition::random(&mut self.rng, GRID_SIZE.0, GRID_SIZE.1);
-                            self.food.pos = new_food_pos;
-                        }
-                        // bodyだったらgameover
-                        Ate::Itself => {
-                            self.gameover = true;
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(())
-    }
-
-    /// 描画
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        // canvasインスタンスを作成、描画
-        let mut canvas =
-            graphics::Canvas::from_frame(ctx, graphics::Color::from([0.0, 0.0, 0.0, 0.0]));
-
-        // snakeとfoodを描画
-        self.snake.draw(&mut canvas);
-        self.food.draw(&mut canvas);
-
-        // 実際に描画
-        canvas.finish(ctx)?;
-
-        // 次のupdateまで他スレッドも実行
-        ggez::timer::yield_now();
 
-        Ok(())
+    // --benchならまずウィンドウなしでtickの速さを測る
+    let mut bench = BenchReport::default();
+    if cli.bench {
+        bench.run_simulation(cli.seed.or(config().seed).unwrap_or(0), cli.bench_ticks);
     }
 
-    /// キーが押されたタイミングで呼ばれる
-    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
-        // key入力を受け取る
-        if let Some(dir) = input.keycode.and_then(Direction::from_keycode) {
-            // If it succeeds, we check if a new direction has already been set
-            // and make sure the new direction is different then `snake.dir`
-            if self.snake.dir != self.snake.last_update_dir && dir.inverse() != self.snake.dir {
-                self.snake.next_dir = Some(dir);
-            } else if dir.inverse() != self.snake.last_update_dir {
-                // If no new direction has been set and the direction is not the inverse
-                // of the `last_update_dir`, then set the snake's new direction to be the
-                // direction the user pressed.
-                self.snake.dir = dir;
-            }
-        }
-        Ok(())
-    }
-}
+    let screen = config().screen_size();
 
-fn main() -> GameResult {
     // Here we use a ContextBuilder to setup metadata about our game. First the title and author
-    let (ctx, events_loop) = ggez::ContextBuilder::new("snake", "Gray Olson")
+    let mut cb = ggez::ContextBuilder::new("snake", &cli.author);
+    // cargo runで起動した場合はプロジェクト直下のresourcesも読み込む
+    if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
+        cb = cb.add_resource_path(Path::new(&manifest_dir).join("resources"));
+    }
+    let (mut ctx, events_loop) = cb
         // Next we set up the window. This title will be displayed in the title bar of the window.
-        .window_setup(ggez::conf::WindowSetup::default().title("Snake!"))
-        // Now we get to set the size of the window, which we use our SCREEN_SIZE constant from earlier to help with
-        .window_mode(ggez::conf::WindowMode::default().dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1))
+        .window_setup(ggez::conf::WindowSetup::default().title(&title))
+        // Now we get to set the size of the window, which we use our config from earlier to help with
+        // ウィンドウの大きさは変えられる(盤面は縦横比を保って拡大・縮小する)
+        // 高DPIのディスプレイでも同じ大きさに見えるように論理ピクセルで指定する
+        // (拡大率の違うディスプレイに移した時も論理ピクセルの大きさを保つ)
+        .window_mode(ggez::conf::WindowMode {
+            logical_size: Some(LogicalSize::new(screen.0, screen.1)),
+            ..ggez::conf::WindowMode::default()
+                .resizable(true)
+                .resize_on_scale_factor_change(true)
+                // --benchでは見えないウィンドウに描画する
+                .visible(!cli.bench)
+        })
         // And finally we attempt to build the context and create the window. If it fails, we panic with the message
         // "Failed to build ggez context"
         .build()?;
 
     // Next we create a new instance of our GameState struct, which implements EventHandler
-    let state = GameState::new();
+    let mut settings = Settings::load(&ctx);
+    settings.seed = cli.seed.or(config().seed);
+    // 前回全画面表示で終わっていたら全画面で始める
+    if settings.fullscreen {
+        ctx.gfx.set_fullscreen(settings.fullscreen_type())?;
+    }
+    let mut state = GameState::new(
+        settings,
+        HighScores::load(ctx.fs.user_data_dir()),
+        Difficulty::NORMAL,
+        Level::load_all(&ctx),
+        0,
+        1,
+        false,
+    );
+    state.audio = AudioManager::load(&ctx, &state.settings);
+    state.presence = Presence::start();
+    state.sprites = Sprites::load(&ctx);
+    state.background_image = BackgroundImage::load(&ctx);
+    state.post_process = PostProcess::load(&ctx);
+    state.stats = Stats::load(ctx.fs.user_data_dir());
+    state.achievements = Achievements::load(ctx.fs.user_data_dir());
+    state.scripts = Script::load_all(&ctx);
+    // --benchなら描画の速さも測って結果を表示して終わる
+    if cli.bench {
+        bench.run_renderer(&mut ctx, &mut state, cli.bench_frames)?;
+        for line in bench.lines() {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    if let Some(path) = &cli.replay {
+        match Replay::load(path) {
+            Ok(replay) => state.start_playback(replay),
+            Err(e) => eprintln!("Could not load replay {}: {}", path.display(), e),
+        }
+    } else if let Some(port) = cli.host {
+        state.start_network(NetSession::host(port));
+    } else if let Some(addr) = cli.connect {
+        state.start_network(Ok(NetSession::connect(addr)));
+    } else if let Some(addr) = cli.spectate {
+        state.start_network(Ok(NetSession::spectate(addr)));
+    } else if cli.demo {
+        state.start_demo();
+    }
     // And finally we actually run our game, passing in our context and state.
     event::run(ctx, events_loop, state)
 }
@@ -0,0 +1,89 @@
+use ggez::{graphics, Context};
+
+use crate::config::{
+    config, MINIMAP_BACKGROUND, MINIMAP_MARGIN, MINIMAP_MIN_CELL_PIXELS, MINIMAP_SIZE,
+    MINIMAP_VIEW_COLOR,
+};
+use crate::controller::BoardView;
+use crate::grid::{Board, Cell, GridPosition};
+use crate::rendering::{screen_coordinates, CellBatch};
+use crate::theme::Theme;
+
+// 画面の右下に盤面全体を小さく描くミニマップ
+// 1マスを1x1の四角形として盤面とは別のbatchに溜め、拡大して1回で描画する
+pub struct Minimap {
+    batch: CellBatch,
+}
+
+impl Minimap {
+    pub fn new(ctx: &Context) -> Self {
+        Minimap {
+            batch: CellBatch::new(ctx),
+        }
+    }
+
+    // ウィンドウの大きさがwindowの時にboardのミニマップを表示するか
+    // (カメラが盤面の一部だけを映す時と、盤面のマスが画面上でMINIMAP_MIN_CELL_PIXELSより小さく描かれる時)
+    pub fn is_needed(window: (f32, f32), board: Board) -> bool {
+        if board.has_camera() {
+            return true;
+        }
+        let scale = window.0 / screen_coordinates(window).w;
+        let cell = config().cell_size;
+        (cell.0.min(cell.1) as f32) * scale < MINIMAP_MIN_CELL_PIXELS
+    }
+
+    // 盤面のマスの中身から、壁・food・蛇を1マスずつ描く
+    // viewはカメラが映している盤面の範囲(ピクセル)で、カメラを使う時だけ明るく塗る
+    pub fn draw(
+        &mut self,
+        canvas: &mut graphics::Canvas,
+        theme: &Theme,
+        view: graphics::Rect,
+        board: &BoardView,
+    ) {
+        let grid = (board.board.width, board.board.height);
+        let screen = config().screen_size();
+        let scale =
+            (screen.0 * MINIMAP_SIZE / grid.0 as f32).min(screen.1 * MINIMAP_SIZE / grid.1 as f32);
+        let dest = [
+            screen.0 - grid.0 as f32 * scale - MINIMAP_MARGIN,
+            screen.1 - grid.1 as f32 * scale - MINIMAP_MARGIN,
+        ];
+        self.batch.clear();
+        self.batch.push(
+            graphics::Rect::new(0.0, 0.0, grid.0 as f32, grid.1 as f32),
+            MINIMAP_BACKGROUND,
+        );
+        if board.board.has_camera() {
+            let cell = config().cell_size;
+            self.batch.push(
+                graphics::Rect::new(
+                    view.x / cell.0 as f32,
+                    view.y / cell.1 as f32,
+                    view.w / cell.0 as f32,
+                    view.h / cell.1 as f32,
+                ),
+                MINIMAP_VIEW_COLOR,
+            );
+        }
+        for (pos, cell) in board.cells.iter() {
+            let color = match cell {
+                Cell::Wall => theme.wall,
+                Cell::Food(kind) => theme.food_color(kind),
+                Cell::SnakeHead(i) => board.snakes[i].colors(theme).0,
+                Cell::SnakeBody(i) => board.snakes[i].colors(theme).1,
+                Cell::Empty | Cell::PowerUp | Cell::Portal => continue,
+            };
+            self.push(pos, color);
+        }
+        self.batch.draw_scaled(canvas, dest, scale);
+    }
+
+    fn push(&mut self, pos: GridPosition, color: [f32; 4]) {
+        self.batch.push(
+            graphics::Rect::new(pos.x as f32, pos.y as f32, 1.0, 1.0),
+            color,
+        );
+    }
+}
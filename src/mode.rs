@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    HUNGER_TICKS, SURVIVAL_SHRINK_SECONDS, TIME_ATTACK_BONUS_SECONDS, TIME_ATTACK_SECONDS,
+};
+use crate::grid::Topology;
+
+// モードごとのゲームのルール
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModeRules {
+    // 画面端を越えた時にどうなるか(Noneなら難易度で決まる)
+    pub topology: Option<Topology>,
+    // 制限時間(秒)と、foodを1個食べるごとに増える秒数
+    pub time_limit: Option<f32>,
+    pub time_bonus: f32,
+    // 盤面が外側から1周縮む間隔(秒)
+    pub shrink_interval: Option<f32>,
+    // 何も食べないと尻尾が1つ減るまでのtick数
+    pub hunger_ticks: Option<u32>,
+    // 広い盤面でコンピュータの蛇何匹かと戦い、最後まで生き残った蛇が勝つか
+    pub battle: bool,
+    // 蛇同士がぶつかった時のルール
+    pub collision: CollisionRule,
+    // foodのスコアの倍率
+    pub score_multiplier: u32,
+    // オンラインのランキングにスコアを送るか
+    pub leaderboard: bool,
+    // 今日の日付からシードとステージを決めるか(ハイスコアは日ごとに記録する)
+    pub daily: bool,
+}
+
+// 蛇同士がぶつかった時のルール
+// head同士がぶつかったらどのルールでも短い方が死ぬ(同じ長さなら両方死ぬ)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionRule {
+    // 他の蛇のbodyにheadが入ったら入った方が死ぬ
+    #[default]
+    Standard,
+    // 他の蛇のbodyにheadが入ったら、入った方は死なずにそこから尻尾までを切り取ってfoodに変える
+    TailCut,
+}
+
+// メニューで選べるゲームのモード(ハイスコアはモードごとに記録する)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    // 難易度で決まるルールのまま
+    #[default]
+    Classic,
+    // 画面端は必ず反対側につながる
+    Endless,
+    // 画面端は必ず壁(スコアは2倍)
+    Walls,
+    // 制限時間内に食べたfoodのスコアを競う
+    TimeAttack,
+    // 盤面が外側から縮んでいく
+    Survival,
+    // 今日の日付で決まる、全員同じfoodの並びとステージ
+    Daily,
+    // 何も食べないと尻尾が減っていき、headだけになったら死ぬ
+    Hunger,
+    // 広い盤面でコンピュータの蛇何匹かと最後の1匹になるまで戦う
+    Battle,
+}
+
+impl GameMode {
+    // メニューで選べる順番
+    pub const ALL: [GameMode; 8] = [
+        GameMode::Classic,
+        GameMode::Endless,
+        GameMode::Walls,
+        GameMode::TimeAttack,
+        GameMode::Survival,
+        GameMode::Daily,
+        GameMode::Hunger,
+        GameMode::Battle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Endless => "Endless",
+            GameMode::Walls => "Walls",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Survival => "Survival",
+            GameMode::Daily => "Daily",
+            GameMode::Hunger => "Hunger",
+            GameMode::Battle => "Battle",
+        }
+    }
+
+    // ファイル名などに使う名前(設定ファイルと同じ小文字)
+    pub fn key(self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::Endless => "endless",
+            GameMode::Walls => "walls",
+            GameMode::TimeAttack => "timeattack",
+            GameMode::Survival => "survival",
+            GameMode::Daily => "daily",
+            GameMode::Hunger => "hunger",
+            GameMode::Battle => "battle",
+        }
+    }
+
+    // 次のモード(Xキーで順番に切り替える)
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn rules(self) -> ModeRules {
+        let classic = ModeRules {
+            topology: None,
+            time_limit: None,
+            time_bonus: 0.0,
+            shrink_interval: None,
+            hunger_ticks: None,
+            battle: false,
+            collision: CollisionRule::Standard,
+            score_multiplier: 1,
+            leaderboard: true,
+            daily: false,
+        };
+        match self {
+            GameMode::Classic => classic,
+            GameMode::Endless => ModeRules {
+                topology: Some(Topology::Wrapping),
+                leaderboard: false,
+                ..classic
+            },
+            GameMode::Walls => ModeRules {
+                topology: Some(Topology::Bounded),
+                score_multiplier: 2,
+                leaderboard: false,
+                ..classic
+            },
+            GameMode::TimeAttack => ModeRules {
+                time_limit: Some(TIME_ATTACK_SECONDS),
+                time_bonus: TIME_ATTACK_BONUS_SECONDS,
+                leaderboard: false,
+                ..classic
+            },
+            GameMode::Survival => ModeRules {
+                shrink_interval: Some(SURVIVAL_SHRINK_SECONDS),
+                leaderboard: false,
+                ..classic
+            },
+            GameMode::Daily => ModeRules {
+                leaderboard: false,
+                daily: true,
+                ..classic
+            },
+            GameMode::Hunger => ModeRules {
+                hunger_ticks: Some(HUNGER_TICKS),
+                leaderboard: false,
+                ..classic
+            },
+            GameMode::Battle => ModeRules {
+                battle: true,
+                collision: CollisionRule::TailCut,
+                leaderboard: false,
+                ..classic
+            },
+        }
+    }
+}
+
+// UNIX時間の秒timestampの日(UTC)のDailyのシード
+// 日付の数をかき混ぜて、隣り合った日でも全く違うfoodの並びにする(splitmix64)
+pub fn daily_seed(timestamp: u64) -> u64 {
+    let mut z = day_of(timestamp).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// UNIX時間の秒timestampの日(UTC)のDailyのステージ(count個の組み込みのステージから日ごとに順番に選ぶ)
+pub fn daily_level(timestamp: u64, count: usize) -> usize {
+    (day_of(timestamp) % count.max(1) as u64) as usize
+}
+
+// UNIX時間の秒を1970-01-01からの日数(UTC)にする
+pub fn day_of(timestamp: u64) -> u64 {
+    timestamp / 86400
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_cycles_through_every_mode() {
+        let mut mode = GameMode::Classic;
+        let mut seen = Vec::new();
+        for _ in 0..GameMode::ALL.len() {
+            seen.push(mode);
+            mode = mode.next();
+        }
+        assert_eq!(mode, GameMode::Classic);
+        assert_eq!(seen, GameMode::ALL);
+    }
+
+    #[test]
+    fn key_matches_the_saved_name() {
+        for mode in GameMode::ALL {
+            let saved = serde_json::to_string(&mode).unwrap();
+            assert_eq!(saved, format!("\"{}\"", mode.key()));
+        }
+    }
+
+    #[test]
+    fn only_classic_scores_go_to_the_leaderboard() {
+        for mode in GameMode::ALL {
+            assert_eq!(mode.rules().leaderboard, mode == GameMode::Classic);
+        }
+    }
+
+    #[test]
+    fn daily_setup_changes_once_a_day() {
+        let morning = 20_000 * 86400 + 3600;
+        let evening = morning + 20 * 3600;
+        let tomorrow = morning + 86400;
+        assert_eq!(daily_seed(morning), daily_seed(evening));
+        assert_ne!(daily_seed(morning), daily_seed(tomorrow));
+        assert_eq!(daily_level(morning, 5), daily_level(evening, 5));
+        assert_eq!(daily_level(tomorrow, 5), (daily_level(morning, 5) + 1) % 5);
+        // ステージがなくても0除算しない
+        assert_eq!(daily_level(morning, 0), 0);
+    }
+}
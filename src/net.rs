@@ -0,0 +1,470 @@
+use serde::{Deserialize, Serialize};
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+use crate::config::{LEADERBOARD_TIMEOUT_SECONDS, NET_TIMEOUT_SECONDS};
+use crate::controller::NetInputs;
+use crate::grid::Direction;
+use crate::script::ScriptSource;
+
+// オンラインのランキングの1行
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    name: String,
+    score: u32,
+}
+
+// オンラインのランキングに送信するスコア
+#[derive(Clone, Debug, Serialize)]
+pub struct ScoreSubmission {
+    pub name: String,
+    pub score: u32,
+    pub seed: u64,
+    pub difficulty: String,
+}
+
+// 通信用のスレッドから受け取るランキングの上位(または失敗の理由)
+pub type LeaderboardResult = Result<Vec<LeaderboardEntry>, String>;
+
+// オンラインのランキング
+// 通信は別スレッドで行い、結果をチャンネルで受け取るので描画は止まらない
+#[derive(Default)]
+pub struct Leaderboard {
+    // 通信中のスレッドからの結果を受け取る
+    receiver: Option<Receiver<LeaderboardResult>>,
+    // 受け取った結果
+    result: Option<LeaderboardResult>,
+}
+
+impl Leaderboard {
+    // スコアを送信してから上位10件を取得する
+    pub fn submit(&mut self, url: &str, submission: ScoreSubmission) {
+        let (sender, receiver) = mpsc::channel();
+        let url = url.to_string();
+        std::thread::spawn(move || {
+            let result = serde_json::to_string(&submission)
+                .map_err(|e| e.to_string())
+                .and_then(|body| http_request(&url, Some(&body)))
+                .and_then(|_| http_request(&url, None))
+                .and_then(|body| {
+                    serde_json::from_str::<Vec<LeaderboardEntry>>(&body).map_err(|e| e.to_string())
+                });
+            // ゲームを始め直していたら受け取る側はもうない
+            let _ = sender.send(result.map(|mut entries| {
+                entries.truncate(10);
+                entries
+            }));
+        });
+        self.receiver = Some(receiver);
+        self.result = None;
+    }
+
+    // 通信が終わっていれば結果を受け取る
+    pub fn poll(&mut self) {
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(result) => {
+                if let Err(e) = &result {
+                    eprintln!("Could not reach the leaderboard: {}", e);
+                }
+                self.result = Some(result);
+                self.receiver = None;
+            }
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => self.receiver = None,
+        }
+    }
+
+    // 画面に表示する行(送信していなければ空)
+    pub fn lines(&self) -> Vec<String> {
+        if self.receiver.is_some() {
+            return vec!["Global top 10: loading...".to_string()];
+        }
+        match &self.result {
+            Some(Ok(entries)) => std::iter::once("Global top 10".to_string())
+                .chain(
+                    entries
+                        .iter()
+                        .enumerate()
+                        .map(|(i, e)| format!("{:2}. {:6}  {}", i + 1, e.score, e.name)),
+                )
+                .collect(),
+            Some(Err(_)) => vec!["Global top 10: unavailable".to_string()],
+            None => Vec::new(),
+        }
+    }
+}
+
+// URL(http://かhttps://)にリクエストを送り、成功(2xx)ならレスポンスの本文を返す
+// bodyがあればJSONとしてPOSTし、なければGETする
+pub fn http_request(url: &str, body: Option<&str>) -> Result<String, String> {
+    let timeout = Duration::from_secs(LEADERBOARD_TIMEOUT_SECONDS);
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .build()
+        .into();
+    let response = match body {
+        Some(body) => agent
+            .post(url)
+            .header("Content-Type", "application/json")
+            .send(body),
+        None => agent.get(url).call(),
+    };
+    response
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| e.to_string())
+}
+
+// ネットワーク対戦でやり取りするメッセージ(1行に1つのJSON)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum NetMessage {
+    // 接続した側が最初に送る、対戦するか観戦するだけか
+    Join {
+        spectator: bool,
+    },
+    // ホストが決めたゲームの設定(お互いに同じシードで同じゲームを始める)
+    Start {
+        seed: u64,
+        difficulty: String,
+        level: String,
+        food_count: usize,
+        max_tick_rate: u32,
+        grid_size: (i16, i16),
+        // ホストのスクリプト(お互いに同じルールで進める)
+        #[serde(default)]
+        scripts: Vec<ScriptSource>,
+    },
+    // そのtickの前に入力した方向(入力がなければ空)
+    Input {
+        tick: u32,
+        dirs: Vec<Direction>,
+    },
+    // ホストが観戦者に送る、そのtickの両方の蛇の入力(蛇の番号順)
+    Tick {
+        tick: u32,
+        inputs: Vec<Vec<Direction>>,
+    },
+}
+
+// 通信用のスレッドから届くできごと
+pub enum NetEvent {
+    // 相手に接続できた(書き込み用のストリーム)
+    Connected(TcpStream),
+    Message(NetMessage),
+    // 接続が切れた・接続できなかった理由
+    Disconnected(String),
+}
+
+// ホストが受け付けた接続(対戦か観戦かはJoinが届くまで分からない)と観戦者
+pub struct Peer {
+    events: Receiver<NetEvent>,
+    stream: Option<TcpStream>,
+}
+
+impl Peer {
+    // 接続を閉じる(読み込み用のスレッドも終わる)
+    pub fn close(&self) {
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+// ネットワーク対戦の接続
+// 毎tick自分の入力を送り、相手の同じtickの入力が届いてから進める(ロックステップ)
+// 観戦者は入力を送らず、ホストから届く両方の蛇の入力で同じゲームを進めるだけ
+pub struct NetSession {
+    // ホストなら1人目、接続した側なら2人目の蛇を操作する
+    pub host: bool,
+    // 観戦するだけの接続か(1人目の入力をlocal、2人目をremoteで受け取る)
+    pub spectator: bool,
+    // 接続を待ち受けている間だけSome(ホスト。対戦が始まっても観戦者を受け付ける)
+    pub listener: Option<TcpListener>,
+    // 通信用のスレッドからのできごと
+    pub events: Option<Receiver<NetEvent>>,
+    // 相手への書き込み用
+    pub stream: Option<TcpStream>,
+    // 画面に表示する状態
+    pub status: String,
+    // 次のtickで送る自分の入力
+    pub pending: Vec<Direction>,
+    // 送った自分の入力(tickごと。相手の入力が届いたら同じtickで入れる)
+    pub local: NetInputs,
+    // 届いた相手の入力(tickごと)
+    pub remote: NetInputs,
+    // ホストが受け付けて、まだJoinが届いていない接続
+    pub joining: Vec<Peer>,
+    pub spectators: Vec<Peer>,
+    // ホストが送ったゲームの設定と、進めた全てのtickの入力(途中から観戦を始めた相手に送る)
+    pub start: Option<NetMessage>,
+    pub history: Vec<Vec<Vec<Direction>>>,
+}
+
+impl NetSession {
+    // portで相手の接続を待ち受ける
+    pub fn host(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| e.to_string())?;
+        // 待っている間も描画を止めないようにupdateで毎回確かめる
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(NetSession {
+            listener: Some(listener),
+            status: format!("Waiting for an opponent on port {}...", port),
+            ..NetSession::new(true)
+        })
+    }
+
+    // addrのホストに接続して観戦する
+    pub fn spectate(addr: String) -> Self {
+        NetSession {
+            spectator: true,
+            ..NetSession::connect(addr)
+        }
+    }
+
+    // addrのホストに接続する(接続は別スレッドで行う)
+    pub fn connect(addr: String) -> Self {
+        let status = format!("Connecting to {}...", addr);
+        let events = Self::spawn(move || {
+            let socket_addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::NotFound, "could not resolve address")
+            })?;
+            TcpStream::connect_timeout(&socket_addr, Duration::from_secs(NET_TIMEOUT_SECONDS))
+        });
+        NetSession {
+            events: Some(events),
+            status,
+            ..NetSession::new(false)
+        }
+    }
+
+    fn new(host: bool) -> Self {
+        NetSession {
+            host,
+            spectator: false,
+            listener: None,
+            events: None,
+            stream: None,
+            status: String::new(),
+            pending: Vec::new(),
+            local: NetInputs::default(),
+            remote: NetInputs::default(),
+            joining: Vec::new(),
+            spectators: Vec::new(),
+            start: None,
+            history: Vec::new(),
+        }
+    }
+
+    // ホストは新しい接続を受け付け、Joinが届いたら対戦の相手か観戦者に振り分ける
+    // 対戦の相手が決まったら、その書き込み用のストリームを返す(相手がいれば2人目は切る)
+    pub fn accept_peers(&mut self) -> Option<TcpStream> {
+        if let Some(listener) = &self.listener {
+            match listener.accept() {
+                Ok((stream, _)) => self.joining.push(Peer {
+                    events: Self::spawn(move || Ok(stream)),
+                    stream: None,
+                }),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+                Err(e) => self.status = format!("Connection failed: {}", e),
+            }
+        }
+        let mut opponent = None;
+        for mut peer in std::mem::take(&mut self.joining) {
+            let mut join = None;
+            let mut closed = false;
+            for event in peer.events.try_iter() {
+                match event {
+                    NetEvent::Connected(stream) => peer.stream = Some(stream),
+                    NetEvent::Message(NetMessage::Join { spectator }) => join = Some(spectator),
+                    NetEvent::Message(_) => (),
+                    NetEvent::Disconnected(_) => closed = true,
+                }
+            }
+            match join {
+                _ if closed => (),
+                None => self.joining.push(peer),
+                Some(false) if self.events.is_none() && opponent.is_none() => {
+                    opponent = peer.stream;
+                    self.events = Some(peer.events);
+                }
+                Some(false) => peer.close(),
+                Some(true) => self.add_spectator(peer),
+            }
+        }
+        // 切れた観戦者は外す
+        self.spectators.retain(|peer| {
+            !peer
+                .events
+                .try_iter()
+                .any(|event| matches!(event, NetEvent::Disconnected(_)))
+        });
+        opponent
+    }
+
+    // 観戦者を加え、もう始まっていればゲームの設定と今までのtickの入力を送る
+    fn add_spectator(&mut self, mut peer: Peer) {
+        let Some(stream) = &mut peer.stream else {
+            return;
+        };
+        let mut messages: Vec<NetMessage> = self.start.iter().cloned().collect();
+        messages.extend(
+            self.history
+                .iter()
+                .enumerate()
+                .map(|(tick, inputs)| NetMessage::Tick {
+                    tick: tick as u32,
+                    inputs: inputs.clone(),
+                }),
+        );
+        if messages.iter().all(|m| write_message(stream, m).is_ok()) {
+            self.spectators.push(peer);
+        }
+    }
+
+    // 全ての観戦者にメッセージを送る(送れなかった観戦者は外す)
+    pub fn broadcast(&mut self, message: &NetMessage) {
+        self.spectators.retain_mut(|peer| {
+            peer.stream
+                .as_mut()
+                .is_some_and(|stream| write_message(stream, message).is_ok())
+        });
+    }
+
+    // 別スレッドでopenしたストリームからメッセージを読み続ける
+    pub fn spawn(
+        open: impl FnOnce() -> std::io::Result<TcpStream> + Send + 'static,
+    ) -> Receiver<NetEvent> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let streams = open().and_then(|stream| {
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+                let writer = stream.try_clone()?;
+                Ok((stream, writer))
+            });
+            let (reader, writer) = match streams {
+                Ok(streams) => streams,
+                Err(e) => {
+                    let _ = sender.send(NetEvent::Disconnected(e.to_string()));
+                    return;
+                }
+            };
+            if sender.send(NetEvent::Connected(writer)).is_err() {
+                return;
+            }
+            for line in BufReader::new(reader).lines() {
+                let message = line
+                    .map_err(|e| e.to_string())
+                    .and_then(|line| serde_json::from_str(&line).map_err(|e| e.to_string()));
+                let event = match message {
+                    Ok(message) => NetEvent::Message(message),
+                    Err(e) => NetEvent::Disconnected(e),
+                };
+                let disconnected = matches!(event, NetEvent::Disconnected(_));
+                // 接続を終えていたら受け取る側はもうない
+                if sender.send(event).is_err() || disconnected {
+                    return;
+                }
+            }
+            let _ = sender.send(NetEvent::Disconnected("Connection closed".to_string()));
+        });
+        receiver
+    }
+
+    // 自分が操作する蛇の番号(観戦者は1人目の入力をlocalで受け取る)
+    pub fn local_player(&self) -> usize {
+        if self.host || self.spectator {
+            0
+        } else {
+            1
+        }
+    }
+
+    // 相手にメッセージを送る
+    pub fn send(&mut self, message: &NetMessage) -> Result<(), String> {
+        let stream = self.stream.as_mut().ok_or("Not connected")?;
+        write_message(stream, message)
+    }
+}
+
+// メッセージを1行のJSONにして書き込む
+fn write_message(stream: &mut TcpStream, message: &NetMessage) -> Result<(), String> {
+    let line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    writeln!(stream, "{}", line).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 読み込み用のスレッドにつないだ書き込み側のストリームと、届いたできごと
+    fn connect() -> (TcpStream, Receiver<NetEvent>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let events = NetSession::spawn(move || listener.accept().map(|(stream, _)| stream));
+        let stream = TcpStream::connect(addr).unwrap();
+        (stream, events)
+    }
+
+    fn next(events: &Receiver<NetEvent>) -> NetEvent {
+        events.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn messages_arrive_one_per_line() {
+        let (mut stream, events) = connect();
+        assert!(matches!(next(&events), NetEvent::Connected(_)));
+        let start = NetMessage::Start {
+            seed: u64::MAX,
+            difficulty: "Hard".to_string(),
+            level: "Classic".to_string(),
+            food_count: 2,
+            max_tick_rate: 20,
+            grid_size: (40, 30),
+            scripts: vec![ScriptSource {
+                name: "rules".to_string(),
+                text: "fn on_death() {\n    speed(1);\n}\n".to_string(),
+            }],
+        };
+        let input = NetMessage::Input {
+            tick: 7,
+            dirs: vec![Direction::Up, Direction::Left],
+        };
+        write_message(&mut stream, &start).unwrap();
+        write_message(&mut stream, &input).unwrap();
+        match next(&events) {
+            NetEvent::Message(NetMessage::Start { seed, scripts, .. }) => {
+                assert_eq!(seed, u64::MAX);
+                assert_eq!(scripts.len(), 1);
+                assert_eq!(scripts[0].text, "fn on_death() {\n    speed(1);\n}\n");
+            }
+            _ => panic!("expected Start"),
+        }
+        match next(&events) {
+            NetEvent::Message(NetMessage::Input { tick, dirs }) => {
+                assert_eq!(tick, 7);
+                assert_eq!(dirs, vec![Direction::Up, Direction::Left]);
+            }
+            _ => panic!("expected Input"),
+        }
+    }
+
+    #[test]
+    fn reads_older_starts_and_stops_on_broken_lines() {
+        let (mut stream, events) = connect();
+        assert!(matches!(next(&events), NetEvent::Connected(_)));
+        // スクリプトを送らない相手のStart
+        let older = r#"{"Start":{"seed":1,"difficulty":"Normal","level":"Classic","food_count":1,"max_tick_rate":20,"grid_size":[40,30]}}"#;
+        writeln!(stream, "{}\nnot json", older).unwrap();
+        assert!(matches!(
+            next(&events),
+            NetEvent::Message(NetMessage::Start { scripts, .. }) if scripts.is_empty()
+        ));
+        assert!(matches!(next(&events), NetEvent::Disconnected(_)));
+    }
+}
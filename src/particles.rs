@@ -0,0 +1,81 @@
+use oorandom::Rand32;
+
+use ggez::graphics;
+
+use crate::config::{PARTICLE_COUNT, PARTICLE_SECONDS, PARTICLE_SIZE, PARTICLE_SPEED};
+use crate::grid::GridPosition;
+use crate::rendering::CellBatch;
+
+// 飛び散って消えていく小さな四角
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    // 中心の位置と速さ(ピクセル, ピクセル/秒)
+    pos: [f32; 2],
+    velocity: [f32; 2],
+    // 出てからの秒数
+    age: f32,
+    color: [f32; 4],
+}
+
+// foodを食べた時などに出るパーティクル
+// 見た目だけなのでゲームの乱数とは別の乱数を使う(リプレイの再現に影響しない)
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: Rand32,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Particles {
+            particles: Vec::new(),
+            rng: Rand32::new(0),
+        }
+    }
+}
+
+impl Particles {
+    // posのマスの中心からcolorのパーティクルを四方に飛ばす
+    pub fn burst(&mut self, pos: GridPosition, color: [f32; 4]) {
+        let cell: graphics::Rect = pos.into();
+        let center = [cell.x + cell.w / 2.0, cell.y + cell.h / 2.0];
+        for _ in 0..PARTICLE_COUNT {
+            let angle = self.rng.rand_float() * std::f32::consts::TAU;
+            let speed = PARTICLE_SPEED * (0.5 + self.rng.rand_float());
+            self.particles.push(Particle {
+                pos: center,
+                velocity: [angle.cos() * speed, angle.sin() * speed],
+                age: 0.0,
+                color,
+            });
+        }
+    }
+
+    // dt秒だけ動かし、消える時間になったものを消す(フレームレートによらず同じ速さ)
+    pub fn update(&mut self, dt: f32) {
+        for particle in &mut self.particles {
+            particle.pos[0] += particle.velocity[0] * dt;
+            particle.pos[1] += particle.velocity[1] * dt;
+            particle.age += dt;
+        }
+        self.particles.retain(|p| p.age < PARTICLE_SECONDS);
+    }
+
+    // パーティクルの四角形をbatchに追加する(時間が経つほど小さく薄くなる)
+    pub fn draw(&self, batch: &mut CellBatch) {
+        for particle in &self.particles {
+            let left = 1.0 - particle.age / PARTICLE_SECONDS;
+            let size = PARTICLE_SIZE * left;
+            let mut color = particle.color;
+            color[3] *= left;
+            batch.push(
+                graphics::Rect::new(
+                    particle.pos[0] - size / 2.0,
+                    particle.pos[1] - size / 2.0,
+                    size,
+                    size,
+                ),
+                color,
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::food::FoodKind;
+use crate::grid::{Cell, Cells, Direction, GridPosition};
+use crate::level::Portal;
+
+// 経路の1歩(進む方向と、進んだ先のマス。ワープマスに入ったらワープ先)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub dir: Direction,
+    pub pos: GridPosition,
+}
+
+// 蛇が安全に入れるマスならtrue(蛇と壁に加えて毒のfoodも避ける)
+pub fn is_safe(cells: &Cells, pos: GridPosition) -> bool {
+    !cells.is_blocked(pos) && cells.get(pos) != Cell::Food(FoodKind::Poison)
+}
+
+// 毒以外のfoodのマスならtrue
+pub fn is_food(cells: &Cells, pos: GridPosition) -> bool {
+    matches!(cells.get(pos), Cell::Food(kind) if kind != FoodKind::Poison)
+}
+
+// posからdirに1マス進んだ先(ワープマスならワープ先)。画面端が壁で進めなければNone
+pub fn step(
+    cells: &Cells,
+    portals: &[Portal],
+    pos: GridPosition,
+    dir: Direction,
+) -> Option<GridPosition> {
+    GridPosition::new_from_move(pos, dir, cells.board()).map(|p| Portal::warp(portals, p))
+}
+
+// 幅優先探索で、fromからis_goalのマスまで安全なマスだけを通る最短経路を探す
+// 同じ長さの経路が複数あればDirection::ALLの順に先に見つかった方
+// たどり着けなければNone(fromがゴールなら空の経路)
+pub fn shortest_path(
+    cells: &Cells,
+    portals: &[Portal],
+    from: GridPosition,
+    is_goal: impl Fn(GridPosition) -> bool,
+) -> Option<Vec<Step>> {
+    if is_goal(from) {
+        return Some(Vec::new());
+    }
+    // マスごとに、どのマスからどの方向に進んで着いたか
+    let mut came_from: HashMap<GridPosition, (GridPosition, Direction)> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(pos) = queue.pop_front() {
+        for dir in Direction::ALL {
+            let Some(next) = step(cells, portals, pos, dir) else {
+                continue;
+            };
+            if next == from || came_from.contains_key(&next) || !is_safe(cells, next) {
+                continue;
+            }
+            came_from.insert(next, (pos, dir));
+            if is_goal(next) {
+                return Some(rebuild_path(&came_from, from, next));
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+// 最短経路の最初の1歩(fromがゴールか、たどり着けなければNone)
+pub fn first_step(
+    cells: &Cells,
+    portals: &[Portal],
+    from: GridPosition,
+    is_goal: impl Fn(GridPosition) -> bool,
+) -> Option<Step> {
+    shortest_path(cells, portals, from, is_goal)?
+        .first()
+        .copied()
+}
+
+// fromから安全なマスだけを通って行けるマスの数(fromも含む。limitまで数えたらやめる)
+// 進んだ先が蛇の長さより狭ければ閉じ込められる
+pub fn reachable_area(
+    cells: &Cells,
+    portals: &[Portal],
+    from: GridPosition,
+    limit: usize,
+) -> usize {
+    let mut visited = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(pos) = queue.pop_front() {
+        if visited.len() >= limit {
+            break;
+        }
+        for dir in Direction::ALL {
+            if let Some(next) = step(cells, portals, pos, dir) {
+                if is_safe(cells, next) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    visited.len().min(limit)
+}
+
+// ゴールからcame_fromをたどってfromからの経路にする
+fn rebuild_path(
+    came_from: &HashMap<GridPosition, (GridPosition, Direction)>,
+    from: GridPosition,
+    goal: GridPosition,
+) -> Vec<Step> {
+    let mut path = Vec::new();
+    let mut pos = goal;
+    while pos != from {
+        let (prev, dir) = came_from[&pos];
+        path.push(Step { dir, pos });
+        pos = prev;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Board, Topology};
+
+    fn cells(size: (i16, i16), walls: &[(i16, i16)]) -> Cells {
+        let mut cells = Cells::new(Board::new(size, Topology::Bounded));
+        for &(x, y) in walls {
+            cells.set(GridPosition::new(x, y), Cell::Wall);
+        }
+        cells
+    }
+
+    fn food_at(goal: GridPosition) -> impl Fn(GridPosition) -> bool {
+        move |pos| pos == goal
+    }
+
+    #[test]
+    fn finds_the_shortest_way_around_walls() {
+        let cells = cells((8, 5), &[(3, 0), (3, 1), (3, 2), (3, 3)]);
+        let from = GridPosition::new(1, 1);
+        let goal = GridPosition::new(5, 1);
+        let path = shortest_path(&cells, &[], from, food_at(goal)).unwrap();
+        // 壁の下の隙間(3, 4)を通る
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.last().map(|s| s.pos), Some(goal));
+        assert!(path.iter().any(|s| s.pos == GridPosition::new(3, 4)));
+        assert_eq!(
+            first_step(&cells, &[], from, food_at(goal)),
+            path.first().copied()
+        );
+        assert_eq!(
+            shortest_path(&cells, &[], goal, food_at(goal)),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn avoids_poison_and_gives_up_when_blocked() {
+        let mut cells = cells((5, 1), &[]);
+        let from = GridPosition::new(0, 0);
+        let goal = GridPosition::new(4, 0);
+        assert_eq!(
+            shortest_path(&cells, &[], from, food_at(goal)).map(|p| p.len()),
+            Some(4)
+        );
+        cells.set(GridPosition::new(2, 0), Cell::Food(FoodKind::Poison));
+        assert_eq!(shortest_path(&cells, &[], from, food_at(goal)), None);
+        assert_eq!(first_step(&cells, &[], from, food_at(goal)), None);
+    }
+
+    #[test]
+    fn goes_through_portals() {
+        let cells = cells((10, 1), &[]);
+        let portals = [Portal {
+            a: GridPosition::new(2, 0),
+            b: GridPosition::new(7, 0),
+        }];
+        let path = shortest_path(
+            &cells,
+            &portals,
+            GridPosition::new(1, 0),
+            food_at(GridPosition::new(8, 0)),
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            vec![
+                Step {
+                    dir: Direction::Right,
+                    pos: GridPosition::new(7, 0),
+                },
+                Step {
+                    dir: Direction::Right,
+                    pos: GridPosition::new(8, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_the_area_on_one_side_of_a_wall() {
+        let cells = cells((6, 4), &[(2, 0), (2, 1), (2, 2), (2, 3)]);
+        let from = GridPosition::new(0, 0);
+        assert_eq!(reachable_area(&cells, &[], from, 100), 8);
+        assert_eq!(reachable_area(&cells, &[], from, 5), 5);
+    }
+}
@@ -0,0 +1,47 @@
+use ggez::{graphics, Context, GameResult};
+
+use crate::config::{config, SHADERS_DIR};
+
+// 画面全体にかけるシェーダーの効果
+// 1フレーム分をウィンドウと同じ大きさの画像に描いてから、シェーダーを通してウィンドウに描く
+pub struct PostProcess {
+    shader: graphics::Shader,
+    // 1フレーム分を描く画像(ウィンドウの大きさが変わったら作り直される)
+    target: graphics::ScreenImage,
+}
+
+impl PostProcess {
+    // configの効果のシェーダー(/shaders/<名前>.wgsl)を読み込む
+    // 効果を使わない・読み込めなければNone(ウィンドウに直接描く)
+    pub fn load(ctx: &Context) -> Option<PostProcess> {
+        let name = config().post_effect.file_name()?;
+        let path = format!("{}/{}.wgsl", SHADERS_DIR, name);
+        match graphics::ShaderBuilder::new()
+            .fragment_path(&path)
+            .build(ctx)
+        {
+            Ok(shader) => Some(PostProcess {
+                shader,
+                target: graphics::ScreenImage::new(ctx, None, 1.0, 1.0, 1),
+            }),
+            Err(e) => {
+                eprintln!("Could not load {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    // このフレームを描くcanvas(ウィンドウではなく画像に描く)
+    pub fn canvas(&mut self, ctx: &Context, clear: graphics::Color) -> graphics::Canvas {
+        graphics::Canvas::from_screen_image(ctx, &mut self.target, clear)
+    }
+
+    // 描き終えた画像をシェーダーを通してウィンドウに描く
+    pub fn present(&mut self, ctx: &mut Context) -> GameResult {
+        let image = self.target.image(ctx);
+        let mut canvas = graphics::Canvas::from_frame(ctx, graphics::Color::BLACK);
+        canvas.set_shader(&self.shader);
+        canvas.draw(&image, graphics::DrawParam::new());
+        canvas.finish(ctx)
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use ggez::{graphics, Context, GameResult};
+
+use crate::config::{GIF_FPS, GIF_SECONDS, GIF_WIDTH};
+use crate::records::{file_timestamp, now_timestamp};
+
+// 書き出し用のスレッドから受け取る結果(書いたファイルか失敗の理由)
+pub type GifResult = Result<PathBuf, String>;
+
+// プレイ中の最後のGIF_SECONDS秒を縮小して取っておき、ゲームが終わったらアニメーションGIFに書き出す
+// 画像はGPUで縮小してから次のフレームで読み出し、GIFへの変換は別スレッドで行うので描画は止まらない
+#[derive(Default)]
+pub struct GifRecorder {
+    // 縮小して取っておいたフレームのRGBA(古い順。GIF_SECONDS秒分を超えたら古いものから捨てる)
+    frames: VecDeque<Vec<u8>>,
+    // フレームの大きさ(ウィンドウの大きさが変わったら取り直す)
+    size: (u32, u32),
+    // 次に取るまでの秒数
+    cooldown: f32,
+    // 縮小したフレームを描く画像
+    target: Option<graphics::Image>,
+    // targetに描いたフレームをまだ読み出していなければtrue
+    pending: bool,
+    // 取り終えたら書き出すディレクトリ
+    export_to: Option<PathBuf>,
+    // 書き出し中のスレッドからの結果を受け取る
+    receiver: Option<Receiver<GifResult>>,
+}
+
+impl GifRecorder {
+    // 取っておいたフレームを捨てる(ゲームを始め直した時)
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.pending = false;
+        self.export_to = None;
+    }
+
+    // ウィンドウに描き終えたフレームを縮小して取る(drawの最後に呼ぶ)
+    // recordingがfalseになったら、書き出しを頼まれていれば書き出す
+    pub fn capture(&mut self, ctx: &mut Context, recording: bool) -> GameResult {
+        // 前のフレームで縮小した画像はもう描き終わっているので読み出せる
+        if let (true, Some(target)) = (self.pending, &self.target) {
+            self.frames.push_back(target.to_pixels(ctx)?);
+            while self.frames.len() > (GIF_SECONDS * GIF_FPS as f32) as usize {
+                self.frames.pop_front();
+            }
+            self.pending = false;
+        }
+        if !recording {
+            if let Some(dir) = self.export_to.take() {
+                self.export(dir);
+            }
+            return Ok(());
+        }
+        self.cooldown -= ctx.time.delta().as_secs_f32();
+        if self.cooldown > 0.0 {
+            return Ok(());
+        }
+        self.cooldown += 1.0 / GIF_FPS as f32;
+        self.cooldown = self.cooldown.max(0.0);
+        let frame = ctx.gfx.frame().clone();
+        // 最小化してウィンドウの大きさが0の間は取らない(大きさ0の画像は作れない)
+        if frame.width() == 0 || frame.height() == 0 {
+            return Ok(());
+        }
+        let width = GIF_WIDTH.min(frame.width());
+        let size = (width, (frame.height() * width / frame.width()).max(1));
+        if size != self.size || self.target.is_none() {
+            self.frames.clear();
+            self.size = size;
+            self.target = Some(graphics::Image::new_canvas_image(
+                ctx,
+                graphics::ImageFormat::Rgba8UnormSrgb,
+                size.0,
+                size.1,
+                1,
+            ));
+        }
+        let Some(target) = &self.target else {
+            return Ok(());
+        };
+        let mut canvas = graphics::Canvas::from_image(ctx, target.clone(), graphics::Color::BLACK);
+        canvas.draw(
+            &frame,
+            graphics::DrawParam::new().scale([
+                size.0 as f32 / frame.width() as f32,
+                size.1 as f32 / frame.height() as f32,
+            ]),
+        );
+        canvas.finish(ctx)?;
+        self.pending = true;
+        Ok(())
+    }
+
+    // 取るのをやめたら(蛇が消え終わったら)dirにGIFを書き出す
+    pub fn request_export(&mut self, dir: PathBuf) {
+        self.export_to = Some(dir);
+    }
+
+    // 取っておいたフレームを別スレッドでGIFにしてdirに書き込む
+    fn export(&mut self, dir: PathBuf) {
+        let frames: Vec<Vec<u8>> = self.frames.drain(..).collect();
+        if frames.is_empty() {
+            return;
+        }
+        let size = self.size;
+        let path = dir.join(format!("run-{}.gif", file_timestamp(now_timestamp())));
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = write_gif(&dir, &path, size, frames).map(|()| path);
+            // 受け取る側がなくなっていても書き出しは終わっている
+            let _ = sender.send(result);
+        });
+        self.receiver = Some(receiver);
+    }
+
+    // 書き出しが終わっていればその結果
+    pub fn poll(&mut self) -> Option<GifResult> {
+        let receiver = self.receiver.as_ref()?;
+        match receiver.try_recv() {
+            Ok(result) => {
+                self.receiver = None;
+                Some(result)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.receiver = None;
+                None
+            }
+        }
+    }
+}
+
+// フレームを色を減らしながらGIFの1コマずつにして、繰り返し再生するGIFに書く
+fn write_gif(
+    dir: &std::path::Path,
+    path: &std::path::Path,
+    (width, height): (u32, u32),
+    frames: Vec<Vec<u8>>,
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let (width, height) = (width as u16, height as u16);
+    let mut encoder =
+        gif::Encoder::new(BufWriter::new(file), width, height, &[]).map_err(|e| e.to_string())?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(|e| e.to_string())?;
+    for mut pixels in frames {
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        gif_frame.delay = (100 / GIF_FPS) as u16;
+        encoder.write_frame(&gif_frame).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
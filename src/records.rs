@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+
+use ggez::{graphics, GameResult};
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{
+    config, ACHIEVEMENTS_FILE, HIGH_SCORES_FILE, HIGH_SCORE_COUNT, STATS_FILE, TOAST_SECONDS,
+    TOAST_SLIDE_SECONDS,
+};
+use crate::mode::{day_of, GameMode};
+
+// ハイスコア1件分
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub score: u32,
+    // ゲームオーバーの画面で入力した名前
+    #[serde(default)]
+    pub name: String,
+    // 記録した時刻(UNIX時間の秒)
+    pub timestamp: u64,
+    // プレイした難易度の名前
+    #[serde(default)]
+    pub difficulty: String,
+    // プレイしたモード
+    #[serde(default)]
+    pub mode: GameMode,
+}
+
+// モードごとに上位HIGH_SCORE_COUNT件のハイスコア表
+// 全てのモードをスコアの高い順に1つに並べて保存する
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    pub entries: Vec<HighScoreEntry>,
+    // 保存先のファイル
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl HighScores {
+    // dir内のハイスコアファイルを読み込む。存在しない・壊れている場合は空の表
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(HIGH_SCORES_FILE);
+        let mut high_scores: HighScores = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        high_scores.path = path;
+        high_scores
+    }
+
+    // ハイスコアファイルに書き込む
+    pub fn save(&self) -> GameResult {
+        let contents =
+            toml::to_string(self).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+
+    // modeのハイスコア表(Dailyはtimestampと同じ日の表)
+    pub fn table(&self, mode: GameMode, timestamp: u64) -> Vec<&HighScoreEntry> {
+        self.entries
+            .iter()
+            .filter(|e| Self::in_table(e, mode, timestamp))
+            .collect()
+    }
+
+    // entryがmodeのハイスコア表に入っているか(Dailyは日ごとに別の表)
+    fn in_table(entry: &HighScoreEntry, mode: GameMode, timestamp: u64) -> bool {
+        entry.mode == mode && (!mode.rules().daily || day_of(entry.timestamp) == day_of(timestamp))
+    }
+
+    // modeの表にnameのスコアを追加し、表に入ったら順位(0始まり)を返す
+    pub fn insert(
+        &mut self,
+        mode: GameMode,
+        score: u32,
+        timestamp: u64,
+        difficulty: &str,
+        name: &str,
+    ) -> Option<usize> {
+        // 同じスコアなら先に記録した方を上にする
+        let rank = self
+            .entries
+            .iter()
+            .filter(|e| Self::in_table(e, mode, timestamp))
+            .take_while(|e| e.score >= score)
+            .count();
+        if rank >= HIGH_SCORE_COUNT {
+            return None;
+        }
+        let index = self.entries.iter().take_while(|e| e.score >= score).count();
+        self.entries.insert(
+            index,
+            HighScoreEntry {
+                score,
+                name: name.to_string(),
+                timestamp,
+                difficulty: difficulty.to_string(),
+                mode,
+            },
+        );
+        // 表からあふれたmodeのスコアを消す
+        let mut count = 0;
+        self.entries.retain(|e| {
+            if !Self::in_table(e, mode, timestamp) {
+                return true;
+            }
+            count += 1;
+            count <= HIGH_SCORE_COUNT
+        });
+        Some(rank)
+    }
+
+    // timestampに記録したmodeの表のrank番目の名前を変える
+    pub fn set_name(&mut self, mode: GameMode, timestamp: u64, rank: usize, name: &str) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .filter(|e| Self::in_table(e, mode, timestamp))
+            .nth(rank)
+        {
+            entry.name = name.to_string();
+        }
+    }
+}
+
+// 全てのゲームを通した成績
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    // 遊んだゲームの数
+    games_played: u32,
+    // 食べたfoodの合計
+    pub foods_eaten: u32,
+    // 一番長くなった時の蛇の長さ
+    longest_snake: usize,
+    // 遊んだ時間の合計(秒)
+    play_time: f64,
+    // 保存先のファイル
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Stats {
+    // dir内の成績ファイルを読み込む。存在しない・壊れている場合は全て0
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(STATS_FILE);
+        let mut stats: Stats = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        stats.path = path;
+        stats
+    }
+
+    // 成績ファイルに書き込む
+    pub fn save(&self) -> GameResult {
+        let contents =
+            toml::to_string(self).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+
+    // 1ゲーム分の結果を足す
+    pub fn record(&mut self, foods_eaten: u32, longest_snake: usize, play_time: f64) {
+        self.games_played += 1;
+        self.foods_eaten += foods_eaten;
+        self.longest_snake = self.longest_snake.max(longest_snake);
+        self.play_time += play_time;
+    }
+
+    // 成績の画面に表示する行
+    pub fn lines(&self) -> Vec<String> {
+        let seconds = self.play_time as u64;
+        vec![
+            "Statistics".to_string(),
+            format!("Games played: {}", self.games_played),
+            format!("Food eaten: {}", self.foods_eaten),
+            format!("Longest snake: {}", self.longest_snake),
+            format!(
+                "Play time: {}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ),
+            "Backspace: back".to_string(),
+        ]
+    }
+}
+
+// 実績の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Achievement {
+    // 通算でfoodを100個食べる
+    Eat100,
+    // 蛇の長さが50になる
+    Length50,
+    // 1回のゲームで5分生き残る
+    Survive5Minutes,
+    // 1度も左に曲がらずに勝つ
+    NoLeftTurns,
+}
+
+impl Achievement {
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::Eat100 => "Eat 100 food",
+            Achievement::Length50 => "Reach length 50",
+            Achievement::Survive5Minutes => "Survive 5 minutes",
+            Achievement::NoLeftTurns => "Win without turning left",
+        }
+    }
+}
+
+// 解除した実績と、表示待ちの通知
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+    // 保存先のファイル
+    #[serde(skip)]
+    path: PathBuf,
+    // 画面右上に出す通知(先頭を表示中)
+    #[serde(skip)]
+    toasts: VecDeque<String>,
+    // 先頭の通知を表示し始めてからの秒数
+    #[serde(skip)]
+    toast_time: f32,
+}
+
+impl Achievements {
+    // dir内の実績ファイルを読み込む。存在しない・壊れている場合は何も解除していない
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(ACHIEVEMENTS_FILE);
+        let mut achievements: Achievements = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default();
+        achievements.path = path;
+        achievements
+    }
+
+    // 実績ファイルに書き込む
+    pub fn save(&self) -> GameResult {
+        let contents =
+            toml::to_string(self).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+
+    // 解除済みか
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    // まだ解除していなければ解除して通知を出す。新しく解除したらtrue
+    pub fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.unlocked.contains(&achievement) {
+            return false;
+        }
+        self.unlocked.push(achievement);
+        self.notify(format!("Achievement: {}", achievement.name()));
+        true
+    }
+
+    // 実績以外の通知(「Saved screenshot」など)も同じ場所に順番に出す
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.toasts.push_back(text.into());
+    }
+
+    // 通知の表示時間を進め、表示し終えたら次の通知へ
+    pub fn update(&mut self, dt: f32) {
+        if self.toasts.is_empty() {
+            return;
+        }
+        self.toast_time += dt;
+        if self.toast_time >= TOAST_SECONDS {
+            self.toasts.pop_front();
+            self.toast_time = 0.0;
+        }
+    }
+
+    // 表示中の通知を画面右上に描画する(右からスライドして出入りする)
+    pub fn draw_toast(&self, canvas: &mut graphics::Canvas) {
+        let Some(toast) = self.toasts.front() else {
+            return;
+        };
+        let screen = config().screen_size();
+        let (w, h, margin) = (560.0, 80.0, 20.0);
+        let t = self.toast_time;
+        let shown = (t / TOAST_SLIDE_SECONDS)
+            .min((TOAST_SECONDS - t) / TOAST_SLIDE_SECONDS)
+            .clamp(0.0, 1.0);
+        let x = screen.0 - (w + margin) * shown;
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest_rect(graphics::Rect::new(x, margin, w, h))
+                .color([0.0, 0.0, 0.0, 0.8]),
+        );
+        let mut text = graphics::Text::new(toast.as_str());
+        text.set_scale(28.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([x + w / 2.0, margin + h / 2.0])
+                .offset([0.5, 0.5])
+                .color(graphics::Color::WHITE),
+        );
+    }
+}
+
+// 現在時刻をUNIX時間の秒で返す
+pub fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// UNIX時間の秒をファイル名に使える「YYYY-MM-DD_HH-MM-SS」(UTC)に変換
+pub fn file_timestamp(timestamp: u64) -> String {
+    format!(
+        "{}-{:02}",
+        format_timestamp(timestamp)
+            .replace(' ', "_")
+            .replace(':', "-"),
+        timestamp % 60
+    )
+}
+
+// UNIX時間の秒を「YYYY-MM-DD HH:MM」(UTC)に変換
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+    // 1970-01-01からの日数を年月日に変換(Howard Hinnantのcivil_from_days)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(table: &[&HighScoreEntry]) -> Vec<u32> {
+        table.iter().map(|e| e.score).collect()
+    }
+
+    #[test]
+    fn insert_keeps_the_top_scores_in_order() {
+        let mut high_scores = HighScores::default();
+        let mode = GameMode::Classic;
+        // 10, 20, ..., 100の順に記録すると毎回1位
+        for score in 1..=HIGH_SCORE_COUNT as u32 {
+            assert_eq!(
+                high_scores.insert(mode, score * 10, 0, "Normal", "A"),
+                Some(0)
+            );
+        }
+        // 同じスコアは先に記録した方が上(100〜50の6件の下)
+        assert_eq!(high_scores.insert(mode, 50, 0, "Normal", "B"), Some(6));
+        let table = high_scores.table(mode, 0);
+        assert_eq!(table.len(), HIGH_SCORE_COUNT);
+        assert_eq!(table[6].name, "B");
+        // 一番低いスコアはあふれて消える
+        assert_eq!(table.last().map(|e| e.score), Some(20));
+        assert_eq!(high_scores.insert(mode, 5, 0, "Normal", "C"), None);
+        assert!(scores(&high_scores.table(mode, 0)).is_sorted_by(|a, b| a >= b));
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        // うるう年の2月29日
+        assert_eq!(format_timestamp(951_827_696), "2000-02-29 12:34");
+        assert_eq!(file_timestamp(951_827_696), "2000-02-29_12-34-56");
+    }
+}
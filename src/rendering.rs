@@ -0,0 +1,587 @@
+use ggez::{graphics, Context, GameResult};
+
+use crate::challenge::{Challenge, ChallengeStatus};
+use crate::config::{
+    config, CHECKER_COLOR, DEATH_EFFECT_SECONDS, GRID_LINE_COLOR, GRID_LINE_WIDTH, OUTLINE_COLOR,
+    OUTLINE_WIDTH, SHAKE_PIXELS,
+};
+use crate::food::ActiveEffect;
+use crate::grid::{Board, Direction, GridPosition};
+use crate::records::{format_timestamp, HighScoreEntry};
+use crate::settings::Background;
+use crate::theme::Theme;
+
+// rectを中心はそのままにscale倍に縮める
+pub fn scale_rect(rect: graphics::Rect, scale: f32) -> graphics::Rect {
+    graphics::Rect::new(
+        rect.x + rect.w * (1.0 - scale) / 2.0,
+        rect.y + rect.h * (1.0 - scale) / 2.0,
+        rect.w * scale,
+        rect.h * scale,
+    )
+}
+
+// マスの四角形を溜めておき、1回のdrawでまとめて描画する
+// 白い1x1のテクスチャのInstanceArrayで、毎フレームclearしてから四角形を追加し直す
+pub struct CellBatch {
+    instances: graphics::InstanceArray,
+}
+
+impl CellBatch {
+    pub fn new(ctx: &Context) -> Self {
+        let white = graphics::Image::from_color(ctx, 1, 1, Some(graphics::Color::WHITE));
+        CellBatch {
+            instances: graphics::InstanceArray::new(ctx, white),
+        }
+    }
+
+    // 前のフレームで追加した四角形を消す
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+
+    // rectをcolorで塗る四角形を追加する
+    pub fn push(&mut self, rect: graphics::Rect, color: [f32; 4]) {
+        self.instances
+            .push(graphics::DrawParam::new().dest_rect(rect).color(color));
+    }
+
+    // 追加した四角形の数
+    pub fn cell_count(&self) -> usize {
+        self.instances.instances().len()
+    }
+
+    // 追加した四角形を追加した順に1回で描画する
+    // (同じフレームで2回描画すると前の分も後の内容になるので1フレームに1回だけ)
+    pub fn draw(&self, canvas: &mut graphics::Canvas) {
+        canvas.draw(&self.instances, graphics::DrawParam::new());
+    }
+
+    // 追加した四角形をscale倍に拡大してdestの位置に描画する
+    pub fn draw_scaled(&self, canvas: &mut graphics::Canvas, dest: [f32; 2], scale: f32) {
+        canvas.draw(
+            &self.instances,
+            graphics::DrawParam::new().dest(dest).scale([scale, scale]),
+        );
+    }
+}
+
+// ウィンドウの大きさがwindowの時に使う画面の座標
+// 盤面(screen_size)を縦横比を保ったまま中央に収め、余った分は左右か上下に帯として残す
+pub fn screen_coordinates(window: (f32, f32)) -> graphics::Rect {
+    let screen = config().screen_size();
+    // 最小化中などで大きさが0ならそのまま
+    if window.0 <= 0.0 || window.1 <= 0.0 {
+        return graphics::Rect::new(0.0, 0.0, screen.0, screen.1);
+    }
+    let scale = (window.0 / screen.0).min(window.1 / screen.1);
+    let (w, h) = (window.0 / scale, window.1 / scale);
+    graphics::Rect::new(-(w - screen.0) / 2.0, -(h - screen.1) / 2.0, w, h)
+}
+
+// カメラが追う位置focus(盤面の座標)を画面の中央に映すように、画面の座標coordsをずらす
+// 盤面の端より外は映さない(盤面全体が画面に収まる向きはずらさない)
+pub fn camera_coordinates(
+    coords: graphics::Rect,
+    focus: (f32, f32),
+    board: Board,
+) -> graphics::Rect {
+    let screen = config().screen_size();
+    let board = board.pixel_size();
+    let offset = |focus: f32, screen: f32, board: f32| {
+        (focus - screen / 2.0).clamp(0.0, (board - screen).max(0.0))
+    };
+    graphics::Rect::new(
+        coords.x + offset(focus.0, screen.0, board.0),
+        coords.y + offset(focus.1, screen.1, board.1),
+        coords.w,
+        coords.h,
+    )
+}
+
+// ウィンドウの拡大率(Retinaなどの高DPIのディスプレイでは1より大きい)
+// ggezのウィンドウの大きさとマウスの位置は物理ピクセルなので、これで割って論理ピクセルにする
+pub fn scale_factor(ctx: &Context) -> f32 {
+    ctx.gfx.window().scale_factor() as f32
+}
+
+// ウィンドウ上の位置(x, y)を盤面の座標にする
+pub fn window_to_screen(window: (f32, f32), x: f32, y: f32) -> (f32, f32) {
+    let coords = screen_coordinates(window);
+    if window.0 <= 0.0 || window.1 <= 0.0 {
+        return (x, y);
+    }
+    (
+        coords.x + x * coords.w / window.0,
+        coords.y + y * coords.h / window.1,
+    )
+}
+
+// config.tomlで指定した盤面の後ろに描く画像
+pub struct BackgroundImage {
+    image: graphics::Image,
+    tiled: bool,
+}
+
+impl BackgroundImage {
+    // 指定した画像を読み込む。指定がない・読み込めなければNone(背景色で塗るだけ)
+    pub fn load(ctx: &Context) -> Option<BackgroundImage> {
+        let path = config().background_image.as_ref()?;
+        match graphics::Image::from_path(ctx, path) {
+            Ok(image) => Some(BackgroundImage {
+                image,
+                tiled: config().background_tiled,
+            }),
+            Err(e) => {
+                eprintln!("Could not load {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    // 盤面全体に描画する
+    pub fn draw(&self, canvas: &mut graphics::Canvas, board: Board) {
+        let screen = board.pixel_size();
+        let (w, h) = (self.image.width() as f32, self.image.height() as f32);
+        if self.tiled {
+            // 画像何枚分かをsrcにして、はみ出した分は繰り返すsamplerで並べる
+            canvas.set_sampler(graphics::Sampler {
+                clamp_u: graphics::ClampMode::Repeat,
+                clamp_v: graphics::ClampMode::Repeat,
+                ..graphics::Sampler::linear_clamp()
+            });
+            canvas.draw(
+                &self.image,
+                graphics::DrawParam::new().src(graphics::Rect::new(
+                    0.0,
+                    0.0,
+                    screen.0 / w,
+                    screen.1 / h,
+                )),
+            );
+            canvas.set_default_sampler();
+        } else {
+            canvas.draw(
+                &self.image,
+                graphics::DrawParam::new().scale([screen.0 / w, screen.1 / h]),
+            );
+        }
+    }
+}
+
+// 盤面の背景の模様を1つのMeshにする(Noneなら何も描かないのでNone)
+// 模様を切り替えた時と、盤面の大きさが変わった時だけ作り直せばいい
+pub fn background_mesh(
+    ctx: &Context,
+    background: Background,
+    board: Board,
+) -> GameResult<Option<graphics::Mesh>> {
+    let (columns, rows) = (board.width, board.height);
+    let (w, h) = (config().cell_size.0 as f32, config().cell_size.1 as f32);
+    let screen = board.pixel_size();
+    let mut builder = graphics::MeshBuilder::new();
+    match background {
+        Background::None => return Ok(None),
+        Background::Grid => {
+            // 画面の端には引かず、マスの境目だけに縦横の線を引く
+            for x in 1..columns {
+                let x = x as f32 * w;
+                builder.line(
+                    &[[x, 0.0], [x, screen.1]],
+                    GRID_LINE_WIDTH,
+                    GRID_LINE_COLOR.into(),
+                )?;
+            }
+            for y in 1..rows {
+                let y = y as f32 * h;
+                builder.line(
+                    &[[0.0, y], [screen.0, y]],
+                    GRID_LINE_WIDTH,
+                    GRID_LINE_COLOR.into(),
+                )?;
+            }
+        }
+        Background::Checkerboard => {
+            for x in 0..columns {
+                for y in (0..rows).filter(|y| (x + y) % 2 == 1) {
+                    builder.rectangle(
+                        graphics::DrawMode::fill(),
+                        graphics::Rect::new(x as f32 * w, y as f32 * h, w, h),
+                        CHECKER_COLOR.into(),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(Some(graphics::Mesh::from_data(ctx, builder.build())))
+}
+
+// 蛇が自分にぶつかった時の画面の揺れと赤いフラッシュ
+// startしてからDEATH_EFFECT_SECONDSの間、時間が経つほど弱くなる
+#[derive(Default)]
+pub struct DeathEffect {
+    // 残りの秒数
+    remaining: f32,
+}
+
+impl DeathEffect {
+    pub fn start(&mut self) {
+        self.remaining = DEATH_EFFECT_SECONDS;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.remaining = (self.remaining - dt).max(0.0);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0.0
+    }
+
+    // 強さ(始めが1.0で終わりが0.0)
+    fn strength(&self) -> f32 {
+        self.remaining / DEATH_EFFECT_SECONDS
+    }
+
+    // 画面の座標coordsをずらして、この後に描くものを揺らす
+    pub fn begin_shake(&self, canvas: &mut graphics::Canvas, coords: graphics::Rect) {
+        if !self.is_active() {
+            return;
+        }
+        // 縦横で違う速さで振動させる
+        let t = self.remaining;
+        let amount = SHAKE_PIXELS * self.strength();
+        let dx = (t * 90.0).sin() * amount;
+        let dy = (t * 70.0).cos() * amount;
+        canvas.set_screen_coordinates(graphics::Rect::new(
+            coords.x + dx,
+            coords.y + dy,
+            coords.w,
+            coords.h,
+        ));
+    }
+
+    // 画面の座標をcoordsに戻し、盤面全体に赤いフラッシュを重ねる
+    pub fn end_shake(&self, canvas: &mut graphics::Canvas, coords: graphics::Rect) {
+        if !self.is_active() {
+            return;
+        }
+        let screen = config().screen_size();
+        let rect = graphics::Rect::new(0.0, 0.0, screen.0, screen.1);
+        canvas.set_screen_coordinates(coords);
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new().dest_rect(rect).color([
+                1.0,
+                0.0,
+                0.0,
+                0.5 * self.strength(),
+            ]),
+        );
+    }
+}
+
+// 画面全体をcolorで薄く染める(残りremaining tickが少なくなると薄くなり、tickごとに明滅する)
+pub fn draw_tint(canvas: &mut graphics::Canvas, color: [f32; 4], remaining: u32, tick: u32) {
+    let screen = config().screen_size();
+    let fade = (remaining as f32 / 10.0).min(1.0);
+    let pulse = 0.15 + 0.05 * (tick as f32 * 0.8).sin();
+    let [r, g, b, _] = color;
+    canvas.draw(
+        &graphics::Quad,
+        graphics::DrawParam::new()
+            .dest_rect(graphics::Rect::new(0.0, 0.0, screen.0, screen.1))
+            .color([r, g, b, pulse * fade]),
+    );
+}
+
+// ヒントの矢印(posのマスにdirの方を向いた三角形)をbuilderに追加する
+pub fn draw_hint_arrow(
+    builder: &mut graphics::MeshBuilder,
+    pos: GridPosition,
+    dir: Direction,
+    color: [f32; 4],
+) -> GameResult {
+    let r = scale_rect(pos.into(), 0.7);
+    let (left, right, top, bottom) = (r.x, r.x + r.w, r.y, r.y + r.h);
+    let (cx, cy) = (r.x + r.w / 2.0, r.y + r.h / 2.0);
+    let points = match dir {
+        Direction::Up => [[cx, top], [right, bottom], [left, bottom]],
+        Direction::Down => [[cx, bottom], [left, top], [right, top]],
+        Direction::Left => [[left, cy], [right, top], [right, bottom]],
+        Direction::Right => [[right, cy], [left, bottom], [left, top]],
+    };
+    let [red, g, b, _] = color;
+    builder.polygon(graphics::DrawMode::fill(), &points, [red, g, b, 0.5].into())?;
+    Ok(())
+}
+
+// 盤面の外側からmargin周のマスをcolorで塗る(サバイバルで縮んで通れなくなったマス)
+pub fn draw_dead_zone(batch: &mut CellBatch, board: Board, margin: i16, color: [f32; 4]) {
+    if margin <= 0 {
+        return;
+    }
+    let (w, h) = board.pixel_size();
+    let x = margin as f32 * config().cell_size.0 as f32;
+    let y = margin as f32 * config().cell_size.1 as f32;
+    // 上下は端から端まで、左右はその間だけ塗る
+    batch.push(graphics::Rect::new(0.0, 0.0, w, y), color);
+    batch.push(graphics::Rect::new(0.0, h - y, w, y), color);
+    batch.push(graphics::Rect::new(0.0, y, x, h - y * 2.0), color);
+    batch.push(graphics::Rect::new(w - x, y, x, h - y * 2.0), color);
+}
+
+// 四角形を1つバッチに追加する。outlinedがtrueなら縁取りを付ける
+// 縁取りはrectの内側に収めるので、隣のセルにははみ出さない
+pub fn draw_cell(batch: &mut CellBatch, rect: graphics::Rect, color: [f32; 4], outlined: bool) {
+    if !outlined {
+        batch.push(rect, color);
+        return;
+    }
+    // 先に縁取りの色でrect全体を塗り、その内側を本来の色で塗る
+    batch.push(rect, OUTLINE_COLOR);
+    let inner = graphics::Rect::new(
+        rect.x + OUTLINE_WIDTH,
+        rect.y + OUTLINE_WIDTH,
+        (rect.w - OUTLINE_WIDTH * 2.0).max(0.0),
+        (rect.h - OUTLINE_WIDTH * 2.0).max(0.0),
+    );
+    batch.push(inner, color);
+}
+
+// 左上にスコアなどの情報を描画
+// 右上には残機を蛇のheadと同じ色の四角で並べる
+pub fn draw_hud(canvas: &mut graphics::Canvas, theme: &Theme, info: &str, lives: u32) {
+    let screen = config().screen_size();
+    let mut text = graphics::Text::new(info);
+    text.set_scale(28.0);
+    canvas.draw(
+        &text,
+        graphics::DrawParam::new()
+            .dest([10.0, 10.0])
+            .color(theme.hud),
+    );
+    let size = 20.0;
+    for i in 0..lives {
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest_rect(graphics::Rect::new(
+                    screen.0 - (size + 8.0) * (i + 1) as f32,
+                    14.0,
+                    size,
+                    size,
+                ))
+                .color(theme.snake_head),
+        );
+    }
+}
+
+// 残機の下のrow段目に、次に尻尾が減るまでの残りやスタミナ(fullnessは0.0〜1.0)を横棒で描画
+// 残りが少なくなったら赤くする
+pub fn draw_meter(canvas: &mut graphics::Canvas, theme: &Theme, row: usize, fullness: f32) {
+    let screen = config().screen_size();
+    let (width, height) = (150.0, 12.0);
+    let x = screen.0 - width - 8.0;
+    let y = 44.0 + row as f32 * (height + 6.0);
+    let mut back = theme.hud;
+    back[3] *= 0.3;
+    canvas.draw(
+        &graphics::Quad,
+        graphics::DrawParam::new()
+            .dest_rect(graphics::Rect::new(x, y, width, height))
+            .color(back),
+    );
+    let color = if fullness < 0.25 {
+        graphics::Color::RED
+    } else {
+        graphics::Color::from(theme.snake_head)
+    };
+    canvas.draw(
+        &graphics::Quad,
+        graphics::DrawParam::new()
+            .dest_rect(graphics::Rect::new(
+                x,
+                y,
+                width * fullness.clamp(0.0, 1.0),
+                height,
+            ))
+            .color(color),
+    );
+}
+
+// HUDの下に効果中のパワーアップと残り秒数を描画
+// effectsは(表示する名前の前に付ける文字列, 効果)の組
+pub fn draw_effects(
+    canvas: &mut graphics::Canvas,
+    effects: &[(String, ActiveEffect)],
+    tick_rate: u32,
+) {
+    for (i, (label, effect)) in effects.iter().enumerate() {
+        let seconds = effect.remaining as f32 / tick_rate.max(1) as f32;
+        let mut text =
+            graphics::Text::new(format!("{}{} {:.1}s", label, effect.kind.name(), seconds));
+        text.set_scale(24.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([10.0, 44.0 + 28.0 * i as f32])
+                .color(effect.kind.color()),
+        );
+    }
+}
+
+// 挑戦中の目標を画面左下に1行ずつ描画(達成は緑、失敗は赤)
+// 下端はバナーの上にそろえる
+pub fn draw_challenges(canvas: &mut graphics::Canvas, challenges: &[Box<dyn Challenge>]) {
+    let screen = config().screen_size();
+    let line_height = 28.0;
+    let top = screen.1 - 80.0 - line_height * challenges.len() as f32;
+    for (i, challenge) in challenges.iter().enumerate() {
+        let mut text = graphics::Text::new(challenge.text());
+        text.set_scale(24.0);
+        let color = match challenge.status() {
+            ChallengeStatus::InProgress => graphics::Color::WHITE,
+            ChallengeStatus::Completed => graphics::Color::GREEN,
+            ChallengeStatus::Failed => graphics::Color::RED,
+        };
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([10.0, top + line_height * i as f32])
+                .color(color),
+        );
+    }
+}
+
+// 画面全体を暗くして、linesを1行ずつ中央に描画
+pub fn draw_overlay(canvas: &mut graphics::Canvas, lines: &[String]) {
+    let screen = config().screen_size();
+    canvas.draw(
+        &graphics::Quad,
+        graphics::DrawParam::new()
+            .dest_rect(graphics::Rect::new(0.0, 0.0, screen.0, screen.1))
+            .color([0.0, 0.0, 0.0, 0.6]),
+    );
+    let line_height = 60.0;
+    // 全体の高さの半分だけ上から書き始める
+    let top = screen.1 / 2.0 - line_height * (lines.len() as f32 - 1.0) / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        let mut text = graphics::Text::new(line.as_str());
+        text.set_scale(48.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([screen.0 / 2.0, top + line_height * i as f32])
+                .offset([0.5, 0.5])
+                .color(graphics::Color::WHITE),
+        );
+    }
+}
+
+// 画面下の中央に1行のテキストを描画
+pub fn draw_banner(canvas: &mut graphics::Canvas, line: &str) {
+    let screen = config().screen_size();
+    let mut text = graphics::Text::new(line);
+    text.set_scale(36.0);
+    canvas.draw(
+        &text,
+        graphics::DrawParam::new()
+            .dest([screen.0 / 2.0, screen.1 - 40.0])
+            .offset([0.5, 0.5])
+            .color(graphics::Color::WHITE),
+    );
+}
+
+// 蛇が動き出すまでの残りの秒数を盤面の中央に大きく描画
+// titleがあればその上に描く(キャンペーンのステージ名など)
+pub fn draw_countdown(canvas: &mut graphics::Canvas, seconds: u32, title: Option<&str>) {
+    let screen = config().screen_size();
+    if let Some(title) = title {
+        let mut text = graphics::Text::new(title);
+        text.set_scale(64.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([screen.0 / 2.0, screen.1 / 2.0 - 140.0])
+                .offset([0.5, 0.5])
+                .color(graphics::Color::WHITE),
+        );
+    }
+    let mut text = graphics::Text::new(seconds.to_string());
+    text.set_scale(160.0);
+    canvas.draw(
+        &text,
+        graphics::DrawParam::new()
+            .dest([screen.0 / 2.0, screen.1 / 2.0])
+            .offset([0.5, 0.5])
+            .color(graphics::Color::WHITE),
+    );
+}
+
+// ハイスコア表を画面下部に描画し、highlightの順位だけ色を変える
+pub fn draw_high_scores(
+    canvas: &mut graphics::Canvas,
+    entries: &[&HighScoreEntry],
+    highlight: Option<usize>,
+) {
+    let screen = config().screen_size();
+    let line_height = 32.0;
+    let top = screen.1 / 2.0 + 150.0;
+    for (i, entry) in entries.iter().enumerate() {
+        let is_new = highlight == Some(i);
+        let mut text = graphics::Text::new(format!(
+            "{:2}. {:6}  {:12}  {:6}  {}{}",
+            i + 1,
+            entry.score,
+            entry.name,
+            entry.difficulty,
+            format_timestamp(entry.timestamp),
+            if is_new { "  NEW!" } else { "" }
+        ));
+        text.set_scale(28.0);
+        let color = if is_new {
+            graphics::Color::YELLOW
+        } else {
+            graphics::Color::WHITE
+        };
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([screen.0 / 2.0, top + line_height * i as f32])
+                .offset([0.5, 0.0])
+                .color(color),
+        );
+    }
+}
+
+// バトルの順位を画面の左に上から描画
+pub fn draw_standings(canvas: &mut graphics::Canvas, lines: &[String]) {
+    let line_height = 32.0;
+    for (i, line) in lines.iter().enumerate() {
+        let mut text = graphics::Text::new(line.as_str());
+        text.set_scale(28.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([20.0, 80.0 + line_height * i as f32])
+                .color(graphics::Color::WHITE),
+        );
+    }
+}
+
+// オンラインのランキングを画面上の中央に描画
+pub fn draw_leaderboard(canvas: &mut graphics::Canvas, lines: &[String]) {
+    let screen = config().screen_size();
+    let line_height = 32.0;
+    for (i, line) in lines.iter().enumerate() {
+        let mut text = graphics::Text::new(line.as_str());
+        text.set_scale(28.0);
+        canvas.draw(
+            &text,
+            graphics::DrawParam::new()
+                .dest([screen.0 / 2.0, 20.0 + line_height * i as f32])
+                .offset([0.5, 0.0])
+                .color(graphics::Color::WHITE),
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use ggez::GameResult;
+
+use std::path::Path;
+
+use crate::food::{Food, PowerUp};
+use crate::grid::Direction;
+use crate::mode::GameMode;
+use crate::script::ScriptSource;
+use crate::settings::Settings;
+use crate::snake::Snake;
+
+// リプレイに記録する1回分の方向入力
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ReplayInput {
+    // 入力したのはプレイ開始から何tick目か
+    pub tick: u32,
+    // 何番目の蛇への入力か
+    pub player: usize,
+    pub dir: Direction,
+}
+
+// シードと設定、tickごとの方向入力だけを記録したリプレイ
+// 同じシードで同じ入力を与えれば同じゲームを再現できる
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    // tomlの整数はi64なので、u64のシードはビットをそのまま変換して保存する
+    pub seed: i64,
+    pub difficulty: String,
+    pub level: String,
+    pub players: usize,
+    pub opponent: bool,
+    // キャンペーンのゲームか(levelは最初のステージ)
+    #[serde(default)]
+    pub campaign: bool,
+    // ゲームのモード
+    #[serde(default)]
+    pub mode: GameMode,
+    pub food_count: usize,
+    pub lives: u32,
+    pub max_tick_rate: u32,
+    pub grid_size: (i16, i16),
+    // 1人目の蛇の最後のスコア
+    #[serde(default)]
+    pub score: u32,
+    pub inputs: Vec<ReplayInput>,
+    // ゲームで実行していたスクリプト
+    #[serde(default)]
+    pub scripts: Vec<ScriptSource>,
+}
+
+impl Replay {
+    // 入力以外の設定とシードが同じ(同じ盤面とfoodの並びで始まる)ならtrue
+    pub fn same_game(&self, other: &Replay) -> bool {
+        self.seed == other.seed
+            && self.difficulty == other.difficulty
+            && self.level == other.level
+            && self.players == other.players
+            && self.opponent == other.opponent
+            && self.campaign == other.campaign
+            && self.mode == other.mode
+            && self.food_count == other.food_count
+            && self.lives == other.lives
+            && self.max_tick_rate == other.max_tick_rate
+            && self.grid_size == other.grid_size
+            && self.scripts == other.scripts
+    }
+
+    // リプレイファイルを読み込む
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&contents).map_err(|e| e.to_string())
+    }
+
+    // リプレイファイルに書き込む
+    pub fn save(&self, path: &Path) -> GameResult {
+        let contents =
+            toml::to_string(self).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+// 途中まで遊んだゲームのセーブデータ
+// 盤面とスコアに加えて乱数の状態も保存し、続きから全く同じように進める
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub difficulty: String,
+    pub level: String,
+    pub players: usize,
+    pub opponent: bool,
+    #[serde(default)]
+    pub mode: GameMode,
+    // キャンペーンのゲームなら、進んだステージの数と今のステージで食べたfoodの個数(levelは今のステージ)
+    #[serde(default)]
+    pub campaign: bool,
+    #[serde(default)]
+    pub stage: usize,
+    #[serde(default)]
+    pub level_food: u32,
+    // タイムアタックのゲームなら残りの秒数
+    #[serde(default)]
+    pub time_left: Option<f32>,
+    // サバイバルのゲームなら次に縮むまでの秒数と、縮んだ周の数
+    #[serde(default)]
+    pub next_shrink: Option<f32>,
+    #[serde(default)]
+    pub margin: i16,
+    // バトルのゲームなら死んだ蛇(snakesの添字)を死んだ順に
+    #[serde(default)]
+    pub standings: Vec<usize>,
+    pub food_count: usize,
+    pub max_tick_rate: u32,
+    pub grid_size: (i16, i16),
+    // シードとrngの状態はReplayと同じくi64に変換して保存する
+    pub seed: i64,
+    pub rng_state: (i64, i64),
+    pub foods_eaten: u32,
+    pub lives: u32,
+    pub tick_rate: u32,
+    pub tick: u32,
+    pub longest_snake: usize,
+    pub play_time: f64,
+    pub turned_left: bool,
+    pub snakes: Vec<Snake>,
+    pub foods: Vec<Food>,
+    pub power_up: Option<PowerUp>,
+    pub recording: Vec<ReplayInput>,
+}
+
+impl SaveGame {
+    // セーブデータを読み込む
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&contents).map_err(|e| e.to_string())
+    }
+
+    // セーブデータを書き込む
+    // tomlではテーブルの後に値を書けないので、一度Valueにして並べ替えてから文字列にする
+    pub fn save(&self, path: &Path) -> GameResult {
+        let contents = toml::Value::try_from(self)
+            .and_then(|value| toml::to_string(&value))
+            .map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+// 再生中のリプレイ
+pub struct Playback {
+    pub replay: Replay,
+    // 再生前の設定(再生が終わったら戻す)
+    pub settings: Settings,
+}
@@ -0,0 +1,279 @@
+use ggez::Context;
+use rhai::{CallFnOptions, Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::io::Read;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::config::{SCRIPTS_DIR, SCRIPT_MAX_OPERATIONS};
+
+// スクリプトでフックできるできごと
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    // 蛇がfoodを食べた(on_food_eaten)
+    FoodEaten,
+    // 1tick進んだ(on_tick。値はゲーム開始からのtick数)
+    Tick(u32),
+    // 蛇が死んだ(on_death)
+    Death,
+}
+
+impl Hook {
+    // スクリプトに書く関数の名前
+    fn function(self) -> &'static str {
+        match self {
+            Hook::FoodEaten => "on_food_eaten",
+            Hook::Tick(_) => "on_tick",
+            Hook::Death => "on_death",
+        }
+    }
+}
+
+// フックしたできごとが起きた時にスクリプトが呼んだコマンド
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    // 蛇のスコアを増やす(負の値なら減らす)(add_score(n))
+    AddScore(i32),
+    // foodをn個置く(spawn_food(n))
+    SpawnFood(u32),
+    // 1秒間のupdateの回数を変える(speed(n))
+    Speed(i32),
+}
+
+// スクリプトの名前と中身(リプレイとネットワーク対戦の設定に入れて、同じスクリプトで再現する)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptSource {
+    pub name: String,
+    pub text: String,
+}
+
+// コマンドを受け取る関数を登録したrhaiのエンジン(呼ばれたコマンドはcommandsに溜まる)
+struct Runtime {
+    engine: Engine,
+    commands: Rc<RefCell<Vec<Command>>>,
+}
+
+impl Runtime {
+    fn new() -> Self {
+        let commands: Rc<RefCell<Vec<Command>>> = Rc::default();
+        let mut engine = Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        let sink = commands.clone();
+        engine.register_fn("add_score", move |n: i64| {
+            let n = n.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+            sink.borrow_mut().push(Command::AddScore(n));
+        });
+        let sink = commands.clone();
+        engine.register_fn("spawn_food", move |n: i64| {
+            let n = n.clamp(0, u32::MAX.into()) as u32;
+            sink.borrow_mut().push(Command::SpawnFood(n));
+        });
+        let sink = commands.clone();
+        engine.register_fn("speed", move |n: i64| {
+            let n = n.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+            sink.borrow_mut().push(Command::Speed(n));
+        });
+        Runtime { engine, commands }
+    }
+}
+
+thread_local! {
+    // エンジンの準備は重いので、スレッドごとに1つだけ作って全てのスクリプトで使う
+    static RUNTIME: Runtime = Runtime::new();
+}
+
+// ゲームのルールを変えるrhaiスクリプト
+// リソースの/scripts/*.rhaiに、フックの名前の関数を書く
+//
+//     // foodを食べるたびに5点追加してfoodを1個増やす
+//     fn on_food_eaten() {
+//         add_score(5);
+//         spawn_food(1);
+//     }
+//     // 200tickごとに速くする
+//     fn on_tick(tick) {
+//         if tick % 200 == 0 { speed(1); }
+//     }
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub source: ScriptSource,
+    ast: AST,
+}
+
+impl Script {
+    // スクリプトのテキストをコンパイルする。間違いがあれば何行目かを含むエラーを返す
+    pub fn parse(name: &str, text: &str) -> Result<Script, String> {
+        let ast = RUNTIME
+            .with(|runtime| runtime.engine.compile(text))
+            .map_err(|e| e.to_string())?;
+        Ok(Script {
+            source: ScriptSource {
+                name: name.to_string(),
+                text: text.to_string(),
+            },
+            ast,
+        })
+    }
+
+    // リプレイやホストから受け取ったスクリプトを全てコンパイルする(1つでも間違いがあればエラー)
+    pub fn parse_all(sources: &[ScriptSource]) -> Result<Vec<Script>, String> {
+        sources
+            .iter()
+            .map(|source| {
+                Script::parse(&source.name, &source.text)
+                    .map_err(|e| format!("script {}: {}", source.name, e))
+            })
+            .collect()
+    }
+
+    // eventが起きた時にスクリプトが呼んだコマンド(フックの関数がなければ空)
+    // 実行中のエラーは表示して、そのフックのコマンドは全て取り消す(ゲームは続ける)
+    pub fn commands(&self, event: Hook) -> Vec<Command> {
+        let name = event.function();
+        if !self.ast.iter_functions().any(|f| f.name == name) {
+            return Vec::new();
+        }
+        RUNTIME.with(|runtime| {
+            // 関数の外に書いた文はフックのたびに実行しない
+            let options = CallFnOptions::new().eval_ast(false);
+            let mut scope = Scope::new();
+            let result = match event {
+                Hook::Tick(tick) => runtime.engine.call_fn_with_options::<rhai::Dynamic>(
+                    options,
+                    &mut scope,
+                    &self.ast,
+                    name,
+                    (i64::from(tick),),
+                ),
+                _ => runtime.engine.call_fn_with_options::<rhai::Dynamic>(
+                    options,
+                    &mut scope,
+                    &self.ast,
+                    name,
+                    (),
+                ),
+            };
+            let commands = runtime.commands.take();
+            match result {
+                Ok(_) => commands,
+                Err(e) => {
+                    eprintln!("Script {} failed in {}: {}", self.source.name, name, e);
+                    Vec::new()
+                }
+            }
+        })
+    }
+
+    // リソースの/scripts/*.rhaiを全て読み込む
+    // 読み込めないファイルはエラーを表示して飛ばす(ゲームはスクリプトなしで続ける)
+    pub fn load_all(ctx: &Context) -> Vec<Script> {
+        let mut paths: Vec<PathBuf> = match ctx.fs.read_dir(SCRIPTS_DIR) {
+            Ok(paths) => paths
+                .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        paths.sort();
+        let mut scripts = Vec::new();
+        for path in paths {
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut text = String::new();
+            let result = ctx
+                .fs
+                .open(&path)
+                .and_then(|mut file| Ok(file.read_to_string(&mut text)?))
+                .map_err(|e| e.to_string())
+                .and_then(|_| Script::parse(&name, &text));
+            match result {
+                Ok(script) => scripts.push(script),
+                Err(e) => eprintln!("Could not load script {:?}: {}", path, e),
+            }
+        }
+        scripts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_return_the_commands_they_call() {
+        let script = Script::parse(
+            "test",
+            "fn on_food_eaten() { add_score(5); spawn_food(2); }
+             fn on_death() { speed(-1); }",
+        )
+        .unwrap();
+        assert_eq!(
+            script.commands(Hook::FoodEaten),
+            vec![Command::AddScore(5), Command::SpawnFood(2)]
+        );
+        assert_eq!(script.commands(Hook::Death), vec![Command::Speed(-1)]);
+        // on_tickは書いていない
+        assert!(script.commands(Hook::Tick(1)).is_empty());
+    }
+
+    #[test]
+    fn on_tick_receives_the_tick() {
+        let script = Script::parse(
+            "test",
+            "fn on_tick(tick) { if tick % 200 == 0 { speed(1); } }",
+        )
+        .unwrap();
+        assert!(script.commands(Hook::Tick(199)).is_empty());
+        assert_eq!(script.commands(Hook::Tick(400)), vec![Command::Speed(1)]);
+    }
+
+    #[test]
+    fn top_level_statements_do_not_run_on_hooks() {
+        let script = Script::parse("test", "add_score(1);\nfn on_death() {}").unwrap();
+        assert!(script.commands(Hook::Death).is_empty());
+    }
+
+    #[test]
+    fn parse_reports_syntax_errors() {
+        let error =
+            Script::parse("test", "fn on_death() {\n  speed(1)\n  speed(2);\n}").unwrap_err();
+        assert!(error.contains("line 3"), "{}", error);
+    }
+
+    #[test]
+    fn runtime_errors_discard_the_hook_commands() {
+        let script = Script::parse(
+            "test",
+            "fn on_food_eaten() { add_score(1); unknown_function(); }
+             fn on_death() { speed(1); loop {} }
+             fn on_tick(tick) { spawn_food(1); }",
+        )
+        .unwrap();
+        assert!(script.commands(Hook::FoodEaten).is_empty());
+        // 無限ループは命令の数の上限で止まる
+        assert!(script.commands(Hook::Death).is_empty());
+        // 取り消したコマンドが次のフックに残らない
+        assert_eq!(script.commands(Hook::Tick(1)), vec![Command::SpawnFood(1)]);
+    }
+
+    #[test]
+    fn parse_all_names_the_broken_script() {
+        let sources = [
+            ScriptSource {
+                name: "good".to_string(),
+                text: "fn on_death() { add_score(1); }".to_string(),
+            },
+            ScriptSource {
+                name: "bad".to_string(),
+                text: "fn on_death( {".to_string(),
+            },
+        ];
+        assert_eq!(Script::parse_all(&sources[..1]).unwrap().len(), 1);
+        assert!(Script::parse_all(&sources)
+            .unwrap_err()
+            .starts_with("script bad"));
+    }
+}
@@ -0,0 +1,387 @@
+use serde::{Deserialize, Serialize};
+
+use ggez::{conf::FullscreenType, input::keyboard::KeyCode, Context, GameResult};
+
+use std::io::{Read, Write};
+
+use crate::config::{
+    config, DEFAULT_FOOD_COUNT, DEFAULT_LIVES, DEFAULT_MAX_TICK_RATE, DEFAULT_VOLUME, SETTINGS_PATH,
+};
+use crate::grid::{Direction, Topology};
+use crate::snake::Skin;
+use crate::theme::{Theme, ThemeName};
+
+// 再起動後も保持する設定
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // 全画面表示か
+    pub fullscreen: bool,
+    // 縁取り付きで描画するか
+    pub high_visibility: bool,
+    // 色覚の違いがあっても見分けやすい配色と形で描くか(テーマより優先)
+    pub colorblind: bool,
+    // 1秒間にupdateが呼ばれる回数の上限
+    pub max_tick_rate: u32,
+    // +/-キーで変えた1秒間のupdateの回数の増減(難易度の速さに足す)
+    pub speed_adjust: i32,
+    // 同時に置くfoodの個数
+    pub food_count: usize,
+    // 残機の数
+    pub lives: u32,
+    // 全体・BGM・効果音の音量(0〜100%)
+    pub master_volume: u32,
+    pub music_volume: u32,
+    pub sfx_volume: u32,
+    // 消音中か
+    pub muted: bool,
+    // 乱数のシード(--seed)。Noneなら毎回ランダム。設定ファイルには保存しない
+    #[serde(skip)]
+    pub seed: Option<u64>,
+    // 色のテーマ
+    pub theme: ThemeName,
+    // 盤面の背景の模様
+    pub background: Background,
+    // 1人目の蛇のbodyの塗り方
+    pub skin: Skin,
+    // 操作ごとのキー
+    pub bindings: InputBindings,
+    // 1人目の蛇がマウスカーソルのマスに向かって曲がるか(キーでも操作できる)
+    pub mouse_steering: bool,
+    // 1人目の蛇の一番近いfoodへの最短経路の最初の1歩を矢印で示すか(初心者向け)
+    pub hint_arrow: bool,
+    // ゲームが終わったら最後の数秒をGIFに書き出すか
+    pub record_gif: bool,
+    // ハイスコアに最後に入力した名前(次に表に入った時の最初の名前になる)
+    pub high_score_name: String,
+    // チュートリアルを最後まで終えたか(終えるまではEnterでチュートリアルから始める)
+    pub tutorial_done: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            fullscreen: false,
+            high_visibility: false,
+            colorblind: false,
+            max_tick_rate: DEFAULT_MAX_TICK_RATE,
+            speed_adjust: 0,
+            food_count: DEFAULT_FOOD_COUNT,
+            lives: DEFAULT_LIVES,
+            master_volume: DEFAULT_VOLUME,
+            music_volume: DEFAULT_VOLUME,
+            sfx_volume: 100,
+            muted: false,
+            seed: None,
+            theme: ThemeName::default(),
+            background: Background::default(),
+            skin: Skin::default(),
+            bindings: InputBindings::default(),
+            mouse_steering: false,
+            hint_arrow: false,
+            record_gif: false,
+            high_score_name: String::new(),
+            tutorial_done: false,
+        }
+    }
+}
+
+impl Settings {
+    // 設定ファイルを読み込む。存在しない・壊れている場合はデフォルト値
+    pub fn load(ctx: &Context) -> Self {
+        let mut contents = String::new();
+        match ctx.fs.open(SETTINGS_PATH) {
+            Ok(mut file) => match file.read_to_string(&mut contents) {
+                Ok(_) => toml::from_str(&contents).unwrap_or_default(),
+                Err(_) => Settings::default(),
+            },
+            Err(_) => Settings::default(),
+        }
+    }
+
+    // 全画面表示ならモニターの解像度いっぱい、そうでなければウィンドウ
+    pub fn fullscreen_type(&self) -> FullscreenType {
+        if self.fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Windowed
+        }
+    }
+
+    // 描画に使うテーマ(色覚モードならその配色)
+    pub fn active_theme(&self) -> Theme {
+        if self.colorblind {
+            Theme::colorblind()
+        } else {
+            self.theme.theme()
+        }
+    }
+
+    // BGMの実際の音量(0.0〜1.0)。消音中は0
+    pub fn music_gain(&self) -> f32 {
+        self.gain(self.music_volume)
+    }
+
+    // 効果音の実際の音量(0.0〜1.0)。消音中は0
+    pub fn sfx_gain(&self) -> f32 {
+        self.gain(self.sfx_volume)
+    }
+
+    fn gain(&self, volume: u32) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            (self.master_volume.min(100) * volume.min(100)) as f32 / 10000.0
+        }
+    }
+
+    // 音量の表示(「Volume 80%  Music 80%  SFX 100%」、消音中は「Volume: Muted (M)」)
+    pub fn volume_text(&self) -> String {
+        if self.muted {
+            "Volume: Muted (M)".to_string()
+        } else {
+            format!(
+                "Volume {}% (9/0)  Music {}% (,/.)  SFX {}% ([/])",
+                self.master_volume, self.music_volume, self.sfx_volume
+            )
+        }
+    }
+
+    // 設定ファイルに書き込む
+    pub fn save(&self, ctx: &Context) -> GameResult {
+        let contents =
+            toml::to_string(self).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+        let mut file = ctx.fs.create(SETTINGS_PATH)?;
+        file.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+}
+
+// 盤面の背景に描く模様(距離の目安にする)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    // 何も描かない
+    #[default]
+    None,
+    // マスの境目に薄い線
+    Grid,
+    // 1マスおきに色を変えた市松模様
+    Checkerboard,
+}
+
+impl Background {
+    pub fn name(self) -> &'static str {
+        match self {
+            Background::None => "None",
+            Background::Grid => "Grid",
+            Background::Checkerboard => "Checkerboard",
+        }
+    }
+
+    // 次の模様(Gキーで順番に切り替える)
+    pub fn next(self) -> Self {
+        match self {
+            Background::None => Background::Grid,
+            Background::Grid => Background::Checkerboard,
+            Background::Checkerboard => Background::None,
+        }
+    }
+}
+
+// キーを割り当てられるプレイ中の操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    // 2人プレイの2人目の方向(1人プレイでは1人目の方向としても使える)
+    P2Up,
+    P2Down,
+    P2Left,
+    P2Right,
+    // 一時停止と再開
+    Pause,
+}
+
+impl Action {
+    // 操作設定の画面に並べる順番
+    pub const ALL: [Action; 9] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::P2Up,
+        Action::P2Down,
+        Action::P2Left,
+        Action::P2Right,
+        Action::Pause,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "P1 Up",
+            Action::Down => "P1 Down",
+            Action::Left => "P1 Left",
+            Action::Right => "P1 Right",
+            Action::P2Up => "P2 Up",
+            Action::P2Down => "P2 Down",
+            Action::P2Left => "P2 Left",
+            Action::P2Right => "P2 Right",
+            Action::Pause => "Pause",
+        }
+    }
+
+    // 方向の操作なら(プレイヤーの番号, 方向)
+    pub fn steer(self) -> Option<(usize, Direction)> {
+        match self {
+            Action::Up => Some((0, Direction::Up)),
+            Action::Down => Some((0, Direction::Down)),
+            Action::Left => Some((0, Direction::Left)),
+            Action::Right => Some((0, Direction::Right)),
+            Action::P2Up => Some((1, Direction::Up)),
+            Action::P2Down => Some((1, Direction::Down)),
+            Action::P2Left => Some((1, Direction::Left)),
+            Action::P2Right => Some((1, Direction::Right)),
+            Action::Pause => None,
+        }
+    }
+
+    // デフォルトのキー(1人目は矢印キー、2人目はWASD)
+    pub fn default_keys(self) -> Vec<KeyCode> {
+        match self {
+            Action::Up => vec![KeyCode::Up],
+            Action::Down => vec![KeyCode::Down],
+            Action::Left => vec![KeyCode::Left],
+            Action::Right => vec![KeyCode::Right],
+            Action::P2Up => vec![KeyCode::W],
+            Action::P2Down => vec![KeyCode::S],
+            Action::P2Left => vec![KeyCode::A],
+            Action::P2Right => vec![KeyCode::D],
+            Action::Pause => vec![KeyCode::Space, KeyCode::P],
+        }
+    }
+}
+
+// 1つの操作に割り当てたキー
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    action: Action,
+    keys: Vec<KeyCode>,
+}
+
+// 操作ごとに割り当てたキー
+// 設定ファイルでは[[bindings]]の表の並びになる
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputBindings {
+    bindings: Vec<Binding>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        InputBindings {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| Binding {
+                    action: *action,
+                    keys: action.default_keys(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    // keyが割り当てられた操作
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| self.keys(*action).contains(&key))
+    }
+
+    // actionに割り当てたキー(設定ファイルにない操作はデフォルトのキー)
+    pub fn keys(&self, action: Action) -> Vec<KeyCode> {
+        self.bindings
+            .iter()
+            .find(|b| b.action == action)
+            .map_or_else(|| action.default_keys(), |b| b.keys.clone())
+    }
+
+    // actionのキーをkeyだけにする(他の操作に割り当てられていれば外す)
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        self.bindings = Action::ALL
+            .iter()
+            .map(|a| Binding {
+                action: *a,
+                keys: if *a == action {
+                    vec![key]
+                } else {
+                    self.keys(*a).into_iter().filter(|k| *k != key).collect()
+                },
+            })
+            .collect();
+    }
+
+    // 割り当てたキーの表示(「Space, P」)
+    pub fn keys_text(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            "-".to_string()
+        } else {
+            keys.iter()
+                .map(|key| format!("{:?}", key))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    }
+}
+
+// 難易度ごとのゲームの設定
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Difficulty {
+    pub name: &'static str,
+    // ゲーム開始時の速さ(configのfpsに対する%)
+    speed: u32,
+    // foodのvalue1あたりに伸びる長さ
+    pub growth_per_food: u16,
+    // 画面端を越えた時にどうなるか
+    pub topology: Topology,
+}
+
+impl Difficulty {
+    const EASY: Difficulty = Difficulty {
+        name: "Easy",
+        speed: 75,
+        growth_per_food: 1,
+        topology: Topology::Wrapping,
+    };
+    pub const NORMAL: Difficulty = Difficulty {
+        name: "Normal",
+        speed: 100,
+        growth_per_food: 1,
+        topology: Topology::Wrapping,
+    };
+    const HARD: Difficulty = Difficulty {
+        name: "Hard",
+        speed: 150,
+        growth_per_food: 2,
+        topology: Topology::Bounded,
+    };
+    // メニューで選べる順番
+    pub const ALL: [Difficulty; 3] = [Difficulty::EASY, Difficulty::NORMAL, Difficulty::HARD];
+
+    // 1秒間にupdateが呼ばれる回数(ゲーム開始時)
+    pub fn tick_rate(self) -> u32 {
+        (config().fps * self.speed / 100).max(1)
+    }
+
+    // 一覧の中で隣の難易度を返す(stepが正なら次、負なら前)
+    pub fn cycle(self, step: isize) -> Self {
+        let len = Self::ALL.len() as isize;
+        let index = Self::ALL.iter().position(|d| *d == self).unwrap_or(0) as isize;
+        Self::ALL[(index + step).rem_euclid(len) as usize]
+    }
+}
@@ -0,0 +1,125 @@
+use crate::event::GameEvent;
+use crate::food::Food;
+use crate::game_state::{GameOutcome, GameState, Scene};
+use crate::grid::{Board, Direction};
+use crate::level::{Level, Obstacle, Portal};
+use crate::records::HighScores;
+use crate::replay::Replay;
+use crate::settings::{Difficulty, Settings};
+use crate::snake::{Controller, Snake};
+
+// ggezのウィンドウを作らずにゲームを進める(CIのテストやAIの学習、ベンチマーク用)
+// 1人プレイで組み込みのステージを使い、ハイスコアなどは何も保存しない
+pub struct Simulation {
+    state: GameState,
+}
+
+impl Simulation {
+    // settingsのシード(なければランダム)とdifficultyでlevel番目の組み込みのステージを始める
+    pub fn new(settings: Settings, difficulty: Difficulty, level: usize) -> Self {
+        let levels = Level::builtin();
+        let level = level % levels.len();
+        let mut state = GameState::new(
+            settings,
+            HighScores::default(),
+            difficulty,
+            levels,
+            level,
+            1,
+            false,
+        );
+        state.headless = true;
+        state.scene = Scene::Playing;
+        Simulation { state }
+    }
+
+    // リプレイと同じ設定とシードでゲームを始め、記録された入力で進める
+    // リプレイのステージがlevelsになければNone
+    pub fn from_replay(settings: Settings, levels: Vec<Level>, replay: Replay) -> Option<Self> {
+        let mut state = GameState::new(
+            settings,
+            HighScores::default(),
+            Difficulty::NORMAL,
+            levels,
+            0,
+            1,
+            false,
+        );
+        state.headless = true;
+        state.start_playback(replay);
+        state.playback.as_ref()?;
+        Some(Simulation { state })
+    }
+
+    // 蛇を自動操作(一番近いfoodまでの最短経路)にする
+    pub fn autopilot(&mut self) {
+        for snake in &mut self.state.snakes {
+            snake.controller = Controller::Autopilot;
+        }
+        self.state.reset_controllers();
+    }
+
+    // 方向を入力(Noneなら入力なし)して1tick進める。まだゲームが続いていればtrue
+    pub fn step(&mut self, input: Option<Direction>) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        self.state.events.clear();
+        if let Some(dir) = input {
+            self.state.steer_player(0, dir);
+        }
+        self.state.update_playing();
+        // 効果音は鳴らさない
+        self.state.sounds.clear();
+        !self.is_over()
+    }
+
+    // 最後のstepで起きたできごと
+    pub fn events(&self) -> &[GameEvent] {
+        &self.state.events
+    }
+
+    pub fn snake(&self) -> &Snake {
+        &self.state.snakes[0]
+    }
+
+    pub fn foods(&self) -> &[Food] {
+        &self.state.foods
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.state.obstacles
+    }
+
+    pub fn portals(&self) -> &[Portal] {
+        &self.state.portals
+    }
+
+    // 進めたtick数
+    pub fn tick(&self) -> u32 {
+        self.state.tick
+    }
+
+    // 盤面の大きさと画面端の扱い
+    pub fn board(&self) -> Board {
+        self.state.board
+    }
+
+    // 残機
+    pub fn lives(&self) -> u32 {
+        self.state.lives
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.state.seed
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.state.scene == Scene::GameOver
+    }
+
+    // ゲームが終わった理由(終わっていなければNone)
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        self.state.outcome
+    }
+}
@@ -0,0 +1,945 @@
+use serde::{Deserialize, Serialize};
+
+use ggez::{graphics, GameResult};
+
+use std::collections::{HashMap, VecDeque};
+use std::ops::Deref;
+
+use crate::config::{
+    BOOST_DRAIN, BOOST_STAMINA, GHOST_ALPHA, INPUT_QUEUE_SIZE, SMOOTH_SEGMENT_SCALE,
+};
+use crate::food::{ActiveEffect, Food, PowerUpKind};
+use crate::grid::{Board, Direction, GridPosition, Segment, Topology};
+use crate::level::{Obstacle, Portal};
+use crate::records::Achievement;
+use crate::rendering::{draw_cell, scale_rect, CellBatch};
+use crate::sprites::Sprites;
+use crate::theme::Theme;
+
+// 食べたもの(自分かえさか、ぶつかった壁や障害物)
+// Starvedは何も食べずに尻尾が減り続けてheadだけになった(何にもぶつかっていない)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Ate {
+    Itself,
+    Food,
+    Wall,
+    Obstacle,
+    Starved,
+}
+
+// 蛇を誰が操作するか
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Controller {
+    // キーボードやゲームパッドで人が操作する
+    Human,
+    // 毎tickコンピュータが方向を決める
+    Computer,
+    // 一番近いfoodまでの最短経路を進む(デモ用)
+    Autopilot,
+}
+
+// bodyの塗り方(headからの番号でセグメントごとに色を変える)
+// Solid以外は実績を解除すると選べる
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Skin {
+    // 全て同じ色
+    #[default]
+    Solid,
+    // 尻尾に向かって暗くなる
+    Gradient,
+    // 2セグメントごとに明るさを変えた縞模様
+    Striped,
+    // セグメントごとに色相をずらした虹色
+    Rainbow,
+    // headの後ろの黄色から尻尾の暗い赤まで
+    Fire,
+}
+
+impl Skin {
+    // 選べる順番
+    pub const ALL: [Skin; 5] = [
+        Skin::Solid,
+        Skin::Gradient,
+        Skin::Striped,
+        Skin::Rainbow,
+        Skin::Fire,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Skin::Solid => "Solid",
+            Skin::Gradient => "Gradient",
+            Skin::Striped => "Striped",
+            Skin::Rainbow => "Rainbow",
+            Skin::Fire => "Fire",
+        }
+    }
+
+    // 選べるようになる実績(Noneなら最初から選べる)
+    pub fn unlocked_by(self) -> Option<Achievement> {
+        match self {
+            Skin::Solid => None,
+            Skin::Gradient => Some(Achievement::Eat100),
+            Skin::Striped => Some(Achievement::Survive5Minutes),
+            Skin::Rainbow => Some(Achievement::Length50),
+            Skin::Fire => Some(Achievement::NoLeftTurns),
+        }
+    }
+
+    // bodyのi番目(headのすぐ後ろが0)のセグメントの色。lenはbodyの長さ、baseは蛇のbodyの色
+    pub fn color(self, base: [f32; 4], i: usize, len: usize) -> [f32; 4] {
+        // headのすぐ後ろが0.0、尻尾が1.0
+        let t = if len > 1 {
+            i as f32 / (len - 1) as f32
+        } else {
+            0.0
+        };
+        let [r, g, b, a] = base;
+        match self {
+            Skin::Solid => base,
+            Skin::Gradient => {
+                let k = 1.0 - 0.6 * t;
+                [r * k, g * k, b * k, a]
+            }
+            Skin::Striped => {
+                let k = if (i / 2).is_multiple_of(2) { 1.0 } else { 0.55 };
+                [r * k, g * k, b * k, a]
+            }
+            Skin::Rainbow => Skin::hue(i as f32 * 30.0, a),
+            Skin::Fire => {
+                let (from, to, t) = if t < 0.5 {
+                    ([1.0, 0.9, 0.2], [0.9, 0.15, 0.0], t * 2.0)
+                } else {
+                    ([0.9, 0.15, 0.0], [0.35, 0.05, 0.0], t * 2.0 - 1.0)
+                };
+                [
+                    from[0] + (to[0] - from[0]) * t,
+                    from[1] + (to[1] - from[1]) * t,
+                    from[2] + (to[2] - from[2]) * t,
+                    a,
+                ]
+            }
+        }
+    }
+
+    // 色相degree(度)の鮮やかな色
+    fn hue(degree: f32, alpha: f32) -> [f32; 4] {
+        let h = degree.rem_euclid(360.0) / 60.0;
+        let x = 1.0 - (h % 2.0 - 1.0).abs();
+        let (r, g, b) = match h as u32 {
+            0 => (1.0, x, 0.0),
+            1 => (x, 1.0, 0.0),
+            2 => (0.0, 1.0, x),
+            3 => (0.0, x, 1.0),
+            4 => (x, 0.0, 1.0),
+            _ => (1.0, 0.0, x),
+        };
+        [r, g, b, alpha]
+    }
+}
+
+// 蛇のbody(headのすぐ後ろから尻尾まで)
+// マスごとのセグメントの数も覚えておき、あるマスにbodyがあるかを長さによらずすぐに調べられるようにする
+// (Ghost中は自分の体を通り抜けるので、同じマスに2つ以上のセグメントが重なることもある)
+// 保存する時はセグメントの並びだけにする
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "VecDeque<Segment>", into = "VecDeque<Segment>")]
+pub struct Body {
+    segments: VecDeque<Segment>,
+    cells: HashMap<GridPosition, u32>,
+}
+
+impl From<VecDeque<Segment>> for Body {
+    fn from(segments: VecDeque<Segment>) -> Self {
+        let mut body = Body::default();
+        for seg in segments {
+            body.push_back(seg);
+        }
+        body
+    }
+}
+
+impl From<Body> for VecDeque<Segment> {
+    fn from(body: Body) -> Self {
+        body.segments
+    }
+}
+
+// 読むだけならVecDequeとして使える(変更はマスの数も合わせるBodyのメソッドで行う)
+impl Deref for Body {
+    type Target = VecDeque<Segment>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.segments
+    }
+}
+
+impl Body {
+    // posのマスにbodyがあればtrue
+    pub fn contains(&self, pos: GridPosition) -> bool {
+        self.cells.contains_key(&pos)
+    }
+
+    pub fn push_front(&mut self, seg: Segment) {
+        self.add(seg.pos);
+        self.segments.push_front(seg);
+    }
+
+    pub fn push_back(&mut self, seg: Segment) {
+        self.add(seg.pos);
+        self.segments.push_back(seg);
+    }
+
+    pub fn pop_back(&mut self) -> Option<Segment> {
+        let seg = self.segments.pop_back()?;
+        self.remove(seg.pos);
+        Some(seg)
+    }
+
+    // 先頭からlen個だけ残して後ろを削除する
+    pub fn truncate(&mut self, len: usize) {
+        self.split_off(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.cells.clear();
+    }
+
+    // at番目から尻尾までを切り取って返す
+    pub fn split_off(&mut self, at: usize) -> VecDeque<Segment> {
+        let severed = self.segments.split_off(at.min(self.segments.len()));
+        for seg in &severed {
+            self.remove(seg.pos);
+        }
+        severed
+    }
+
+    fn add(&mut self, pos: GridPosition) {
+        *self.cells.entry(pos).or_insert(0) += 1;
+    }
+
+    fn remove(&mut self, pos: GridPosition) {
+        if let Some(count) = self.cells.get_mut(&pos) {
+            *count -= 1;
+            if *count == 0 {
+                self.cells.remove(&pos);
+            }
+        }
+    }
+}
+
+// スネーク
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snake {
+    // 頭
+    pub head: Segment,
+    // 現在の方向
+    pub dir: Direction,
+    // 体
+    pub body: Body,
+    // 最後になんの餌を食ったか
+    pub ate: Option<Ate>,
+    // 最後の更新された方向
+    last_update_dir: Direction,
+    // 次のupdate以降で順番に更新される方向(キー入力を保持)
+    next_dirs: VecDeque<Direction>,
+    // これから伸びる残りのセグメント数
+    pub pending_growth: u16,
+    // 食べたfoodのスコアの合計
+    pub score: u32,
+    // 効果中のパワーアップ
+    pub effects: Vec<ActiveEffect>,
+    // 続けて食べたfoodの数と、その最大値
+    pub combo: u32,
+    pub max_combo: u32,
+    // 最後にfoodを食べたtick
+    pub last_food_tick: Option<u32>,
+    // headとbodyの色(Noneならテーマの色)
+    head_color: Option<[f32; 4]>,
+    body_color: Option<[f32; 4]>,
+    // 操作する人(またはコンピュータ)
+    pub controller: Controller,
+    // 何も食べないと尻尾が1つ減るまでのtick数(Noneなら減らない)と、最後に食べてから・減ってからのtick数
+    #[serde(default)]
+    pub hunger_interval: Option<u32>,
+    #[serde(default)]
+    pub hunger: u32,
+    // 死んで盤面から消えたか(バトルでは他の蛇が生き残っている間も死んだままにする)
+    #[serde(default)]
+    pub dead: bool,
+    // 上下左右の入力が逆になっている残りのtick数(混乱のfoodを食べた後)
+    #[serde(default)]
+    pub confusion: u32,
+    // スピードを上げている間か(この蛇だけ1tickに2マス進む)と、使ったスタミナ(0なら満タン)
+    #[serde(default)]
+    pub boosting: bool,
+    #[serde(default)]
+    pub stamina_used: u32,
+}
+
+impl Snake {
+    pub fn new(pos: GridPosition, board: Board) -> Self {
+        Snake::facing(pos, Direction::Right, board)
+    }
+
+    // dirの方向を向いた蛇を作成(bodyはheadの後ろ)
+    pub fn facing(pos: GridPosition, dir: Direction, board: Board) -> Self {
+        let mut body = Body::default();
+        // bosy要素を末尾に追加
+        body.push_back(Segment::new(Self::behind(pos, dir, board)));
+        Snake {
+            head: Segment::new(pos),
+            dir,
+            last_update_dir: dir,
+            body,
+            ate: None,
+            next_dirs: VecDeque::new(),
+            pending_growth: 0,
+            score: 0,
+            effects: Vec::new(),
+            combo: 0,
+            max_combo: 0,
+            last_food_tick: None,
+            controller: Controller::Human,
+            head_color: None,
+            body_color: None,
+            hunger_interval: None,
+            hunger: 0,
+            dead: false,
+            confusion: 0,
+            boosting: false,
+            stamina_used: 0,
+        }
+    }
+
+    // 色を変えた蛇を返す
+    pub fn with_colors(mut self, head_color: [f32; 4], body_color: [f32; 4]) -> Self {
+        self.head_color = Some(head_color);
+        self.body_color = Some(body_color);
+        self
+    }
+
+    // コンピュータが操作する蛇にする
+    pub fn computer(mut self) -> Self {
+        self.controller = Controller::Computer;
+        self
+    }
+
+    // dirを向いたheadのすぐ後ろのマス(盤面の端なら反対側に回り込む)
+    pub fn behind(pos: GridPosition, dir: Direction, board: Board) -> GridPosition {
+        let board = Board {
+            topology: Topology::Wrapping,
+            ..board
+        };
+        GridPosition::new_from_move(pos, dir.inverse(), board).unwrap_or(pos)
+    }
+
+    // スコアと色はそのままで、posからdirを向いて最初の長さでやり直す
+    pub fn respawn(&mut self, pos: GridPosition, dir: Direction, board: Board) {
+        *self = Snake {
+            score: self.score,
+            max_combo: self.max_combo,
+            head_color: self.head_color,
+            body_color: self.body_color,
+            controller: self.controller,
+            hunger_interval: self.hunger_interval,
+            ..Snake::facing(pos, dir, board)
+        };
+    }
+
+    // kindのパワーアップが効果中ならtrue
+    pub fn has_effect(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    // kindのパワーアップが効果中なら使い切ってtrueを返す
+    pub fn consume_effect(&mut self, kind: PowerUpKind) -> bool {
+        let had = self.has_effect(kind);
+        self.effects.retain(|effect| effect.kind != kind);
+        had
+    }
+
+    // 入力された方向を実際に曲がる方向にする(混乱中は上下左右が逆になる)
+    pub fn input_dir(&self, dir: Direction) -> Direction {
+        if self.confusion > 0 {
+            dir.inverse()
+        } else {
+            dir
+        }
+    }
+
+    // 入力された方向に向きを変える
+    // 向きが変わる(または変わる予定になった)ならtrue
+    pub fn steer(&mut self, dir: Direction) -> bool {
+        // If it succeeds, we check if a new direction has already been set
+        // and make sure the new direction is different then `snake.dir`
+        if self.dir != self.last_update_dir || !self.next_dirs.is_empty() {
+            // 最後に入力した方向と同じか逆なら無視し、溜めすぎた入力も捨てる
+            let last = self.next_dirs.back().copied().unwrap_or(self.dir);
+            if dir != last && dir.inverse() != last && self.next_dirs.len() < INPUT_QUEUE_SIZE {
+                self.next_dirs.push_back(dir);
+                return true;
+            }
+        } else if dir.inverse() != self.last_update_dir && dir != self.dir {
+            // If no new direction has been set and the direction is not the inverse
+            // of the `last_update_dir`, then set the snake's new direction to be the
+            // direction the user pressed.
+            self.dir = dir;
+            return true;
+        }
+        false
+    }
+
+    // 末尾からcount個のbodyを削除し、これから伸びる分も取り消す
+    // bodyがcount個以下ならfalseを返して何もしない
+    pub fn shrink(&mut self, count: usize) -> bool {
+        if self.body.len() <= count {
+            return false;
+        }
+        self.body.truncate(self.body.len() - count);
+        self.pending_growth = 0;
+        true
+    }
+
+    // 次に尻尾が減るまでの残りの割合(満腹なら1.0、減らない蛇はNone)
+    pub fn fullness(&self) -> Option<f32> {
+        let interval = self.hunger_interval?;
+        Some(1.0 - self.hunger as f32 / interval.max(1) as f32)
+    }
+
+    // スタミナの残りの割合(満タンなら1.0)
+    pub fn stamina(&self) -> f32 {
+        1.0 - self.stamina_used as f32 / BOOST_STAMINA as f32
+    }
+
+    // boostが押されていてスタミナが残っていればスピードを上げ、スタミナを減らす
+    // 上げていない間は少しずつ回復する
+    pub fn update_boost(&mut self, boost: bool) {
+        self.boosting = boost && self.stamina_used + BOOST_DRAIN <= BOOST_STAMINA;
+        if self.boosting {
+            self.stamina_used += BOOST_DRAIN;
+        } else {
+            self.stamina_used = self.stamina_used.saturating_sub(1);
+        }
+    }
+
+    // 何も食べずにhunger_intervalのtick数が経ったら尻尾を1つ減らす(伸びる分が残っていればそれを減らす)
+    // 減らす尻尾がなければAte::Starved
+    fn starve(&mut self) {
+        let Some(interval) = self.hunger_interval else {
+            return;
+        };
+        if self.ate == Some(Ate::Food) {
+            self.hunger = 0;
+            return;
+        }
+        self.hunger += 1;
+        if self.hunger < interval {
+            return;
+        }
+        self.hunger = 0;
+        if self.pending_growth > 0 {
+            self.pending_growth -= 1;
+        } else if self.body.pop_back().is_none() && self.ate.is_none() {
+            self.ate = Some(Ate::Starved);
+        }
+    }
+
+    // ヘッドの位置にfoodがあったらtrue
+    fn eats(&self, foods: &[Food]) -> bool {
+        foods.iter().any(|food| self.head.pos == food.pos)
+    }
+
+    // 最後のupdateで進んだ方向
+    pub fn last_update_dir(&self) -> Direction {
+        self.last_update_dir
+    }
+
+    // 次のupdateで向きを変える方向(入力が溜まっていなければNone)
+    pub fn next_dir(&self) -> Option<Direction> {
+        self.next_dirs.front().copied()
+    }
+
+    // headを含めた長さ
+    pub(crate) fn len(&self) -> usize {
+        self.body.len() + 1
+    }
+
+    // headから尻尾まで順に全てのマスの位置
+    pub fn segments(&self) -> impl Iterator<Item = GridPosition> + '_ {
+        std::iter::once(self.head.pos).chain(self.body.iter().map(|seg| seg.pos))
+    }
+
+    // posのマスにheadかbodyがあればtrue
+    pub fn occupies(&self, pos: GridPosition) -> bool {
+        self.head.pos == pos || self.body.contains(pos)
+    }
+
+    // ヘッドの位置がbodyのどこかと同じ位置にあったらtrue
+    fn eats_self(&self) -> bool {
+        self.body.contains(self.head.pos)
+    }
+
+    // boardがBoundedなら画面端を越えずにAte::Wallになる
+    // ワープマスに入ったらもう片方のマスに移る
+    pub fn update(
+        &mut self,
+        foods: &[Food],
+        board: Board,
+        obstacles: &[Obstacle],
+        portals: &[Portal],
+    ) {
+        // nextdirsに値が溜まっている時
+        if self.last_update_dir == self.dir {
+            // 逆向きでない方向が見つかるまで先頭から取り出して進行方向にする
+            while let Some(dir) = self.next_dirs.pop_front() {
+                if dir.inverse() != self.dir {
+                    self.dir = dir;
+                    break;
+                }
+            }
+        }
+        let old: Vec<GridPosition> = self.segments().collect();
+        // 新しいヘッドの位置に今のヘッド位置 + 方向
+        // 壁にぶつかる場合は動かない
+        let Some(new_head_pos) = GridPosition::new_from_move(self.head.pos, self.dir, board) else {
+            self.ate = Some(Ate::Wall);
+            self.set_prev(&[]);
+            return;
+        };
+        // ヘッド位置更新
+        let new_head = Segment::new(Portal::warp(portals, new_head_pos));
+        // bodyの先頭にヘッドを追加
+        self.body.push_front(self.head);
+        // headにnew_headを格納
+        self.head = new_head;
+        // 何か食べているかの判定
+        if obstacles.iter().any(|o| o.pos == self.head.pos) {
+            self.ate = Some(Ate::Obstacle);
+        } else if self.eats_self() {
+            self.ate = Some(Ate::Itself);
+        } else if self.eats(foods) {
+            self.ate = Some(Ate::Food);
+        } else {
+            self.ate = None;
+        }
+        // 伸びる分が残っていればそのまま、なければ末尾のbodyを削除
+        if self.pending_growth > 0 {
+            self.pending_growth -= 1;
+        } else {
+            self.body.pop_back();
+        }
+        self.starve();
+        // last_update_dirにdirを格納
+        self.last_update_dir = self.dir;
+        self.set_prev(&old);
+    }
+
+    // 描画で補間するため、headから順にセグメントごとに同じ順番の前のマスを覚える
+    // (伸びて増えた尻尾など、前のマスがないセグメントは補間しない)
+    fn set_prev(&mut self, old: &[GridPosition]) {
+        let segments = std::iter::once(&mut self.head).chain(self.body.segments.iter_mut());
+        for (i, seg) in segments.enumerate() {
+            seg.prev = old.get(i).copied();
+        }
+    }
+
+    // 描画するheadとbodyの色(色を変えていなければthemeの色)
+    // パワーアップの効果中はheadを最後に拾ったものの色にし、Ghostならbodyを半透明にする
+    pub fn colors(&self, theme: &Theme) -> ([f32; 4], [f32; 4]) {
+        let mut body_color = self.body_color.unwrap_or(theme.snake_body);
+        if self.has_effect(PowerUpKind::Ghost) {
+            body_color[3] = 0.4;
+        }
+        let head_color = self
+            .effects
+            .last()
+            .map_or(self.head_color.unwrap_or(theme.snake_head), |effect| {
+                effect.kind.color()
+            });
+        (head_color, body_color)
+    }
+
+    // bodyのi番目のセグメントの色(skinで塗り分け、透明度はbody_colorのまま)
+    fn segment_color(&self, skin: Skin, body_color: [f32; 4], i: usize) -> [f32; 4] {
+        let mut color = skin.color(body_color, i, self.body.len());
+        color[3] = body_color[3];
+        color
+    }
+
+    // スネークの四角形をbatchに追加する
+    // alphaは前のupdateから次のupdateまでの経過の割合(セグメントを前のマスとの間に描く)
+    pub fn draw(
+        &self,
+        batch: &mut CellBatch,
+        theme: &Theme,
+        skin: Skin,
+        outlined: bool,
+        alpha: f32,
+    ) {
+        let (head_color, body_color) = self.colors(theme);
+        for (i, seg) in self.body.iter().enumerate() {
+            // body分描画
+            let color = self.segment_color(skin, body_color, i);
+            draw_cell(batch, seg.rect(alpha), color, outlined);
+        }
+        // head描画(形で見分けるテーマでは縁取る)
+        draw_cell(
+            batch,
+            self.head.rect(alpha),
+            head_color,
+            outlined || theme.shapes,
+        );
+    }
+
+    // 自分の一番いいゲームを再生する蛇として、head・bodyともcolorの半透明で描画
+    pub fn draw_ghost(&self, batch: &mut CellBatch, color: [f32; 4], alpha: f32) {
+        let [r, g, b, _] = color;
+        let color = [r, g, b, GHOST_ALPHA];
+        for seg in self.body.iter() {
+            batch.push(seg.rect(alpha), color);
+        }
+        batch.push(self.head.rect(alpha), color);
+    }
+}
+
+impl Snake {
+    // 隣り合ったマスfromからtoへの方向(ワープで飛んだ場合はNone)
+    fn direction_to(from: GridPosition, to: GridPosition, board: Board) -> Option<Direction> {
+        Direction::ALL
+            .into_iter()
+            .find(|dir| GridPosition::new_from_move(from, *dir, board) == Some(to))
+    }
+
+    // スネークをbuilderのMeshに追加する
+    // bodyはセルより少し小さい角の丸い四角にしてセグメントの間にすき間を空け、headはセルいっぱいの丸にする
+    pub fn draw_mesh(
+        &self,
+        builder: &mut graphics::MeshBuilder,
+        theme: &Theme,
+        skin: Skin,
+        alpha: f32,
+    ) -> GameResult {
+        let (head_color, body_color) = self.colors(theme);
+        for (i, seg) in self.body.iter().enumerate() {
+            let rect = scale_rect(seg.rect(alpha), SMOOTH_SEGMENT_SCALE);
+            let radius = rect.w.min(rect.h) * 0.3;
+            builder.rounded_rectangle(
+                graphics::DrawMode::fill(),
+                rect,
+                radius,
+                self.segment_color(skin, body_color, i).into(),
+            )?;
+        }
+        let head = self.head.rect(alpha);
+        builder.circle(
+            graphics::DrawMode::fill(),
+            [head.x + head.w / 2.0, head.y + head.h / 2.0],
+            head.w.min(head.h) / 2.0,
+            0.5,
+            head_color.into(),
+        )?;
+        Ok(())
+    }
+
+    // スネークをテクスチャで描画する
+    // セグメントごとに前後のセグメントの位置から頭・胴体・曲がり角・尻尾の画像と向きを選ぶ
+    pub fn draw_sprites(
+        &self,
+        canvas: &mut graphics::Canvas,
+        sprites: &Sprites,
+        theme: &Theme,
+        skin: Skin,
+        alpha: f32,
+        board: Board,
+    ) {
+        let (head_color, body_color) = self.colors(theme);
+        let positions: Vec<GridPosition> = self.segments().collect();
+        for (i, seg) in self.body.iter().enumerate() {
+            // positionsではheadの分だけ1つずれる
+            let pos = positions[i + 1];
+            // headの方向(わからなければ進んでいる方向の後ろから来たとする)
+            let front = Self::direction_to(pos, positions[i], board).unwrap_or(self.dir);
+            let (image, rotation) = match positions.get(i + 2) {
+                Some(next) => {
+                    let back = Self::direction_to(pos, *next, board).unwrap_or(front.inverse());
+                    sprites.body_piece(front, back)
+                }
+                None => (&sprites.tail, Sprites::rotation(front)),
+            };
+            let color = self.segment_color(skin, body_color, i);
+            Sprites::draw(canvas, image, seg.rect(alpha), rotation, color);
+        }
+        Sprites::draw(
+            canvas,
+            &sprites.head,
+            self.head.rect(alpha),
+            Sprites::rotation(self.dir),
+            head_color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::food::FoodKind;
+
+    const SIZE: (i16, i16) = (10, 8);
+
+    fn wrapping() -> Board {
+        Board::new(SIZE, Topology::Wrapping)
+    }
+
+    fn bounded() -> Board {
+        Board::new(SIZE, Topology::Bounded)
+    }
+
+    // 何もない盤面で1tick進める
+    fn step(snake: &mut Snake, board: Board) {
+        snake.update(&[], board, &[], &[]);
+    }
+
+    #[test]
+    fn wraps_to_the_opposite_edge() {
+        let mut snake = Snake::facing(
+            GridPosition::new(SIZE.0 - 1, 3),
+            Direction::Right,
+            wrapping(),
+        );
+        step(&mut snake, wrapping());
+        assert_eq!(snake.head.pos, GridPosition::new(0, 3));
+        assert!(snake.ate.is_none());
+
+        let mut snake = Snake::facing(GridPosition::new(4, 0), Direction::Up, wrapping());
+        step(&mut snake, wrapping());
+        assert_eq!(snake.head.pos, GridPosition::new(4, SIZE.1 - 1));
+    }
+
+    #[test]
+    fn body_starts_behind_the_head_on_the_given_board() {
+        // 盤面の左端で右を向くと、bodyはその盤面の右端に回り込む
+        let snake = Snake::facing(GridPosition::new(0, 3), Direction::Right, bounded());
+        assert_eq!(snake.body[0].pos, GridPosition::new(SIZE.0 - 1, 3));
+
+        let snake = Snake::facing(GridPosition::new(4, SIZE.1 - 1), Direction::Up, wrapping());
+        assert_eq!(snake.body[0].pos, GridPosition::new(4, 0));
+    }
+
+    #[test]
+    fn hits_the_wall_when_bounded() {
+        let start = GridPosition::new(SIZE.0 - 1, 3);
+        let mut snake = Snake::facing(start, Direction::Right, bounded());
+        step(&mut snake, bounded());
+        assert!(matches!(snake.ate, Some(Ate::Wall)));
+        assert_eq!(snake.head.pos, start);
+    }
+
+    #[test]
+    fn keeps_its_length_without_growth() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        for _ in 0..5 {
+            step(&mut snake, wrapping());
+        }
+        assert_eq!(snake.len(), 2);
+        assert_eq!(snake.head.pos, GridPosition::new(7, 2));
+        assert_eq!(snake.body[0].pos, GridPosition::new(6, 2));
+    }
+
+    #[test]
+    fn remembers_the_previous_cell_of_each_segment() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        snake.pending_growth = 1;
+        step(&mut snake, wrapping());
+        assert_eq!(snake.head.prev, Some(GridPosition::new(2, 2)));
+        assert_eq!(snake.body[0].prev, Some(GridPosition::new(1, 2)));
+        // 伸びて増えた尻尾は補間しない
+        assert_eq!(snake.body[1].prev, None);
+
+        let start = GridPosition::new(SIZE.0 - 1, 3);
+        let mut snake = Snake::facing(start, Direction::Right, bounded());
+        step(&mut snake, bounded());
+        assert_eq!(snake.head.prev, None);
+    }
+
+    #[test]
+    fn grows_one_segment_per_tick_of_pending_growth() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        snake.pending_growth = 2;
+        let lengths: Vec<usize> = (0..4)
+            .map(|_| {
+                step(&mut snake, wrapping());
+                snake.len()
+            })
+            .collect();
+        assert_eq!(lengths, vec![3, 4, 4, 4]);
+        assert_eq!(snake.pending_growth, 0);
+    }
+
+    #[test]
+    fn shrink_keeps_at_least_one_body_segment() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        snake.pending_growth = 3;
+        for _ in 0..3 {
+            step(&mut snake, wrapping());
+        }
+        assert!(!snake.shrink(4));
+        assert!(snake.shrink(2));
+        assert_eq!(snake.len(), 3);
+    }
+
+    #[test]
+    fn starves_after_losing_every_body_segment() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        snake.hunger_interval = Some(2);
+        step(&mut snake, wrapping());
+        assert_eq!(snake.len(), 2);
+        step(&mut snake, wrapping());
+        assert_eq!(snake.len(), 1);
+        assert!(snake.ate.is_none());
+        step(&mut snake, wrapping());
+        step(&mut snake, wrapping());
+        assert_eq!(snake.ate, Some(Ate::Starved));
+    }
+
+    #[test]
+    fn eating_resets_hunger() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        snake.hunger_interval = Some(2);
+        step(&mut snake, wrapping());
+        assert_eq!(snake.hunger, 1);
+        let food = Food::new(GridPosition::new(4, 2), 1, FoodKind::Normal);
+        snake.update(&[food], wrapping(), &[], &[]);
+        assert_eq!(snake.ate, Some(Ate::Food));
+        assert_eq!(snake.hunger, 0);
+        assert_eq!(snake.fullness(), Some(1.0));
+    }
+
+    #[test]
+    fn keeps_track_of_occupied_cells() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        snake.pending_growth = 2;
+        for _ in 0..3 {
+            step(&mut snake, wrapping());
+        }
+        assert!(snake.occupies(GridPosition::new(5, 2)));
+        assert!(snake.occupies(GridPosition::new(2, 2)));
+        assert!(!snake.occupies(GridPosition::new(1, 2)));
+        let severed = snake.body.split_off(1);
+        assert_eq!(severed.len(), 2);
+        assert!(snake.body.contains(GridPosition::new(4, 2)));
+        assert!(!snake.body.contains(GridPosition::new(3, 2)));
+    }
+
+    #[test]
+    fn boosting_drains_stamina_until_empty() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        for _ in 0..BOOST_STAMINA / BOOST_DRAIN {
+            snake.update_boost(true);
+            assert!(snake.boosting);
+        }
+        assert_eq!(snake.stamina(), 0.0);
+        snake.update_boost(true);
+        assert!(!snake.boosting);
+        assert_eq!(snake.stamina_used, BOOST_STAMINA - 1);
+    }
+
+    #[test]
+    fn detects_running_into_itself() {
+        let mut snake = Snake::new(GridPosition::new(5, 5), wrapping());
+        snake.pending_growth = 4;
+        step(&mut snake, wrapping());
+        for dir in [Direction::Down, Direction::Left, Direction::Up] {
+            assert!(snake.steer(dir));
+            step(&mut snake, wrapping());
+        }
+        assert_eq!(snake.head.pos, GridPosition::new(5, 5));
+        assert!(matches!(snake.ate, Some(Ate::Itself)));
+    }
+
+    #[test]
+    fn detects_food_and_obstacles() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        let foods = [Food::new(GridPosition::new(3, 2), 1, FoodKind::Normal)];
+        snake.update(&foods, wrapping(), &[], &[]);
+        assert!(matches!(snake.ate, Some(Ate::Food)));
+
+        let obstacles = [Obstacle::new(GridPosition::new(4, 2))];
+        snake.update(&[], wrapping(), &obstacles, &[]);
+        assert!(matches!(snake.ate, Some(Ate::Obstacle)));
+    }
+
+    #[test]
+    fn ignores_reversing_into_itself() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        assert!(!snake.steer(Direction::Left));
+        assert!(!snake.steer(Direction::Right));
+        step(&mut snake, wrapping());
+        assert_eq!(snake.dir, Direction::Right);
+    }
+
+    #[test]
+    fn buffers_turns_pressed_within_one_tick() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        // 1つ目はすぐに向きが変わり、2つ目からは次のupdate以降に回される
+        assert!(snake.steer(Direction::Up));
+        assert!(snake.steer(Direction::Left));
+        // 最後に入力した方向と同じか逆なら溜めない
+        assert!(!snake.steer(Direction::Left));
+        assert!(!snake.steer(Direction::Right));
+        assert_eq!(snake.dir, Direction::Up);
+        assert_eq!(snake.next_dirs, VecDeque::from([Direction::Left]));
+
+        step(&mut snake, wrapping());
+        assert_eq!(snake.head.pos, GridPosition::new(2, 1));
+        step(&mut snake, wrapping());
+        assert_eq!(snake.head.pos, GridPosition::new(1, 1));
+        assert!(snake.next_dirs.is_empty());
+    }
+
+    #[test]
+    fn drops_turns_beyond_the_input_queue() {
+        let mut snake = Snake::new(GridPosition::new(2, 2), wrapping());
+        assert!(snake.steer(Direction::Up));
+        let turns = [
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+        ];
+        let accepted = turns.iter().filter(|dir| snake.steer(**dir)).count();
+        assert_eq!(accepted, INPUT_QUEUE_SIZE);
+        assert_eq!(snake.next_dirs.len(), INPUT_QUEUE_SIZE);
+    }
+
+    #[test]
+    fn skins_color_segments_from_head_to_tail() {
+        let base = [0.5, 0.5, 0.5, 1.0];
+        assert_eq!(Skin::Solid.color(base, 3, 5), base);
+        // 尻尾に向かって暗くなる
+        let head = Skin::Gradient.color(base, 0, 5);
+        let tail = Skin::Gradient.color(base, 4, 5);
+        assert_eq!(head, base);
+        assert!(tail[0] < head[0]);
+        // 2セグメントごとに明るさが変わる
+        assert_eq!(
+            Skin::Striped.color(base, 0, 5),
+            Skin::Striped.color(base, 1, 5)
+        );
+        assert_ne!(
+            Skin::Striped.color(base, 1, 5),
+            Skin::Striped.color(base, 2, 5)
+        );
+        // 虹色は隣と違う色で、12セグメントで一周する
+        assert_ne!(
+            Skin::Rainbow.color(base, 0, 20),
+            Skin::Rainbow.color(base, 1, 20)
+        );
+        assert_eq!(
+            Skin::Rainbow.color(base, 0, 20),
+            Skin::Rainbow.color(base, 12, 20)
+        );
+    }
+}
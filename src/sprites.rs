@@ -0,0 +1,102 @@
+use ggez::{graphics, Context};
+
+use std::f32::consts::FRAC_PI_2;
+
+use crate::config::SPRITES_DIR;
+use crate::grid::Direction;
+
+// 蛇とfoodのテクスチャ
+// 蛇の画像は白黒で、描画する時に蛇の色を掛ける
+pub struct Sprites {
+    // 右を向いた頭
+    pub head: graphics::Image,
+    // 左右につながった胴体
+    pub body: graphics::Image,
+    // 左と下につながった曲がり角
+    pub corner: graphics::Image,
+    // 右につながった尻尾の先
+    pub tail: graphics::Image,
+    // 普通のfood
+    pub apple: graphics::Image,
+}
+
+impl Sprites {
+    // リソースの/sprites/<名前>.pngを読み込む
+    // 1つでも読み込めなければNone(今まで通り四角で描画する)
+    pub fn load(ctx: &Context) -> Option<Sprites> {
+        let load = |name: &str| {
+            let path = format!("{}/{}.png", SPRITES_DIR, name);
+            match graphics::Image::from_path(ctx, &path) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    eprintln!("Could not load {}: {}", path, e);
+                    None
+                }
+            }
+        };
+        Some(Sprites {
+            head: load("head")?,
+            body: load("body")?,
+            corner: load("corner")?,
+            tail: load("tail")?,
+            apple: load("apple")?,
+        })
+    }
+
+    // 右向きの画像をdirの方向に向ける回転角(時計回り)
+    pub fn rotation(dir: Direction) -> f32 {
+        match dir {
+            Direction::Right => 0.0,
+            Direction::Down => FRAC_PI_2,
+            Direction::Left => FRAC_PI_2 * 2.0,
+            Direction::Up => FRAC_PI_2 * 3.0,
+        }
+    }
+
+    // 胴体のセグメントの画像と回転角
+    // a, bはセグメントから前後のセグメントへの方向
+    pub fn body_piece(&self, a: Direction, b: Direction) -> (&graphics::Image, f32) {
+        // まっすぐなら左右か上下
+        if a == b.inverse() {
+            let rotation = match a {
+                Direction::Left | Direction::Right => 0.0,
+                Direction::Up | Direction::Down => FRAC_PI_2,
+            };
+            return (&self.body, rotation);
+        }
+        // 曲がり角の画像は左と下につながっているので、時計回りに回してa, bに合わせる
+        let corners = [
+            (Direction::Left, Direction::Down),
+            (Direction::Up, Direction::Left),
+            (Direction::Right, Direction::Up),
+            (Direction::Down, Direction::Right),
+        ];
+        let turns = corners
+            .iter()
+            .position(|&(c, d)| (c, d) == (a, b) || (c, d) == (b, a))
+            .unwrap_or(0);
+        (&self.corner, FRAC_PI_2 * turns as f32)
+    }
+
+    // imageをrectいっぱいに、中心でrotationだけ回して描画する
+    pub fn draw(
+        canvas: &mut graphics::Canvas,
+        image: &graphics::Image,
+        rect: graphics::Rect,
+        rotation: f32,
+        color: [f32; 4],
+    ) {
+        canvas.draw(
+            image,
+            graphics::DrawParam::new()
+                .dest([rect.x + rect.w / 2.0, rect.y + rect.h / 2.0])
+                .offset([0.5, 0.5])
+                .rotation(rotation)
+                .scale([
+                    rect.w / image.width() as f32,
+                    rect.h / image.height() as f32,
+                ])
+                .color(color),
+        );
+    }
+}
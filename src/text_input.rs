@@ -0,0 +1,46 @@
+// キーボードで1行の文字を入力する欄
+// 文字はtext_input_eventで受け取り、Backspaceで1文字消す
+#[derive(Clone, Debug, Default)]
+pub struct TextInput {
+    text: String,
+    // 入力できる最大の文字数
+    max_len: usize,
+}
+
+impl TextInput {
+    pub fn new(max_len: usize) -> Self {
+        TextInput {
+            text: String::new(),
+            max_len,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // 表示できる文字なら末尾に追加する(最大の文字数を超える分と制御文字は無視)
+    pub fn insert(&mut self, c: char) {
+        if !c.is_control() && self.text.chars().count() < self.max_len {
+            self.text.push(c);
+        }
+    }
+
+    // 最後の1文字を消す
+    pub fn backspace(&mut self) {
+        self.text.pop();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    // カーソルを付けた表示
+    pub fn line(&self) -> String {
+        format!("{}_", self.text)
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::config;
+use crate::food::FoodKind;
+
+// 盤面とHUDの色(RGBA)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub background: [f32; 4],
+    pub snake_head: [f32; 4],
+    pub snake_body: [f32; 4],
+    // 普通のfoodの色(valueが1, 2, 3以上)
+    pub food: [[f32; 4]; 3],
+    pub golden: [f32; 4],
+    pub poison: [f32; 4],
+    pub shrink: [f32; 4],
+    pub wandering: [f32; 4],
+    pub confusion: [f32; 4],
+    pub wall: [f32; 4],
+    // HUDの文字
+    pub hud: [f32; 4],
+    // 色だけでなく形でも見分けられるように描くか(foodを種類ごとの形にし、headを縁取る)
+    pub shapes: bool,
+}
+
+impl Theme {
+    // 色覚の違いがあっても見分けやすい色(Okabe-Itoの配色)と形で描くテーマ
+    pub fn colorblind() -> Theme {
+        Theme {
+            background: [0.0, 0.0, 0.0, 1.0],
+            snake_head: [0.0, 0.62, 0.45, 1.0],
+            snake_body: [0.34, 0.71, 0.91, 1.0],
+            food: [
+                [0.9, 0.6, 0.0, 1.0],
+                [0.95, 0.7, 0.2, 1.0],
+                [1.0, 0.8, 0.4, 1.0],
+            ],
+            golden: [0.94, 0.89, 0.26, 1.0],
+            poison: [0.8, 0.47, 0.65, 1.0],
+            shrink: [1.0, 1.0, 1.0, 1.0],
+            wandering: [0.84, 0.37, 0.0, 1.0],
+            confusion: [0.0, 0.45, 0.7, 1.0],
+            wall: [0.6, 0.6, 0.6, 1.0],
+            hud: [1.0, 1.0, 1.0, 1.0],
+            shapes: true,
+        }
+    }
+
+    // 種類ごとのfoodの色(普通のfoodはvalueが1の色)
+    pub fn food_color(&self, kind: FoodKind) -> [f32; 4] {
+        match kind {
+            FoodKind::Normal => self.food[0],
+            FoodKind::Golden => self.golden,
+            FoodKind::Poison => self.poison,
+            FoodKind::Shrink => self.shrink,
+            FoodKind::Wandering => self.wandering,
+            FoodKind::Confusion => self.confusion,
+        }
+    }
+
+    // 背景色を不透明にした色(背景色でマスを塗って穴を空ける時に使う)
+    pub fn background_fill(&self) -> [f32; 4] {
+        let [r, g, b, _] = self.background;
+        [r, g, b, 1.0]
+    }
+}
+
+// 設定で選べる組み込みのテーマ
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    // config.tomlのcolorsの色
+    #[default]
+    Classic,
+    // 昔の携帯電話の液晶のような緑
+    Nokia,
+    // 暗い灰色に落ち着いた色
+    Dark,
+    // 黒に蛍光色
+    Neon,
+}
+
+impl ThemeName {
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeName::Classic => "Classic",
+            ThemeName::Nokia => "Nokia",
+            ThemeName::Dark => "Dark",
+            ThemeName::Neon => "Neon",
+        }
+    }
+
+    // 次のテーマ(Tキーで順番に切り替える)
+    pub fn next(self) -> Self {
+        match self {
+            ThemeName::Classic => ThemeName::Nokia,
+            ThemeName::Nokia => ThemeName::Dark,
+            ThemeName::Dark => ThemeName::Neon,
+            ThemeName::Neon => ThemeName::Classic,
+        }
+    }
+
+    // テーマの色
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeName::Classic => {
+                let colors = &config().colors;
+                Theme {
+                    background: colors.background,
+                    snake_head: colors.snake_head,
+                    snake_body: colors.snake_body,
+                    // valueが大きいほど明るいブルー
+                    food: [
+                        [0.0, 0.0, 1.0, 1.0],
+                        [0.0, 0.5, 1.0, 1.0],
+                        [0.3, 0.8, 1.0, 1.0],
+                    ],
+                    golden: [1.0, 0.85, 0.0, 1.0],
+                    poison: [0.6, 0.0, 0.8, 1.0],
+                    shrink: [0.0, 1.0, 1.0, 1.0],
+                    wandering: [1.0, 0.4, 0.7, 1.0],
+                    confusion: [1.0, 0.5, 0.0, 1.0],
+                    wall: colors.wall,
+                    hud: [1.0, 1.0, 1.0, 1.0],
+                    shapes: false,
+                }
+            }
+            // 明るい緑の背景に濃い緑の3色だけ
+            ThemeName::Nokia => Theme {
+                background: [0.61, 0.74, 0.06, 1.0],
+                snake_head: [0.06, 0.22, 0.06, 1.0],
+                snake_body: [0.19, 0.38, 0.19, 1.0],
+                food: [
+                    [0.19, 0.38, 0.19, 1.0],
+                    [0.12, 0.3, 0.12, 1.0],
+                    [0.06, 0.22, 0.06, 1.0],
+                ],
+                golden: [0.35, 0.45, 0.05, 1.0],
+                poison: [0.06, 0.22, 0.06, 1.0],
+                shrink: [0.19, 0.38, 0.19, 1.0],
+                wandering: [0.12, 0.3, 0.12, 1.0],
+                confusion: [0.06, 0.22, 0.06, 1.0],
+                wall: [0.19, 0.38, 0.19, 1.0],
+                hud: [0.06, 0.22, 0.06, 1.0],
+                shapes: false,
+            },
+            ThemeName::Dark => Theme {
+                background: [0.07, 0.07, 0.09, 1.0],
+                snake_head: [0.85, 0.85, 0.85, 1.0],
+                snake_body: [0.4, 0.4, 0.45, 1.0],
+                food: [
+                    [0.35, 0.55, 0.9, 1.0],
+                    [0.45, 0.65, 1.0, 1.0],
+                    [0.6, 0.8, 1.0, 1.0],
+                ],
+                golden: [0.9, 0.75, 0.2, 1.0],
+                poison: [0.55, 0.2, 0.65, 1.0],
+                shrink: [0.3, 0.75, 0.75, 1.0],
+                wandering: [0.85, 0.45, 0.6, 1.0],
+                confusion: [0.75, 0.55, 0.3, 1.0],
+                wall: [0.3, 0.3, 0.33, 1.0],
+                hud: [0.85, 0.85, 0.85, 1.0],
+                shapes: false,
+            },
+            ThemeName::Neon => Theme {
+                background: [0.02, 0.0, 0.06, 1.0],
+                snake_head: [0.0, 1.0, 0.6, 1.0],
+                snake_body: [0.0, 0.6, 0.9, 1.0],
+                food: [
+                    [1.0, 0.0, 0.8, 1.0],
+                    [1.0, 0.3, 0.9, 1.0],
+                    [1.0, 0.6, 1.0, 1.0],
+                ],
+                golden: [1.0, 1.0, 0.0, 1.0],
+                poison: [0.6, 0.0, 1.0, 1.0],
+                shrink: [0.0, 1.0, 1.0, 1.0],
+                wandering: [1.0, 0.5, 0.0, 1.0],
+                confusion: [0.5, 1.0, 0.0, 1.0],
+                wall: [0.2, 0.2, 1.0, 1.0],
+                hud: [0.0, 1.0, 1.0, 1.0],
+                shapes: false,
+            },
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::event::GameEvent;
+use crate::grid::Direction;
+
+// チュートリアルの1つの手順で、プレイヤーがすること
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TutorialGoal {
+    // ゲームを止めてdirの方向キーが押されるのを待つ
+    Turn(Direction),
+    // ゲームを進めてtick数だけ待つ
+    Move(u32),
+    // ゲームを進めてfoodを食べるのを待つ
+    EatFood,
+}
+
+// チュートリアルの手順(表示する文と、次の手順に進む条件)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TutorialStep {
+    pub prompt: &'static str,
+    pub goal: TutorialGoal,
+}
+
+// チュートリアルの手順の一覧(蛇は右向きで始まる)
+pub const TUTORIAL_STEPS: [TutorialStep; 5] = [
+    TutorialStep {
+        prompt: "Press \u{2193} to turn",
+        goal: TutorialGoal::Turn(Direction::Down),
+    },
+    TutorialStep {
+        prompt: "Nice!",
+        goal: TutorialGoal::Move(3),
+    },
+    TutorialStep {
+        prompt: "Press \u{2192} to turn",
+        goal: TutorialGoal::Turn(Direction::Right),
+    },
+    TutorialStep {
+        prompt: "Eat the food to grow",
+        goal: TutorialGoal::EatFood,
+    },
+    TutorialStep {
+        prompt: "Don't bite your own tail. Have fun!",
+        goal: TutorialGoal::Move(20),
+    },
+];
+
+// 初めて遊ぶ時のチュートリアルの進み具合
+// GameStateができごととtickを渡し、手順の条件を満たしたら次の手順に進む
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tutorial {
+    // 今の手順(TUTORIAL_STEPSの添字)
+    step: usize,
+    // 今の手順になってから進んだtick数
+    ticks: u32,
+}
+
+impl Tutorial {
+    // 今の手順(全て終わったらNone)
+    pub fn current(&self) -> Option<&'static TutorialStep> {
+        TUTORIAL_STEPS.get(self.step)
+    }
+
+    // ゲームを止めて方向キーを待っているならその方向
+    pub fn waiting_for(&self) -> Option<Direction> {
+        match self.current()?.goal {
+            TutorialGoal::Turn(dir) => Some(dir),
+            TutorialGoal::Move(_) | TutorialGoal::EatFood => None,
+        }
+    }
+
+    // 全ての手順が終わったか
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+
+    // 1人目の蛇のできごとで手順を進める
+    pub fn on_event(&mut self, event: &GameEvent) {
+        let Some(step) = self.current() else {
+            return;
+        };
+        let done = match (step.goal, *event) {
+            (TutorialGoal::Turn(goal), GameEvent::SnakeTurned { snake: 0, dir }) => dir == goal,
+            (TutorialGoal::EatFood, GameEvent::FoodEaten { snake: 0, .. }) => true,
+            _ => false,
+        };
+        if done {
+            self.advance();
+        }
+    }
+
+    // ゲームが1tick進んだ
+    pub fn on_tick(&mut self) {
+        self.ticks += 1;
+        if let Some(TutorialGoal::Move(ticks)) = self.current().map(|step| step.goal) {
+            if self.ticks >= ticks {
+                self.advance();
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        self.step += 1;
+        self.ticks = 0;
+    }
+}
@@ -0,0 +1,67 @@
+// ランダムな入力を大量にSimulationに与えて、どのtickでも成り立つはずの性質を確かめる
+
+use ggez_tutorial::grid::{Direction, GridPosition};
+use ggez_tutorial::settings::{Difficulty, Settings};
+use ggez_tutorial::simulation::Simulation;
+use ggez_tutorial::snake::Ate;
+use oorandom::Rand32;
+
+// 試す入力の列の数と、1つの列のtick数
+const RUNS: u64 = 2000;
+const TICKS: u32 = 150;
+
+// 何もしないtickも混ぜたランダムな入力
+fn random_input(rng: &mut Rand32) -> Option<Direction> {
+    let roll = rng.rand_range(0..8) as usize;
+    Direction::ALL.get(roll).copied()
+}
+
+#[test]
+fn random_inputs_keep_the_snake_consistent() {
+    for run in 0..RUNS {
+        let difficulty = Difficulty::ALL[run as usize % Difficulty::ALL.len()];
+        let settings = Settings {
+            seed: Some(run),
+            ..Settings::default()
+        };
+        let mut simulation = Simulation::new(settings, difficulty, run as usize);
+        let board = simulation.board();
+        let mut rng = Rand32::new(run ^ 0x5eed);
+        for _ in 0..TICKS {
+            let before_len = simulation.snake().segments().count();
+            let before_growth = simulation.snake().pending_growth;
+            let before_lives = simulation.lives();
+            let running = simulation.step(random_input(&mut rng));
+            let snake = simulation.snake();
+            let segments: Vec<GridPosition> = snake.segments().collect();
+            let context = format!("seed {} tick {}", run, simulation.tick());
+
+            // 全てのマスが盤面の中にある
+            assert!(
+                segments.iter().all(|pos| board.contains(*pos)),
+                "out of bounds: {}",
+                context
+            );
+            // 隣り合うセグメントは1マスずつ離れている(組み込みのステージにワープマスはない)
+            assert!(
+                segments.windows(2).all(|w| w[0].distance(w[1], board) == 1),
+                "body is not contiguous: {}",
+                context
+            );
+            // 伸びるのは食べて伸びる分が残っている時だけで、1tickに1マスまで
+            if segments.len() > before_len {
+                assert!(before_growth > 0, "grew without food: {}", context);
+                assert_eq!(segments.len(), before_len + 1, "{}", context);
+            }
+            // headがbodyに重なっているならAte::Itselfになっている
+            if segments[1..].contains(&segments[0]) {
+                assert_eq!(snake.ate, Some(Ate::Itself), "{}", context);
+            }
+            // 残機は減るだけ
+            assert!(simulation.lives() <= before_lives, "{}", context);
+            if !running {
+                break;
+            }
+        }
+    }
+}